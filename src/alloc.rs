@@ -0,0 +1,207 @@
+//! Counting of heap allocations.
+//!
+//! This module provides [`CountingAllocator`](struct.CountingAllocator.html), a wrapper around a
+//! global allocator that counts the allocations made by the program, and the
+//! [`Allocations`](struct.Allocations.html) measurement, which reports the number of allocations
+//! and the number of bytes allocated per iteration. Allocation counts go through the same
+//! statistical analysis and regression detection as timings.
+//!
+//! The counting allocator has to be installed as the global allocator of the benchmark binary.
+//! `Allocations` is usually combined with a time measurement:
+//!
+//! ```no_run
+//! use criterion::alloc::{Allocations, CountingAllocator};
+//! use criterion::measurement::WallTime;
+//! use criterion::Criterion;
+//! use std::alloc::System;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator<System> = CountingAllocator::new(System);
+//!
+//! fn config() -> Criterion<(WallTime, Allocations)> {
+//!     Criterion::default().with_measurement((WallTime, Allocations))
+//! }
+//! ```
+
+use crate::measurement::{CountFormatter, Measurement, ValueFormatter};
+use crate::Throughput;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator which counts the allocations made through it and forwards them to the
+/// wrapped allocator.
+///
+/// Allocations from all threads are counted. Reallocations are counted as one allocation of the
+/// new size; deallocations are not counted.
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+impl<A> CountingAllocator<A> {
+    /// Wrap the given allocator.
+    pub const fn new(inner: A) -> CountingAllocator<A> {
+        CountingAllocator { inner }
+    }
+}
+
+fn record(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES.fetch_add(size, Ordering::Relaxed);
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        self.inner.realloc(ptr, layout, new_size)
+    }
+}
+
+/// The number of allocations and allocated bytes, as counted by
+/// [`CountingAllocator`](struct.CountingAllocator.html).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationCount {
+    /// The number of allocations.
+    pub allocations: usize,
+    /// The number of bytes allocated.
+    pub bytes: usize,
+}
+impl AllocationCount {
+    /// Return the number of allocations counted so far by all counting allocators.
+    pub fn now() -> AllocationCount {
+        AllocationCount {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            bytes: BYTES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `Allocations` measures the heap allocations made by the benchmark. It records two metrics, the
+/// number of allocations ("allocations") and the number of bytes allocated ("bytes").
+///
+/// The allocations are counted by [`CountingAllocator`](struct.CountingAllocator.html), which must
+/// be installed as the global allocator; otherwise both metrics are always zero. Since
+/// allocations are counted for the whole process, allocations made by other threads while the
+/// benchmark is running are included.
+pub struct Allocations;
+impl Measurement for Allocations {
+    type Intermediate = AllocationCount;
+    type Value = AllocationCount;
+
+    fn start(&self) -> Self::Intermediate {
+        AllocationCount::now()
+    }
+    fn end(&self, i: Self::Intermediate) -> Self::Value {
+        let now = AllocationCount::now();
+        AllocationCount {
+            allocations: now.allocations.wrapping_sub(i.allocations),
+            bytes: now.bytes.wrapping_sub(i.bytes),
+        }
+    }
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        AllocationCount {
+            allocations: v1.allocations + v2.allocations,
+            bytes: v1.bytes + v2.bytes,
+        }
+    }
+    fn zero(&self) -> Self::Value {
+        AllocationCount::default()
+    }
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        value.allocations as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &ALLOCATIONS_FORMATTER
+    }
+    fn allows_zero(&self) -> bool {
+        true
+    }
+    fn metric_count(&self) -> usize {
+        2
+    }
+    fn metric_name(&self, index: usize) -> &str {
+        match index {
+            0 => "allocations",
+            _ => "bytes",
+        }
+    }
+    fn metric_to_f64(&self, value: &Self::Value, index: usize) -> f64 {
+        match index {
+            0 => value.allocations as f64,
+            _ => value.bytes as f64,
+        }
+    }
+    fn metric_formatter(&self, index: usize) -> &dyn ValueFormatter {
+        match index {
+            0 => &ALLOCATIONS_FORMATTER,
+            _ => &BytesFormatter,
+        }
+    }
+}
+
+const ALLOCATIONS_FORMATTER: CountFormatter = CountFormatter {
+    units: ["allocs", "Kallocs", "Mallocs", "Gallocs"],
+    throughput_units: ["B/alloc", "B/alloc", "elem/alloc"],
+    machine_unit: "allocations",
+};
+
+/// Formats amounts of allocated memory using binary prefixes.
+struct BytesFormatter;
+impl ValueFormatter for BytesFormatter {
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (amount, unit) = match *throughput {
+            Throughput::Bytes(bytes) | Throughput::BytesDecimal(bytes) => (bytes as f64, "B/B"),
+            Throughput::Elements(elems) => (elems as f64, "elem/B"),
+        };
+
+        for val in values {
+            *val = amount / *val;
+        }
+
+        unit
+    }
+
+    fn scale_values(&self, bytes: f64, values: &mut [f64]) -> &'static str {
+        let (denominator, unit) = if bytes < 1024.0 {
+            (1.0, "  B")
+        } else if bytes < 1024.0 * 1024.0 {
+            (1024.0, "KiB")
+        } else if bytes < 1024.0 * 1024.0 * 1024.0 {
+            (1024.0 * 1024.0, "MiB")
+        } else {
+            (1024.0 * 1024.0 * 1024.0, "GiB")
+        };
+
+        for val in values {
+            *val /= denominator;
+        }
+
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        // no scaling is needed
+        "bytes"
+    }
+}
@@ -3,13 +3,14 @@ use crate::stats::univariate::{self, mixed};
 use crate::stats::Distribution;
 
 use crate::benchmark::BenchmarkConfig;
+use crate::environment::Environment;
 use crate::error::Result;
 use crate::estimate::{
     build_change_estimates, ChangeDistributions, ChangeEstimates, ChangePointEstimates, Estimates,
 };
 use crate::measurement::Measurement;
 use crate::report::BenchmarkId;
-use crate::{fs, Criterion, SavedSample};
+use crate::{fs, ComparisonTest, Criterion, SavedSample};
 
 // Common comparison procedure
 #[cfg_attr(feature = "cargo-clippy", allow(clippy::type_complexity))]
@@ -33,7 +34,41 @@ pub(crate) fn common<M: Measurement>(
     sample_file.push(&criterion.baseline_directory);
     sample_file.push("sample.json");
     let sample: SavedSample = fs::load(&sample_file)?;
-    let SavedSample { iters, times, .. } = sample;
+    let SavedSample {
+        iters, times, cpu, ..
+    } = sample;
+    if cpu != criterion.cpu {
+        let describe = |cpu: Option<usize>| match cpu {
+            Some(cpu) => format!("pinned to CPU {}", cpu),
+            None => "unpinned".to_owned(),
+        };
+        eprintln!(
+            "Warning: {} was measured {} in the baseline, but {} now; the comparison may be unreliable.",
+            id.as_title(),
+            describe(cpu),
+            describe(criterion.cpu)
+        );
+    }
+
+    // Baselines saved before the environment was recorded have no snapshot
+    let mut environment_file = criterion.output_directory.clone();
+    environment_file.push(id.as_directory_name());
+    environment_file.push(&criterion.baseline_directory);
+    environment_file.push("environment.json");
+    if environment_file.exists() {
+        let base_environment: Environment = fs::load(&environment_file)?;
+        let differences =
+            base_environment.differences(&Environment::snapshot(&criterion.environment_root));
+        if !differences.is_empty() {
+            eprintln!(
+                "Warning: {} is compared against a baseline measured in a different environment; the comparison may be unreliable.",
+                id.as_title()
+            );
+            for difference in differences {
+                eprintln!("  - {}", difference);
+            }
+        }
+    }
 
     let mut estimates_file = criterion.output_directory.clone();
     estimates_file.push(id.as_directory_name());
@@ -52,13 +87,13 @@ pub(crate) fn common<M: Measurement>(
     change_dir.push(id.as_directory_name());
     change_dir.push("change");
     fs::mkdirp(&change_dir)?;
-    let (t_statistic, t_distribution) = t_test(avg_times, base_avg_time_sample, config);
+    let (statistic, distribution) = test(avg_times, base_avg_time_sample, config);
 
     let (estimates, relative_distributions) =
         estimates(id, avg_times, base_avg_time_sample, config, criterion);
     Ok((
-        t_statistic,
-        t_distribution,
+        statistic,
+        distribution,
         estimates,
         relative_distributions,
         iters,
@@ -68,25 +103,35 @@ pub(crate) fn common<M: Measurement>(
     ))
 }
 
-// Performs a two sample t-test
-fn t_test(
+// Performs the two sample test selected by the configuration, returning its statistic and the
+// distribution of the statistic under the null hypothesis
+fn test(
     avg_times: &Sample<f64>,
     base_avg_times: &Sample<f64>,
     config: &BenchmarkConfig,
 ) -> (f64, Distribution<f64>) {
+    let statistic: fn(&Sample<f64>, &Sample<f64>) -> f64 = match config.comparison_test {
+        ComparisonTest::Welch => |a, b| a.t(b),
+        ComparisonTest::MannWhitney => mann_whitney_z,
+        ComparisonTest::MedianDifference => {
+            |a, b| relative(a.percentiles().median(), b.percentiles().median())
+        }
+    };
     let nresamples = config.nresamples;
 
-    let t_statistic = avg_times.t(base_avg_times);
-    let t_distribution = elapsed!(
-        "Bootstrapping the T distribution",
-        mixed::bootstrap(avg_times, base_avg_times, nresamples, |a, b| (a.t(b),))
+    let observed = statistic(avg_times, base_avg_times);
+    let distribution = elapsed!(
+        "Bootstrapping the distribution of the test statistic",
+        mixed::bootstrap(avg_times, base_avg_times, nresamples, |a, b| (statistic(
+            a, b
+        ),))
     )
     .0;
 
     // HACK: Filter out non-finite numbers, which can happen sometimes when sample size is very small.
     // Downstream code doesn't like non-finite values here.
-    let t_distribution = Distribution::from(
-        t_distribution
+    let distribution = Distribution::from(
+        distribution
             .iter()
             .filter(|a| a.is_finite())
             .cloned()
@@ -94,7 +139,52 @@ fn t_test(
             .into_boxed_slice(),
     );
 
-    (t_statistic, t_distribution)
+    (observed, distribution)
+}
+
+// The Mann-Whitney U statistic of the first sample, standardized with its mean and variance under
+// the null hypothesis (corrected for ties), so that its scale doesn't depend on the sample sizes
+fn mann_whitney_z(a: &Sample<f64>, b: &Sample<f64>) -> f64 {
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let n = n_a + n_b;
+
+    let mut values = a
+        .iter()
+        .map(|&value| (value, true))
+        .chain(b.iter().map(|&value| (value, false)))
+        .collect::<Vec<_>>();
+    values.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+    // Tied values share the average of their ranks
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < values.len() {
+        let end = start
+            + values[start..]
+                .iter()
+                .take_while(|(value, _)| *value == values[start].0)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let count = (end - start) as f64;
+        let in_a = values[start..end].iter().filter(|(_, in_a)| *in_a).count();
+        rank_sum += rank * in_a as f64;
+        ties += count.powi(3) - count;
+        start = end;
+    }
+
+    let u = rank_sum - n_a * (n_a + 1.0) / 2.0;
+    let variance = n_a * n_b / 12.0 * (n + 1.0 - ties / (n * (n - 1.0)));
+    (u - n_a * n_b / 2.0) / variance.sqrt()
+}
+
+// Equal values count as no change, even when both are zero (eg. no allocations at all)
+fn relative(a: f64, b: f64) -> f64 {
+    if a == b {
+        0.
+    } else {
+        a / b - 1.
+    }
 }
 
 // Estimates the relative change in the statistics of the population
@@ -107,8 +197,8 @@ fn estimates<M: Measurement>(
 ) -> (ChangeEstimates, ChangeDistributions) {
     fn stats(a: &Sample<f64>, b: &Sample<f64>) -> (f64, f64) {
         (
-            a.mean() / b.mean() - 1.,
-            a.percentiles().median() / b.percentiles().median() - 1.,
+            relative(a.mean(), b.mean()),
+            relative(a.percentiles().median(), b.percentiles().median()),
         )
     }
 
@@ -141,3 +231,32 @@ fn estimates<M: Measurement>(
     }
     (estimates, distributions)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mann_whitney_separated() {
+        // U = 0, with a mean of 4.5 and a variance of 5.25 under the null hypothesis
+        let z = mann_whitney_z(Sample::new(&[1.0, 2.0, 3.0]), Sample::new(&[4.0, 5.0, 6.0]));
+        assert!((z + 4.5 / 5.25f64.sqrt()).abs() < 1e-12);
+
+        let z = mann_whitney_z(Sample::new(&[4.0, 5.0, 6.0]), Sample::new(&[1.0, 2.0, 3.0]));
+        assert!((z - 4.5 / 5.25f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mann_whitney_ties() {
+        // The tied values share the ranks 2 to 5, so U = 1 + 2 * 3.5 - 6 = 2, and the ties reduce
+        // the variance to 9 / 12 * (7 - 60 / 30) = 3.75
+        let z = mann_whitney_z(Sample::new(&[1.0, 2.0, 2.0]), Sample::new(&[2.0, 2.0, 3.0]));
+        assert!((z + 2.5 / 3.75f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mann_whitney_identical() {
+        let z = mann_whitney_z(Sample::new(&[1.0, 2.0, 3.0]), Sample::new(&[3.0, 2.0, 1.0]));
+        assert_eq!(z, 0.0);
+    }
+}
@@ -8,14 +8,21 @@ use crate::stats::{Distribution, Tails};
 
 use crate::benchmark::BenchmarkConfig;
 use crate::connection::OutgoingMessage;
+use crate::environment::Environment;
+use crate::error::Error;
 use crate::estimate::{
-    build_estimates, ConfidenceInterval, Distributions, Estimate, Estimates, PointEstimates,
+    build_estimates, ConfidenceInterval, Distributions, Estimate, Estimates, PercentileEstimates,
+    PointEstimates, Repetitions,
 };
 use crate::fs;
+use crate::isolation;
 use crate::measurement::Measurement;
-use crate::report::{BenchmarkId, Report, ReportContext};
-use crate::routine::Routine;
-use crate::{Baseline, Criterion, SavedSample, Throughput};
+use crate::report::{BenchmarkId, MeasurementData, MetricData, Report, ReportContext, WarmUp};
+use crate::routine::{Measurements, Routine};
+use crate::scheduling;
+use crate::stats::rand_util;
+use crate::timeout::Timer;
+use crate::{ActualSamplingMode, Baseline, Criterion, Interleaving, SavedSample, Throughput};
 
 macro_rules! elapsed {
     ($msg:expr, $block:expr) => {{
@@ -35,7 +42,7 @@ macro_rules! elapsed {
 
 mod compare;
 
-// Common analysis procedure
+// Common analysis procedure. The benchmark must have been started with `start` first.
 pub(crate) fn common<M: Measurement, T: ?Sized>(
     id: &BenchmarkId,
     routine: &mut dyn Routine<M, T>,
@@ -45,60 +52,79 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
     parameter: &T,
     throughput: Option<Throughput>,
 ) {
-    criterion.report.benchmark_start(id, report_context);
-
-    if let Baseline::CompareStrict = criterion.baseline {
-        if !base_dir_exists(
-            id,
-            &criterion.baseline_directory,
-            &criterion.output_directory,
-        ) {
-            panic!(
-                "Baseline '{base}' must exist before comparison is allowed; try --save-baseline {base}",
-                base=criterion.baseline_directory,
-            );
-        }
-    }
-
-    let (sampling_mode, iters, times);
+    let mut repetitions = vec![];
+    let measurements;
     if let Some(baseline) = &criterion.load_baseline {
-        let mut sample_path = criterion.output_directory.clone();
-        sample_path.push(id.as_directory_name());
-        sample_path.push(baseline);
-        sample_path.push("sample.json");
-        let loaded = fs::load::<SavedSample, _>(&sample_path);
-
-        match loaded {
+        let load = |id: &BenchmarkId| {
+            let mut sample_path = criterion.output_directory.clone();
+            sample_path.push(id.as_directory_name());
+            sample_path.push(baseline);
+            sample_path.push("sample.json");
+            fs::load::<SavedSample, _>(&sample_path)
+        };
+
+        match load(id) {
             Err(err) => panic!(
                 "Baseline '{base}' must exist before it can be loaded; try --save-baseline {base}. Error: {err}",
                 base = baseline, err = err
             ),
             Ok(samples) => {
-                sampling_mode = samples.sampling_mode;
-                iters = samples.iters.into_boxed_slice();
-                times = samples.times.into_boxed_slice();
+                // Secondary metrics are only analyzed if all of them were saved with the baseline.
+                let metrics = metric_ids(id, criterion)
+                    .iter()
+                    .map(|metric_id| load(metric_id).map(|sample| sample.times.into_boxed_slice()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap_or_default();
+
+                measurements = Measurements {
+                    sampling_mode: samples.sampling_mode,
+                    iters: samples.iters.into_boxed_slice(),
+                    times: samples.times.into_boxed_slice(),
+                    metrics,
+                    overhead: vec![],
+                    latencies: None,
+                    warm_up: None,
+                };
             }
         }
     } else {
-        let sample = routine.sample(
-            &criterion.measurement,
-            id,
-            config,
-            criterion,
-            report_context,
-            parameter,
-        );
-        sampling_mode = sample.0;
-        iters = sample.1;
-        times = sample.2;
+        // Every repetition has the whole timeout to itself
+        let mut sample = || {
+            let _scheduling = scheduling::apply(criterion.cpu, criterion.priority);
+            Timer::new(id, config).run(|| {
+                routine.sample(
+                    &criterion.measurement,
+                    id,
+                    config,
+                    criterion,
+                    report_context,
+                    parameter,
+                )
+            })
+        };
 
+        // Isolated child processes run a single repetition for the parent process, and
+        // cargo-criterion does its own analysis
+        if isolation::child_benchmark_id().is_none() && criterion.connection.is_none() {
+            for _ in 1..config.repetitions {
+                repetitions.push(repetition_estimates(&sample(), config));
+            }
+        }
+        measurements = sample();
+
+        if isolation::child_benchmark_id().is_some() {
+            isolation::send_to_parent(measurements, routine.threads());
+        }
+
+        // The messages of cargo-criterion have no room for the secondary metrics of a composite
+        // measurement, so only the primary one is sent
         if let Some(conn) = &criterion.connection {
             conn.send(&OutgoingMessage::MeasurementComplete {
                 id: id.into(),
-                iters: &iters,
-                times: &times,
+                iters: &measurements.iters,
+                times: &measurements.times,
                 plot_config: (&report_context.plot_config).into(),
-                sampling_method: sampling_mode.into(),
+                sampling_method: measurements.sampling_mode.into(),
                 benchmark_config: config.into(),
             })
             .unwrap();
@@ -109,9 +135,245 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
         }
     }
 
+    analyze_measurements(
+        id,
+        routine.threads(),
+        measurements,
+        repetitions,
+        config,
+        criterion,
+        report_context,
+        throughput,
+    );
+}
+
+// Isolated analysis procedure. The benchmark is sampled in a child process, so that it cannot
+// be affected by the state left behind by other benchmarks, and is then analyzed here.
+pub(crate) fn isolated<M: Measurement>(
+    id: &BenchmarkId,
+    config: &BenchmarkConfig,
+    criterion: &Criterion<M>,
+    report_context: &ReportContext,
+    throughput: Option<Throughput>,
+) {
+    start(id, criterion, report_context);
+
+    // A child process which panics or crashes fails the benchmark
+    let sample = || match isolation::sample_in_child(id, config) {
+        Ok(measurements) => Some(measurements),
+        Err(err) => {
+            let message = match err {
+                Error::IsolatedProcessError { reason, .. } => reason,
+                err => err.to_string(),
+            };
+            criterion.benchmark_failed(id, report_context, &message);
+            None
+        }
+    };
+
+    let mut repetitions = vec![];
+    for _ in 1..config.repetitions {
+        let (measurements, _) = match sample() {
+            Some(sampled) => sampled,
+            None => return,
+        };
+        repetitions.push(repetition_estimates(&measurements, config));
+    }
+    let (measurements, threads) = match sample() {
+        Some(sampled) => sampled,
+        None => return,
+    };
+    analyze_measurements(
+        id,
+        threads,
+        measurements,
+        repetitions,
+        config,
+        criterion,
+        report_context,
+        throughput,
+    );
+}
+
+/// A benchmark which runs after it was defined, because its samples are collected interleaved
+/// with those of other benchmarks or because the benchmarks are shuffled.
+pub(crate) struct DeferredBenchmark<'a, M: Measurement> {
+    pub(crate) id: BenchmarkId,
+    pub(crate) routine: Box<dyn Routine<M, ()> + 'a>,
+    pub(crate) config: BenchmarkConfig,
+    pub(crate) report_context: ReportContext,
+    pub(crate) throughput: Option<Throughput>,
+}
+
+// Interleaved analysis procedure. The benchmarks are warmed up one after another, then one sample
+// of each benchmark is collected in turn, so that drift over time (eg. thermal throttling or
+// background load) affects all of them alike. Each benchmark is analyzed separately afterwards.
+pub(crate) fn interleaved<M: Measurement>(
+    benchmarks: &mut [DeferredBenchmark<'_, M>],
+    order: Interleaving,
+    criterion: &Criterion<M>,
+) {
+    let measurement = &criterion.measurement;
+    let scheduling = scheduling::apply(criterion.cpu, criterion.priority);
+
+    // The timeout of each benchmark covers its warm-up and the time spent on its own samples
+    let mut timers = benchmarks
+        .iter()
+        .map(|benchmark| Timer::new(&benchmark.id, &benchmark.config))
+        .collect::<Vec<_>>();
+
+    // A benchmark which panics or times out is reported as failed and left out of the remaining
+    // rounds
+    let mut samples = benchmarks
+        .iter_mut()
+        .zip(timers.iter_mut())
+        .map(|(benchmark, timer)| {
+            start(&benchmark.id, criterion, &benchmark.report_context);
+            let routine = &mut benchmark.routine;
+            let (id, config, report_context) =
+                (&benchmark.id, &benchmark.config, &benchmark.report_context);
+            criterion.catch_failure(id, report_context, || {
+                timer.run(|| routine.plan(measurement, id, config, criterion, report_context, &()))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let rounds = samples
+        .iter()
+        .flatten()
+        .map(|s| s.iters.len())
+        .max()
+        .unwrap_or(0);
+    let mut indices = (0..benchmarks.len()).collect::<Vec<_>>();
+    let mut rng = rand_util::new_rng();
+    for round in 0..rounds {
+        if let Interleaving::Random = order {
+            rand_util::shuffle(&mut rng, &mut indices);
+        }
+        for &index in &indices {
+            let (benchmark, slot) = (&mut benchmarks[index], &mut samples[index]);
+            let iters = match slot.as_ref().and_then(|samples| samples.iters.get(round)) {
+                Some(&iters) => iters,
+                None => continue,
+            };
+            let routine = &mut benchmark.routine;
+            let config = &benchmark.config;
+            let timer = &mut timers[index];
+            let sampled = criterion.catch_failure(&benchmark.id, &benchmark.report_context, || {
+                timer.run(|| routine.bench_samples(measurement, &[iters], &(), config))
+            });
+            match (sampled, slot.as_mut()) {
+                (Some((values, histogram)), Some(samples)) => {
+                    samples.values.extend(values);
+                    samples.histogram.merge(&histogram);
+                }
+                _ => *slot = None,
+            }
+        }
+    }
+
+    drop(scheduling);
+
+    for (benchmark, samples) in benchmarks.iter_mut().zip(samples) {
+        let samples = match samples {
+            Some(samples) => samples,
+            None => continue,
+        };
+        let measurements = benchmark.routine.measurements(
+            measurement,
+            &benchmark.id,
+            &benchmark.config,
+            samples,
+            &(),
+        );
+        analyze_measurements(
+            &benchmark.id,
+            benchmark.routine.threads(),
+            measurements,
+            vec![],
+            &benchmark.config,
+            criterion,
+            &benchmark.report_context,
+            benchmark.throughput.clone(),
+        );
+    }
+}
+
+// Reports the start of a benchmark, warns about a noisy environment and checks that the baseline
+// to compare against exists. A missing baseline is a configuration error rather than a failure of
+// the benchmark, so it panics outside of `Criterion::catch_failure`.
+pub(crate) fn start<M: Measurement>(
+    id: &BenchmarkId,
+    criterion: &Criterion<M>,
+    report_context: &ReportContext,
+) {
+    criterion.report.benchmark_start(id, report_context);
+
+    // The parent process has already warned about the same environment
+    if isolation::child_benchmark_id().is_none() {
+        Environment::snapshot(&criterion.environment_root).warn_once();
+    }
+
+    if let Baseline::CompareStrict = criterion.baseline {
+        if !base_dir_exists(
+            id,
+            &criterion.baseline_directory,
+            &criterion.output_directory,
+        ) {
+            panic!(
+                "Baseline '{base}' must exist before comparison is allowed; try --save-baseline {base}",
+                base=criterion.baseline_directory,
+            );
+        }
+    }
+}
+
+// The IDs of the secondary metrics of a composite measurement
+fn metric_ids<M: Measurement>(id: &BenchmarkId, criterion: &Criterion<M>) -> Vec<BenchmarkId> {
+    (1..criterion.measurement.metric_count())
+        .map(|index| id.metric(criterion.measurement.metric_name(index)))
+        .collect()
+}
+
+// Estimates the statistics of one of the earlier repetitions of a benchmark, which are neither
+// reported nor saved on their own
+fn repetition_estimates(measurements: &Measurements, config: &BenchmarkConfig) -> Estimates {
+    let avg_times = average(&measurements.iters, &measurements.times);
+    let (_, mut estimates) = estimates(Sample::new(&avg_times), config);
+    if measurements.sampling_mode.is_linear() {
+        let data = Data::new(&measurements.iters, &measurements.times);
+        estimates.slope = Some(regression(&data, config).1);
+    }
+    estimates
+}
+
+// Analyzes, reports and saves the measurements of a benchmark. The estimates of the earlier
+// repetitions, if any, are combined with those of these measurements, which are the last one.
+#[allow(clippy::too_many_arguments)]
+fn analyze_measurements<M: Measurement>(
+    id: &BenchmarkId,
+    threads: Option<usize>,
+    measurements: Measurements,
+    mut repetitions: Vec<Estimates>,
+    config: &BenchmarkConfig,
+    criterion: &Criterion<M>,
+    report_context: &ReportContext,
+    throughput: Option<Throughput>,
+) {
     criterion.report.analysis(id, report_context);
 
-    if times.iter().any(|&f| f == 0.0) {
+    let metric_ids = metric_ids(id, criterion);
+    let Measurements {
+        sampling_mode,
+        iters,
+        times,
+        metrics,
+        overhead,
+        latencies,
+        warm_up,
+    } = measurements;
+
+    if !criterion.measurement.allows_zero() && times.iter().any(|&f| f == 0.0) {
         error!(
             "At least one measurement of benchmark {} took zero time per \
             iteration. This should not be possible. If using iter_custom, please verify \
@@ -121,12 +383,143 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
         return;
     }
 
-    let avg_times = iters
+    let avg_times = average(&iters, &times);
+    let metric_avg_times = metrics
+        .iter()
+        .map(|values| average(&iters, values))
+        .collect::<Vec<_>>();
+
+    let percentiles = latencies.map(|latencies| {
+        latencies.histogram.percentiles(
+            latencies.timer_overhead,
+            config.confidence_level,
+            config.nresamples,
+        )
+    });
+    let mut measurement_data = analyze(
+        id,
+        sampling_mode,
+        &iters,
+        &times,
+        &avg_times,
+        percentiles,
+        config,
+        criterion,
+    );
+    measurement_data.throughput = match threads {
+        Some(threads) => Some(parallel_throughput(throughput, threads)),
+        None => throughput,
+    };
+    measurement_data.overhead = overhead.first().cloned().flatten();
+    measurement_data.warm_up = warm_up.map(|length| WarmUp {
+        length,
+        max_length: config.max_warm_up_time.max(config.warm_up_time).as_nanos() as f64,
+    });
+    if !repetitions.is_empty() {
+        repetitions.push(measurement_data.absolute_estimates.clone());
+        measurement_data.repetitions = Some(Repetitions::new(repetitions));
+    }
+    measurement_data.metrics = metric_ids
+        .iter()
+        .zip(metrics.iter().zip(metric_avg_times.iter()))
+        .enumerate()
+        .map(|(i, (metric_id, (values, avg_values)))| {
+            let mut measurements = analyze(
+                metric_id,
+                sampling_mode,
+                &iters,
+                values,
+                avg_values,
+                None,
+                config,
+                criterion,
+            );
+            measurements.overhead = overhead.get(i + 1).cloned().flatten();
+
+            MetricData {
+                name: criterion.measurement.metric_name(i + 1),
+                id: metric_id.clone(),
+                formatter: criterion.measurement.metric_formatter(i + 1),
+                measurements,
+            }
+        })
+        .collect();
+
+    criterion.report.measurement_complete(
+        id,
+        report_context,
+        &measurement_data,
+        criterion.measurement.formatter(),
+    );
+
+    if criterion.should_save_baseline() {
+        log_if_err!({
+            let mut benchmark_file = criterion.output_directory.clone();
+            benchmark_file.push(id.as_directory_name());
+            benchmark_file.push("new");
+            benchmark_file.push("benchmark.json");
+            // The throughput of `iter_parallel` covers all of its threads
+            let mut id = id.clone();
+            id.throughput = measurement_data.throughput.clone();
+            fs::save(&id, &benchmark_file)
+        });
+        log_if_err!({
+            let mut environment_file = criterion.output_directory.clone();
+            environment_file.push(id.as_directory_name());
+            environment_file.push("new");
+            environment_file.push("environment.json");
+            fs::save(
+                &Environment::snapshot(&criterion.environment_root),
+                &environment_file,
+            )
+        });
+        let mut repetitions_file = criterion.output_directory.clone();
+        repetitions_file.push(id.as_directory_name());
+        repetitions_file.push("new");
+        repetitions_file.push("repetitions.json");
+        if let Some(repetitions) = &measurement_data.repetitions {
+            log_if_err!(fs::save(repetitions, &repetitions_file));
+        } else {
+            // Don't leave the repetitions of an earlier run behind
+            let _ = std::fs::remove_file(&repetitions_file);
+        }
+    }
+
+    if criterion.connection.is_none() {
+        if let Baseline::Save = criterion.baseline {
+            for id in std::iter::once(id).chain(metric_ids.iter()) {
+                copy_new_dir_to_base(
+                    id.as_directory_name(),
+                    &criterion.baseline_directory,
+                    &criterion.output_directory,
+                );
+            }
+        }
+    }
+}
+
+// Computes the average value per iteration of each sample
+fn average(iters: &[f64], values: &[f64]) -> Vec<f64> {
+    iters
         .iter()
-        .zip(times.iter())
+        .zip(values.iter())
         .map(|(&iters, &elapsed)| elapsed / iters)
-        .collect::<Vec<f64>>();
-    let avg_times = Sample::new(&avg_times);
+        .collect()
+}
+
+// Estimates the statistics of one metric, saves them and compares them against the baseline
+#[allow(clippy::too_many_arguments)]
+fn analyze<'a, M: Measurement>(
+    id: &BenchmarkId,
+    sampling_mode: ActualSamplingMode,
+    iters: &'a [f64],
+    times: &'a [f64],
+    avg_times: &'a [f64],
+    percentiles: Option<PercentileEstimates>,
+    config: &BenchmarkConfig,
+    criterion: &Criterion<M>,
+) -> MeasurementData<'a> {
+    let avg_times = Sample::new(avg_times);
 
     if criterion.should_save_baseline() {
         log_if_err!({
@@ -137,7 +530,7 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
         });
     }
 
-    let data = Data::new(&iters, &times);
+    let data = Data::new(iters, times);
     let labeled_sample = tukey::classify(avg_times);
     if criterion.should_save_baseline() {
         log_if_err!({
@@ -155,6 +548,7 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
         estimates.slope = Some(slope);
         distributions.slope = Some(distribution);
     }
+    estimates.percentiles = percentiles;
 
     if criterion.should_save_baseline() {
         log_if_err!({
@@ -167,6 +561,7 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
                     sampling_mode,
                     iters: data.x().as_ref().to_vec(),
                     times: data.y().as_ref().to_vec(),
+                    cpu: criterion.cpu,
                 },
                 &sample_file,
             )
@@ -188,8 +583,8 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
         let result = compare::common(id, avg_times, config, criterion);
         match result {
             Ok((
-                t_value,
-                t_distribution,
+                test_statistic,
+                test_distribution,
                 relative_estimates,
                 relative_distributions,
                 base_iter_counts,
@@ -197,11 +592,23 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
                 base_avg_times,
                 base_estimates,
             )) => {
-                let p_value = t_distribution.p_value(t_value, &Tails::Two);
+                // Samples without any variance (eg. a constant number of allocations) leave no
+                // finite test statistics to test against; they differ exactly when their means do.
+                let p_value = if test_distribution.is_empty() {
+                    let base_mean = Sample::new(&base_avg_times).mean();
+                    if avg_times.mean() == base_mean {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                } else {
+                    test_distribution.p_value(test_statistic, &Tails::Two)
+                };
                 Some(crate::report::ComparisonData {
+                    test: config.comparison_test,
                     p_value,
-                    t_distribution,
-                    t_value,
+                    test_distribution,
+                    test_statistic,
                     relative_estimates,
                     relative_distributions,
                     significance_threshold: config.significance_level,
@@ -221,40 +628,29 @@ pub(crate) fn common<M: Measurement, T: ?Sized>(
         None
     };
 
-    let measurement_data = crate::report::MeasurementData {
-        data: Data::new(&iters, &times),
+    MeasurementData {
+        data,
         avg_times: labeled_sample,
         absolute_estimates: estimates,
         distributions,
         comparison: compare_data,
-        throughput,
-    };
-
-    criterion.report.measurement_complete(
-        id,
-        report_context,
-        &measurement_data,
-        criterion.measurement.formatter(),
-    );
-
-    if criterion.should_save_baseline() {
-        log_if_err!({
-            let mut benchmark_file = criterion.output_directory.clone();
-            benchmark_file.push(id.as_directory_name());
-            benchmark_file.push("new");
-            benchmark_file.push("benchmark.json");
-            fs::save(&id, &benchmark_file)
-        });
+        throughput: None,
+        overhead: None,
+        warm_up: None,
+        repetitions: None,
+        metrics: Vec::new(),
     }
+}
 
-    if criterion.connection.is_none() {
-        if let Baseline::Save = criterion.baseline {
-            copy_new_dir_to_base(
-                id.as_directory_name(),
-                &criterion.baseline_directory,
-                &criterion.output_directory,
-            );
-        }
+// One iteration of `iter_parallel` runs the routine once on every thread, so the throughput of all
+// threads together is reported.
+fn parallel_throughput(throughput: Option<Throughput>, threads: usize) -> Throughput {
+    let threads = threads as u64;
+    match throughput {
+        Some(Throughput::Bytes(bytes)) => Throughput::Bytes(bytes * threads),
+        Some(Throughput::BytesDecimal(bytes)) => Throughput::BytesDecimal(bytes * threads),
+        Some(Throughput::Elements(elems)) => Throughput::Elements(elems * threads),
+        None => Throughput::Elements(threads),
     }
 }
 
@@ -348,23 +744,7 @@ fn copy_new_dir_to_base(id: &str, baseline: &str, output_directory: &Path) {
         try_else_return!(fs::mkdirp(&base_dir));
     }
 
-    // TODO: consider using walkdir or similar to generically copy.
-    try_else_return!(fs::cp(
-        &new_dir.join("estimates.json"),
-        &base_dir.join("estimates.json")
-    ));
-    try_else_return!(fs::cp(
-        &new_dir.join("sample.json"),
-        &base_dir.join("sample.json")
-    ));
-    try_else_return!(fs::cp(
-        &new_dir.join("tukey.json"),
-        &base_dir.join("tukey.json")
-    ));
-    try_else_return!(fs::cp(
-        &new_dir.join("benchmark.json"),
-        &base_dir.join("benchmark.json")
-    ));
-    #[cfg(feature = "csv_output")]
-    try_else_return!(fs::cp(&new_dir.join("raw.csv"), &base_dir.join("raw.csv")));
+    for file in try_else_return!(fs::list_files(&new_dir)) {
+        try_else_return!(fs::cp(&file, &base_dir.join(file.file_name().unwrap())));
+    }
 }
@@ -3,7 +3,10 @@ use std::time::Duration;
 use std::time::Instant;
 
 use crate::black_box;
+use crate::cache::EvictionBuffer;
+use crate::latency::LatencyHistogram;
 use crate::measurement::{Measurement, WallTime};
+use crate::worker_pool::WorkerPool;
 use crate::BatchSize;
 
 #[cfg(feature = "async")]
@@ -38,6 +41,14 @@ pub struct Bencher<'a, M: Measurement = WallTime> {
     pub(crate) value: M::Value,        // The measured value
     pub(crate) measurement: &'a M,     // Reference to the measurement object
     pub(crate) elapsed_time: Duration, // How much time did it take to perform the iteration? Used for the warmup period.
+    pub(crate) calibrating: bool,      // Are we timing an empty routine to measure the overhead?
+    pub(crate) threads: Option<usize>, // How many threads did iter_parallel run the routine on?
+    // The threads of iter_parallel, kept between samples
+    pub(crate) workers: Option<WorkerPool>,
+    // The latencies of the individual iterations, if they are recorded
+    pub(crate) latencies: Option<LatencyHistogram>,
+    // The buffer evicting the caches for ColdPerIteration, freed with the Bencher
+    pub(crate) eviction_buffer: Option<EvictionBuffer>,
 }
 impl<'a, M: Measurement> Bencher<'a, M> {
     /// Times a `routine` by executing it many times and timing the total elapsed time.
@@ -76,18 +87,40 @@ impl<'a, M: Measurement> Bencher<'a, M> {
     /// criterion_main!(benches);
     /// ```
     ///
+    pub fn iter<O, R>(&mut self, routine: R)
+    where
+        R: FnMut() -> O,
+    {
+        if self.calibrating {
+            self.time_iter(|| ());
+        } else {
+            self.time_iter(routine);
+        }
+    }
+
     #[inline(never)]
-    pub fn iter<O, R>(&mut self, mut routine: R)
+    fn time_iter<O, R>(&mut self, mut routine: R)
     where
         R: FnMut() -> O,
     {
         self.iterated = true;
         let time_start = Instant::now();
-        let start = self.measurement.start();
-        for _ in 0..self.iters {
-            black_box(routine());
+        if let Some(latencies) = &mut self.latencies {
+            self.value = self.measurement.zero();
+            for _ in 0..self.iters {
+                let start = self.measurement.start();
+                black_box(routine());
+                let end = self.measurement.end(start);
+                latencies.record(self.measurement.to_f64(&end));
+                self.value = self.measurement.add(&self.value, &end);
+            }
+        } else {
+            let start = self.measurement.start();
+            for _ in 0..self.iters {
+                black_box(routine());
+            }
+            self.value = self.measurement.end(start);
         }
-        self.value = self.measurement.end(start);
         self.elapsed_time = time_start.elapsed();
     }
 
@@ -133,6 +166,11 @@ impl<'a, M: Measurement> Bencher<'a, M> {
         R: FnMut(u64) -> M::Value,
     {
         self.iterated = true;
+        if self.calibrating {
+            // The routine does its own timing, so there is no overhead to measure
+            self.value = self.measurement.zero();
+            return;
+        }
         let time_start = Instant::now();
         self.value = routine(self.iters);
         self.elapsed_time = time_start.elapsed();
@@ -232,8 +270,20 @@ impl<'a, M: Measurement> Bencher<'a, M> {
     /// criterion_main!(benches);
     /// ```
     ///
+    pub fn iter_batched<I, O, S, R>(&mut self, setup: S, routine: R, size: BatchSize)
+    where
+        S: FnMut() -> I,
+        R: FnMut(I) -> O,
+    {
+        if self.calibrating {
+            self.time_batched(setup, |input| input, size);
+        } else {
+            self.time_batched(setup, routine, size);
+        }
+    }
+
     #[inline(never)]
-    pub fn iter_batched<I, O, S, R>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
+    fn time_batched<I, O, S, R>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
     where
         S: FnMut() -> I,
         R: FnMut(I) -> O,
@@ -247,11 +297,17 @@ impl<'a, M: Measurement> Bencher<'a, M> {
         if batch_size == 1 {
             for _ in 0..self.iters {
                 let input = black_box(setup());
+                if size == BatchSize::ColdPerIteration {
+                    self.evict_caches();
+                }
 
                 let start = self.measurement.start();
                 let output = routine(input);
                 let end = self.measurement.end(start);
                 self.value = self.measurement.add(&self.value, &end);
+                if let Some(latencies) = &mut self.latencies {
+                    latencies.record(self.measurement.to_f64(&end));
+                }
 
                 drop(black_box(output));
             }
@@ -264,10 +320,20 @@ impl<'a, M: Measurement> Bencher<'a, M> {
                 let inputs = black_box((0..batch_size).map(|_| setup()).collect::<Vec<_>>());
                 let mut outputs = Vec::with_capacity(batch_size as usize);
 
-                let start = self.measurement.start();
-                outputs.extend(inputs.into_iter().map(&mut routine));
-                let end = self.measurement.end(start);
-                self.value = self.measurement.add(&self.value, &end);
+                if let Some(latencies) = &mut self.latencies {
+                    for input in inputs {
+                        let start = self.measurement.start();
+                        outputs.push(routine(input));
+                        let end = self.measurement.end(start);
+                        latencies.record(self.measurement.to_f64(&end));
+                        self.value = self.measurement.add(&self.value, &end);
+                    }
+                } else {
+                    let start = self.measurement.start();
+                    outputs.extend(inputs.into_iter().map(&mut routine));
+                    let end = self.measurement.end(start);
+                    self.value = self.measurement.add(&self.value, &end);
+                }
 
                 black_box(outputs);
 
@@ -322,8 +388,20 @@ impl<'a, M: Measurement> Bencher<'a, M> {
     /// criterion_main!(benches);
     /// ```
     ///
+    pub fn iter_batched_ref<I, O, S, R>(&mut self, setup: S, routine: R, size: BatchSize)
+    where
+        S: FnMut() -> I,
+        R: FnMut(&mut I) -> O,
+    {
+        if self.calibrating {
+            self.time_batched_ref(setup, |_| (), size);
+        } else {
+            self.time_batched_ref(setup, routine, size);
+        }
+    }
+
     #[inline(never)]
-    pub fn iter_batched_ref<I, O, S, R>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
+    fn time_batched_ref<I, O, S, R>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
     where
         S: FnMut() -> I,
         R: FnMut(&mut I) -> O,
@@ -337,11 +415,17 @@ impl<'a, M: Measurement> Bencher<'a, M> {
         if batch_size == 1 {
             for _ in 0..self.iters {
                 let mut input = black_box(setup());
+                if size == BatchSize::ColdPerIteration {
+                    self.evict_caches();
+                }
 
                 let start = self.measurement.start();
                 let output = routine(&mut input);
                 let end = self.measurement.end(start);
                 self.value = self.measurement.add(&self.value, &end);
+                if let Some(latencies) = &mut self.latencies {
+                    latencies.record(self.measurement.to_f64(&end));
+                }
 
                 drop(black_box(output));
                 drop(black_box(input));
@@ -355,10 +439,20 @@ impl<'a, M: Measurement> Bencher<'a, M> {
                 let mut inputs = black_box((0..batch_size).map(|_| setup()).collect::<Vec<_>>());
                 let mut outputs = Vec::with_capacity(batch_size as usize);
 
-                let start = self.measurement.start();
-                outputs.extend(inputs.iter_mut().map(&mut routine));
-                let end = self.measurement.end(start);
-                self.value = self.measurement.add(&self.value, &end);
+                if let Some(latencies) = &mut self.latencies {
+                    for input in inputs.iter_mut() {
+                        let start = self.measurement.start();
+                        outputs.push(routine(input));
+                        let end = self.measurement.end(start);
+                        latencies.record(self.measurement.to_f64(&end));
+                        self.value = self.measurement.add(&self.value, &end);
+                    }
+                } else {
+                    let start = self.measurement.start();
+                    outputs.extend(inputs.iter_mut().map(&mut routine));
+                    let end = self.measurement.end(start);
+                    self.value = self.measurement.add(&self.value, &end);
+                }
 
                 black_box(outputs);
 
@@ -368,6 +462,107 @@ impl<'a, M: Measurement> Bencher<'a, M> {
         self.elapsed_time = time_start.elapsed();
     }
 
+    /// Times a `routine` by executing it concurrently on `threads` threads, for measuring
+    /// contention on shared data structures such as locks, queues or allocators. The routine is
+    /// passed the index of the thread it runs on, from `0` to `threads - 1`.
+    ///
+    /// The threads are spawned once per benchmark and parked between samples. The measurement is
+    /// started once all of them are parked and then they are released together, so that thread
+    /// startup isn't measured and they all start iterating at the same time. Each thread executes
+    /// the routine once per iteration, and the sample lasts until the last thread has finished.
+    /// The reported time is therefore the latency of one operation while `threads` operations run
+    /// in parallel. The throughput is reported for all threads together; if no throughput is
+    /// configured, it is reported as `threads` elements per iteration.
+    ///
+    /// Use the thread count as the parameter of a [`BenchmarkId`](struct.BenchmarkId.html) to
+    /// turn the line chart of a benchmark group into a scalability plot.
+    ///
+    /// The measurement is started and stopped on the calling thread, so measurements like
+    /// [`ThreadCpuTime`](measurement/struct.ThreadCpuTime.html) do not include the work done by
+    /// the spawned threads.
+    ///
+    /// # Timing model
+    ///
+    /// ```text
+    /// elapsed = iters * (routine + mem::drop(O) + Range::next) on the slowest thread
+    /// ```
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// #[macro_use] extern crate criterion;
+    ///
+    /// use criterion::*;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// fn bench(c: &mut Criterion) {
+    ///     let counter = AtomicU64::new(0);
+    ///
+    ///     let mut group = c.benchmark_group("fetch_add");
+    ///     for threads in [1, 2, 4] {
+    ///         group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+    ///             b.iter_parallel(threads, |_thread_idx| counter.fetch_add(1, Ordering::Relaxed))
+    ///         });
+    ///     }
+    ///     group.finish();
+    /// }
+    ///
+    /// criterion_group!(benches, bench);
+    /// criterion_main!(benches);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    pub fn iter_parallel<O, R>(&mut self, threads: usize, routine: R)
+    where
+        R: Fn(usize) -> O + Sync,
+    {
+        if self.calibrating {
+            self.time_parallel(threads, |_| ());
+        } else {
+            self.time_parallel(threads, routine);
+        }
+    }
+
+    #[inline(never)]
+    fn time_parallel<O, R>(&mut self, threads: usize, routine: R)
+    where
+        R: Fn(usize) -> O + Sync,
+    {
+        assert!(threads != 0, "Thread count must not be zero.");
+        self.iterated = true;
+        self.threads = Some(threads);
+        let iters = self.iters;
+        let job = |thread_idx| {
+            for _ in 0..iters {
+                black_box(routine(thread_idx));
+            }
+        };
+
+        if self.workers.as_ref().map(WorkerPool::threads) != Some(threads) {
+            self.workers = Some(WorkerPool::new(threads));
+        }
+        let workers = self.workers.as_mut().unwrap();
+        let measurement = self.measurement;
+        let (value, elapsed_time) = workers.run(
+            &job,
+            || (Instant::now(), measurement.start()),
+            |(time_start, start)| (measurement.end(start), time_start.elapsed()),
+        );
+
+        self.value = value;
+        self.elapsed_time = elapsed_time;
+    }
+
+    // Evicts the benchmark's data from the CPU caches, allocating the buffer it streams through the
+    // first time.
+    fn evict_caches(&mut self) {
+        self.eviction_buffer
+            .get_or_insert_with(EvictionBuffer::new)
+            .evict();
+    }
+
     // Benchmarks must actually call one of the iter methods. This causes benchmarks to fail loudly
     // if they don't.
     pub(crate) fn assert_iterated(&mut self) {
@@ -430,8 +625,20 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
     /// criterion_main!(benches);
     /// ```
     ///
+    pub fn iter<O, R, F>(&mut self, routine: R)
+    where
+        R: FnMut() -> F,
+        F: Future<Output = O>,
+    {
+        if self.b.calibrating {
+            self.time_iter(|| async {});
+        } else {
+            self.time_iter(routine);
+        }
+    }
+
     #[inline(never)]
-    pub fn iter<O, R, F>(&mut self, mut routine: R)
+    fn time_iter<O, R, F>(&mut self, mut routine: R)
     where
         R: FnMut() -> F,
         F: Future<Output = O>,
@@ -440,11 +647,22 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
         runner.block_on(async {
             b.iterated = true;
             let time_start = Instant::now();
-            let start = b.measurement.start();
-            for _ in 0..b.iters {
-                black_box(routine().await);
+            if let Some(latencies) = &mut b.latencies {
+                b.value = b.measurement.zero();
+                for _ in 0..b.iters {
+                    let start = b.measurement.start();
+                    black_box(routine().await);
+                    let end = b.measurement.end(start);
+                    latencies.record(b.measurement.to_f64(&end));
+                    b.value = b.measurement.add(&b.value, &end);
+                }
+            } else {
+                let start = b.measurement.start();
+                for _ in 0..b.iters {
+                    black_box(routine().await);
+                }
+                b.value = b.measurement.end(start);
             }
-            b.value = b.measurement.end(start);
             b.elapsed_time = time_start.elapsed();
         });
     }
@@ -495,6 +713,12 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
         F: Future<Output = M::Value>,
     {
         let AsyncBencher { b, runner } = self;
+        if b.calibrating {
+            // The routine does its own timing, so there is no overhead to measure
+            b.iterated = true;
+            b.value = b.measurement.zero();
+            return;
+        }
         runner.block_on(async {
             b.iterated = true;
             let time_start = Instant::now();
@@ -611,8 +835,21 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
     /// criterion_main!(benches);
     /// ```
     ///
+    pub fn iter_batched<I, O, S, R, F>(&mut self, setup: S, routine: R, size: BatchSize)
+    where
+        S: FnMut() -> I,
+        R: FnMut(I) -> F,
+        F: Future<Output = O>,
+    {
+        if self.b.calibrating {
+            self.time_batched(setup, |input| async move { input }, size);
+        } else {
+            self.time_batched(setup, routine, size);
+        }
+    }
+
     #[inline(never)]
-    pub fn iter_batched<I, O, S, R, F>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
+    fn time_batched<I, O, S, R, F>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
     where
         S: FnMut() -> I,
         R: FnMut(I) -> F,
@@ -629,11 +866,17 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
             if batch_size == 1 {
                 for _ in 0..b.iters {
                     let input = black_box(setup());
+                    if size == BatchSize::ColdPerIteration {
+                        b.evict_caches();
+                    }
 
                     let start = b.measurement.start();
                     let output = routine(input).await;
                     let end = b.measurement.end(start);
                     b.value = b.measurement.add(&b.value, &end);
+                    if let Some(latencies) = &mut b.latencies {
+                        latencies.record(b.measurement.to_f64(&end));
+                    }
 
                     drop(black_box(output));
                 }
@@ -646,13 +889,23 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
                     let inputs = black_box((0..batch_size).map(|_| setup()).collect::<Vec<_>>());
                     let mut outputs = Vec::with_capacity(batch_size as usize);
 
-                    let start = b.measurement.start();
-                    // Can't use .extend here like the sync version does
-                    for input in inputs {
-                        outputs.push(routine(input).await);
+                    if let Some(latencies) = &mut b.latencies {
+                        for input in inputs {
+                            let start = b.measurement.start();
+                            outputs.push(routine(input).await);
+                            let end = b.measurement.end(start);
+                            latencies.record(b.measurement.to_f64(&end));
+                            b.value = b.measurement.add(&b.value, &end);
+                        }
+                    } else {
+                        let start = b.measurement.start();
+                        // Can't use .extend here like the sync version does
+                        for input in inputs {
+                            outputs.push(routine(input).await);
+                        }
+                        let end = b.measurement.end(start);
+                        b.value = b.measurement.add(&b.value, &end);
                     }
-                    let end = b.measurement.end(start);
-                    b.value = b.measurement.add(&b.value, &end);
 
                     black_box(outputs);
 
@@ -709,8 +962,21 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
     /// criterion_main!(benches);
     /// ```
     ///
+    pub fn iter_batched_ref<I, O, S, R, F>(&mut self, setup: S, routine: R, size: BatchSize)
+    where
+        S: FnMut() -> I,
+        R: FnMut(&mut I) -> F,
+        F: Future<Output = O>,
+    {
+        if self.b.calibrating {
+            self.time_batched_ref(setup, |_| async {}, size);
+        } else {
+            self.time_batched_ref(setup, routine, size);
+        }
+    }
+
     #[inline(never)]
-    pub fn iter_batched_ref<I, O, S, R, F>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
+    fn time_batched_ref<I, O, S, R, F>(&mut self, mut setup: S, mut routine: R, size: BatchSize)
     where
         S: FnMut() -> I,
         R: FnMut(&mut I) -> F,
@@ -727,11 +993,17 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
             if batch_size == 1 {
                 for _ in 0..b.iters {
                     let mut input = black_box(setup());
+                    if size == BatchSize::ColdPerIteration {
+                        b.evict_caches();
+                    }
 
                     let start = b.measurement.start();
                     let output = routine(&mut input).await;
                     let end = b.measurement.end(start);
                     b.value = b.measurement.add(&b.value, &end);
+                    if let Some(latencies) = &mut b.latencies {
+                        latencies.record(b.measurement.to_f64(&end));
+                    }
 
                     drop(black_box(output));
                     drop(black_box(input));
@@ -745,13 +1017,23 @@ impl<'a, 'b, A: AsyncExecutor, M: Measurement> AsyncBencher<'a, 'b, A, M> {
                     let inputs = black_box((0..batch_size).map(|_| setup()).collect::<Vec<_>>());
                     let mut outputs = Vec::with_capacity(batch_size as usize);
 
-                    let start = b.measurement.start();
-                    // Can't use .extend here like the sync version does
-                    for mut input in inputs {
-                        outputs.push(routine(&mut input).await);
+                    if let Some(latencies) = &mut b.latencies {
+                        for mut input in inputs {
+                            let start = b.measurement.start();
+                            outputs.push(routine(&mut input).await);
+                            let end = b.measurement.end(start);
+                            latencies.record(b.measurement.to_f64(&end));
+                            b.value = b.measurement.add(&b.value, &end);
+                        }
+                    } else {
+                        let start = b.measurement.start();
+                        // Can't use .extend here like the sync version does
+                        for mut input in inputs {
+                            outputs.push(routine(&mut input).await);
+                        }
+                        let end = b.measurement.end(start);
+                        b.value = b.measurement.add(&b.value, &end);
                     }
-                    let end = b.measurement.end(start);
-                    b.value = b.measurement.add(&b.value, &end);
 
                     black_box(outputs);
 
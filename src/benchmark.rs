@@ -1,9 +1,10 @@
-use crate::{PlotConfiguration, SamplingMode};
+use crate::{ComparisonTest, PlotConfiguration, SamplingMode, WarmUpMode};
 use std::time::Duration;
 
 // TODO: Move the benchmark config stuff to a separate module for easier use.
 
 /// Struct containing all of the configuration options for a benchmark.
+#[derive(Clone)]
 pub struct BenchmarkConfig {
     pub confidence_level: f64,
     pub measurement_time: Duration,
@@ -11,9 +12,20 @@ pub struct BenchmarkConfig {
     pub nresamples: usize,
     pub sample_size: usize,
     pub significance_level: f64,
+    pub comparison_test: ComparisonTest,
     pub warm_up_time: Duration,
     pub sampling_mode: SamplingMode,
     pub quick_mode: bool,
+    pub calibrate_overhead: bool,
+    pub target_precision: Option<f64>,
+    pub max_measurement_time: Duration,
+    pub warm_up_mode: WarmUpMode,
+    pub max_warm_up_time: Duration,
+    pub repetitions: usize,
+    pub timeout: Option<Duration>,
+    /// The factor by which the warm-up and measurement times were scaled down to fit the time
+    /// budget, if they were.
+    pub time_budget_scale: Option<f64>,
 }
 
 /// Struct representing a partially-complete per-benchmark configuration.
@@ -25,9 +37,17 @@ pub(crate) struct PartialBenchmarkConfig {
     pub(crate) nresamples: Option<usize>,
     pub(crate) sample_size: Option<usize>,
     pub(crate) significance_level: Option<f64>,
+    pub(crate) comparison_test: Option<ComparisonTest>,
     pub(crate) warm_up_time: Option<Duration>,
     pub(crate) sampling_mode: Option<SamplingMode>,
     pub(crate) quick_mode: Option<bool>,
+    pub(crate) calibrate_overhead: Option<bool>,
+    pub(crate) target_precision: Option<f64>,
+    pub(crate) max_measurement_time: Option<Duration>,
+    pub(crate) warm_up_mode: Option<WarmUpMode>,
+    pub(crate) max_warm_up_time: Option<Duration>,
+    pub(crate) repetitions: Option<usize>,
+    pub(crate) timeout: Option<Duration>,
     pub(crate) plot_config: PlotConfiguration,
 }
 
@@ -42,9 +62,22 @@ impl PartialBenchmarkConfig {
             significance_level: self
                 .significance_level
                 .unwrap_or(defaults.significance_level),
+            comparison_test: self.comparison_test.unwrap_or(defaults.comparison_test),
             warm_up_time: self.warm_up_time.unwrap_or(defaults.warm_up_time),
             sampling_mode: self.sampling_mode.unwrap_or(defaults.sampling_mode),
             quick_mode: self.quick_mode.unwrap_or(defaults.quick_mode),
+            calibrate_overhead: self
+                .calibrate_overhead
+                .unwrap_or(defaults.calibrate_overhead),
+            target_precision: self.target_precision.or(defaults.target_precision),
+            max_measurement_time: self
+                .max_measurement_time
+                .unwrap_or(defaults.max_measurement_time),
+            warm_up_mode: self.warm_up_mode.unwrap_or(defaults.warm_up_mode),
+            max_warm_up_time: self.max_warm_up_time.unwrap_or(defaults.max_warm_up_time),
+            repetitions: self.repetitions.unwrap_or(defaults.repetitions),
+            timeout: self.timeout.or(defaults.timeout),
+            time_budget_scale: None,
         }
     }
 }
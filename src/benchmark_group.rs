@@ -1,12 +1,17 @@
-use crate::analysis;
-use crate::benchmark::PartialBenchmarkConfig;
+use crate::analysis::{self, DeferredBenchmark};
+use crate::benchmark::{BenchmarkConfig, PartialBenchmarkConfig};
 use crate::connection::OutgoingMessage;
+use crate::isolation;
 use crate::measurement::Measurement;
 use crate::report::BenchmarkId as InternalBenchmarkId;
 use crate::report::Report;
 use crate::report::ReportContext;
 use crate::routine::{Function, Routine};
-use crate::{Bencher, Criterion, Mode, PlotConfiguration, SamplingMode, Throughput};
+use crate::shuffle;
+use crate::{
+    Bencher, ComparisonTest, Criterion, Interleaving, ListFormat, Mode, PlotConfiguration,
+    SamplingMode, Throughput, WarmUpMode,
+};
 use std::time::Duration;
 
 /// Structure used to group together a set of related benchmarks, along with custom configuration
@@ -82,6 +87,7 @@ pub struct BenchmarkGroup<'a, M: Measurement> {
     any_matched: bool,
     partial_config: PartialBenchmarkConfig,
     throughput: Option<Throughput>,
+    tags: Vec<String>,
 }
 impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
     /// Changes the size of the sample for this benchmark
@@ -113,6 +119,26 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
         self
     }
 
+    /// Changes the warm-up mode for benchmarks in this group. See
+    /// [`Criterion::warm_up_mode`](struct.Criterion.html#method.warm_up_mode).
+    pub fn warm_up_mode(&mut self, mode: WarmUpMode) -> &mut Self {
+        self.partial_config.warm_up_mode = Some(mode);
+        self
+    }
+
+    /// Changes the maximum warm-up time of the steady-state warm-up for benchmarks in this group.
+    /// See [`Criterion::max_warm_up_time`](struct.Criterion.html#method.max_warm_up_time).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input duration is zero
+    pub fn max_warm_up_time(&mut self, dur: Duration) -> &mut Self {
+        assert!(dur.as_nanos() > 0);
+
+        self.partial_config.max_warm_up_time = Some(dur);
+        self
+    }
+
     /// Changes the target measurement time for this benchmark group.
     ///
     /// Criterion will attempt to spent approximately this amount of time measuring each
@@ -152,6 +178,33 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
         self
     }
 
+    /// Changes the number of repetitions for benchmarks in this group. See
+    /// [`Criterion::repetitions`](struct.Criterion.html#method.repetitions).
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is zero
+    pub fn repetitions(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+
+        self.partial_config.repetitions = Some(n);
+        self
+    }
+
+    /// Changes the timeout for benchmarks in this group. A benchmark whose warm-up and sampling
+    /// take longer than this is aborted and reported as failed. See
+    /// [`Criterion::timeout`](struct.Criterion.html#method.timeout).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout is zero
+    pub fn timeout(&mut self, dur: Duration) -> &mut Self {
+        assert!(dur.as_nanos() > 0);
+
+        self.partial_config.timeout = Some(dur);
+        self
+    }
+
     /// Changes the noise threshold for benchmarks in this group. The noise threshold
     /// is used to filter out small changes in performance from one run to the next, even if they
     /// are statistically significant. Sometimes benchmarking the same code twice will result in
@@ -216,6 +269,14 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
         self
     }
 
+    /// Changes the statistical test used to decide whether the performance of the benchmarks in
+    /// this group has changed. See
+    /// [`Criterion::comparison_test`](struct.Criterion.html#method.comparison_test).
+    pub fn comparison_test(&mut self, test: ComparisonTest) -> &mut Self {
+        self.partial_config.comparison_test = Some(test);
+        self
+    }
+
     /// Changes the plot configuration for this benchmark group.
     pub fn plot_config(&mut self, new_config: PlotConfiguration) -> &mut Self {
         self.partial_config.plot_config = new_config;
@@ -229,12 +290,59 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
         self
     }
 
+    /// Adds a tag to the benchmarks added to this group after this call. Tags can be used to
+    /// select benchmarks with `--tag` and `--skip-tag` or with
+    /// [`BenchmarkFilter::Tag`](enum.BenchmarkFilter.html#variant.Tag), and are shown by `--list`
+    /// and saved in `benchmark.json`. See also
+    /// [`BenchmarkId::with_tags`](struct.BenchmarkId.html#method.with_tags).
+    pub fn tag<S: Into<String>>(&mut self, tag: S) -> &mut Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
     /// Set the sampling mode for this benchmark group.
     pub fn sampling_mode(&mut self, new_mode: SamplingMode) -> &mut Self {
         self.partial_config.sampling_mode = Some(new_mode);
         self
     }
 
+    /// Enables or disables the calibration of the timing loop overhead for benchmarks in this
+    /// group. See [`Criterion::calibrate_overhead`](struct.Criterion.html#method.calibrate_overhead).
+    pub fn calibrate_overhead(&mut self, enabled: bool) -> &mut Self {
+        self.partial_config.calibrate_overhead = Some(enabled);
+        self
+    }
+
+    /// Enables adaptive sampling for benchmarks in this group. Samples are collected until the
+    /// confidence interval of the mean is within `precision` of the mean. See
+    /// [`Criterion::target_precision`](struct.Criterion.html#method.target_precision).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the precision is not positive
+    pub fn target_precision(&mut self, precision: f64) -> &mut Self {
+        assert!(precision > 0.0);
+
+        self.partial_config.target_precision = Some(precision);
+        self
+    }
+
+    /// Changes the maximum measurement time for benchmarks in this group. See
+    /// [`Criterion::max_measurement_time`](struct.Criterion.html#method.max_measurement_time).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input duration is zero
+    pub fn max_measurement_time(&mut self, dur: Duration) -> &mut Self {
+        assert!(dur.as_nanos() > 0);
+
+        self.partial_config.max_measurement_time = Some(dur);
+        self
+    }
+
     pub(crate) fn new(criterion: &mut Criterion<M>, group_name: String) -> BenchmarkGroup<'_, M> {
         BenchmarkGroup {
             criterion,
@@ -243,6 +351,7 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
             any_matched: false,
             partial_config: PartialBenchmarkConfig::default(),
             throughput: None,
+            tags: vec![],
         }
     }
 
@@ -270,23 +379,218 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
         self
     }
 
+    /// Benchmark the given function with every combination of the values of several parameters,
+    /// ie. their cartesian product. The dimensions are given as a tuple of up to four
+    /// `(name, values)` pairs, and every benchmark gets a tuple of one value of each dimension as
+    /// its input. The benchmarks are identified by
+    /// [`BenchmarkId::with_parameters`](struct.BenchmarkId.html#method.with_parameters).
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// use criterion::{Criterion, PlotConfiguration};
+    ///
+    /// fn bench(c: &mut Criterion) {
+    ///     let mut group = c.benchmark_group("sort");
+    ///     // Plot the size on the x-axis, and one line for each number of threads
+    ///     group.plot_config(PlotConfiguration::default().x_axis_parameter("size"));
+    ///     group.bench_cartesian(
+    ///         "sort",
+    ///         (("size", [1_000u64, 10_000, 100_000]), ("threads", [1, 2, 4])),
+    ///         |b, &(size, threads)| {
+    ///             let input = (0..size).rev().collect::<Vec<u64>>();
+    ///             b.iter(|| {
+    ///                 let mut input = input.clone();
+    ///                 input.sort();
+    ///                 (input, threads)
+    ///             })
+    ///         },
+    ///     );
+    ///     group.finish();
+    /// }
+    /// ```
+    pub fn bench_cartesian<S, D, F>(
+        &mut self,
+        function_name: S,
+        dimensions: D,
+        mut f: F,
+    ) -> &mut Self
+    where
+        S: Into<String>,
+        D: Dimensions,
+        F: FnMut(&mut Bencher<'_, M>, &D::Point),
+    {
+        let function_name = function_name.into();
+        for (parameters, point) in dimensions.product() {
+            let id = BenchmarkId::with_parameters(function_name.clone(), parameters);
+            self.run_bench(id, &point, &mut f);
+        }
+        self
+    }
+
+    /// Starts a set of benchmarks in this group whose samples are collected interleaved instead
+    /// of one benchmark after another. Benchmarks are added to the returned `InterleavedGroup`
+    /// like to the group itself; they run when it is finished or dropped. Each benchmark is
+    /// warmed up separately, then one sample of each benchmark is collected in turn, in the given
+    /// order, and finally each benchmark is analyzed and reported separately.
+    ///
+    /// Interleaving cancels out drift, such as thermal throttling or changing background load,
+    /// which would otherwise bias whichever benchmark runs later. This is most useful when
+    /// comparing several implementations of the same function.
+    ///
+    /// Benchmarks that use quick mode, a target precision or repetitions, and benchmarks run under
+    /// cargo-criterion, are run one after another as usual.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// use criterion::{Criterion, Interleaving};
+    ///
+    /// fn bench(c: &mut Criterion) {
+    ///     let input = (0..1000u64).collect::<Vec<_>>();
+    ///
+    ///     let mut group = c.benchmark_group("sum");
+    ///     let mut interleaved = group.interleaved(Interleaving::Random);
+    ///     interleaved.bench_with_input("iter", &input, |b, i| b.iter(|| i.iter().sum::<u64>()));
+    ///     interleaved.bench_with_input("fold", &input, |b, i| {
+    ///         b.iter(|| i.iter().fold(0, |a, b| a + b))
+    ///     });
+    ///     interleaved.finish();
+    ///     group.finish();
+    /// }
+    /// ```
+    pub fn interleaved(&mut self, order: Interleaving) -> InterleavedGroup<'_, 'a, M> {
+        InterleavedGroup {
+            group: self,
+            order,
+            benchmarks: vec![],
+        }
+    }
+
+    /// Starts a set of benchmarks in this group which run in random order with `--shuffle`.
+    /// Benchmarks are added to the returned `ShuffledGroup` like to the group itself, but they are
+    /// collected instead of run right away, and run when it is finished or dropped: in random
+    /// order with `--shuffle`, and in the order they were added otherwise.
+    ///
+    /// The benchmarks added to the group itself always run right away, in the order they are
+    /// defined.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// use criterion::{BenchmarkId, Criterion};
+    ///
+    /// fn bench(c: &mut Criterion) {
+    ///     let inputs = [10u64, 100, 1000];
+    ///
+    ///     let mut group = c.benchmark_group("sum");
+    ///     let mut shuffled = group.shuffled();
+    ///     for n in &inputs {
+    ///         shuffled.bench_with_input(BenchmarkId::new("iter", n), n, |b, &n| {
+    ///             b.iter(|| (0..n).sum::<u64>())
+    ///         });
+    ///     }
+    ///     shuffled.finish();
+    ///     group.finish();
+    /// }
+    /// ```
+    pub fn shuffled(&mut self) -> ShuffledGroup<'_, 'a, M> {
+        ShuffledGroup {
+            group: self,
+            benchmarks: vec![],
+        }
+    }
+
+    /// Starts a set of benchmarks in this group which share a fixture, such as a populated
+    /// database, an async runtime or a large generated input. The fixture is built by `setup`
+    /// before the first of the benchmarks runs, passed to every benchmark added to the returned
+    /// `FixtureGroup`, and torn down when it is finished or dropped. If none of the benchmarks run,
+    /// eg. because they don't match the filter, the fixture is never built. If `setup` panics, all
+    /// benchmarks of the `FixtureGroup` are reported as failed.
+    ///
+    /// Building and tearing down the fixture, and the per-sample hooks of the `FixtureGroup`, are
+    /// never part of the measured time.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// use criterion::Criterion;
+    /// use std::collections::HashMap;
+    ///
+    /// fn bench(c: &mut Criterion) {
+    ///     let mut group = c.benchmark_group("lookup");
+    ///     let mut fixture = group.setup(|| {
+    ///         (0..1_000_000u64).map(|i| (i, i * 2)).collect::<HashMap<_, _>>()
+    ///     });
+    ///     fixture.teardown(|map| println!("Tearing down {} entries", map.len()));
+    ///     fixture.bench_function("hit", |b, map| b.iter(|| map.get(&500_000)));
+    ///     fixture.bench_function("miss", |b, map| b.iter(|| map.get(&5_000_000)));
+    ///     fixture.finish();
+    ///     group.finish();
+    /// }
+    /// ```
+    pub fn setup<'b, S, F>(&'b mut self, setup: F) -> FixtureGroup<'b, 'a, M, S>
+    where
+        F: FnOnce() -> S + 'b,
+    {
+        FixtureGroup {
+            group: self,
+            setup: Some(Box::new(setup)),
+            fixture: None,
+            teardown: None,
+            before_sample: None,
+            after_sample: None,
+            deferred: vec![],
+        }
+    }
+
     fn run_bench<F, I>(&mut self, id: BenchmarkId, input: &I, f: F)
     where
         F: FnMut(&mut Bencher<'_, M>, &I),
         I: ?Sized,
     {
         let config = self.partial_config.to_complete(&self.criterion.config);
-        let report_context = ReportContext {
+        let report_context = self.report_context();
+        let (id, do_run) = self.register(id);
+        let mut func = Function::new(f);
+
+        let throughput = self.throughput.clone();
+
+        self.run_routine(
+            &id,
+            do_run,
+            &mut func,
+            input,
+            &config,
+            &report_context,
+            throughput,
+        );
+    }
+
+    fn report_context(&self) -> ReportContext {
+        ReportContext {
             output_directory: self.criterion.output_directory.clone(),
             plot_config: self.partial_config.plot_config.clone(),
-        };
+        }
+    }
 
+    // Creates the unique ID of a new benchmark and checks whether it matches the filter
+    fn register(&mut self, id: BenchmarkId) -> (InternalBenchmarkId, bool) {
+        let id_tags = id.tags;
+        let parameters = id.parameters;
         let mut id = InternalBenchmarkId::new(
             self.group_name.clone(),
             id.function_name,
             id.parameter,
             self.throughput.clone(),
         );
+        id.parameters = parameters;
+        id.tags = self.tags.clone();
+        for tag in id_tags {
+            if !id.tags.contains(&tag) {
+                id.tags.push(tag);
+            }
+        }
 
         assert!(
             !self.all_ids.contains(&id),
@@ -301,61 +605,135 @@ impl<'a, M: Measurement> BenchmarkGroup<'a, M> {
         id.ensure_title_unique(&self.criterion.all_titles);
         self.criterion.all_titles.insert(id.as_title().to_owned());
 
-        let do_run = self.criterion.filter_matches(id.id());
+        let do_run = self.criterion.filter_matches(id.id(), &id.tags);
         self.any_matched |= do_run;
-        let mut func = Function::new(f);
+        self.all_ids.push(id.clone());
+
+        (id, do_run)
+    }
+
+    // Registers a benchmark of an interleaved or shuffled group, which runs when that is finished
+    fn defer<'b, F>(
+        &mut self,
+        id: BenchmarkId,
+        routine: Function<M, F, ()>,
+    ) -> (DeferredBenchmark<'b, M>, bool)
+    where
+        M: 'b,
+        F: FnMut(&mut Bencher<'_, M>, &()) + 'b,
+    {
+        let config = self.partial_config.to_complete(&self.criterion.config);
+        let report_context = self.report_context();
+        let throughput = self.throughput.clone();
+        let (id, do_run) = self.register(id);
+
+        let benchmark = DeferredBenchmark {
+            id,
+            routine: Box::new(routine),
+            config,
+            report_context,
+            throughput,
+        };
+        (benchmark, do_run)
+    }
+
+    // Returns true if a benchmark which matched the filter runs its routine in this process, rather
+    // than being listed, loaded from a baseline or run in an isolated child process
+    fn runs_routine(&self, do_run: bool) -> bool {
+        do_run
+            && match self.criterion.mode {
+                Mode::Benchmark => {
+                    self.criterion.load_baseline.is_none() && !self.criterion.spawns_isolated()
+                }
+                Mode::List(_) => false,
+                Mode::Test | Mode::Profile(_) => true,
+            }
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    fn run_routine<I: ?Sized>(
+        &mut self,
+        id: &InternalBenchmarkId,
+        do_run: bool,
+        func: &mut dyn Routine<M, I>,
+        input: &I,
+        config: &BenchmarkConfig,
+        report_context: &ReportContext,
+        throughput: Option<Throughput>,
+    ) {
+        if do_run {
+            shuffle::record(id.id());
+        }
         match &self.criterion.mode {
             Mode::Benchmark => {
                 if let Some(conn) = &self.criterion.connection {
                     if do_run {
-                        conn.send(&OutgoingMessage::BeginningBenchmark { id: (&id).into() })
+                        conn.send(&OutgoingMessage::BeginningBenchmark { id: id.into() })
                             .unwrap();
                     } else {
-                        conn.send(&OutgoingMessage::SkippingBenchmark { id: (&id).into() })
+                        conn.send(&OutgoingMessage::SkippingBenchmark { id: id.into() })
                             .unwrap();
                     }
                 }
+                let mut config = config.clone();
                 if do_run {
-                    analysis::common(
-                        &id,
-                        &mut func,
-                        &config,
-                        self.criterion,
-                        &report_context,
-                        input,
-                        self.throughput.clone(),
-                    );
+                    self.criterion.apply_time_budget(Some(&mut config));
+                }
+                if do_run && self.criterion.spawns_isolated() {
+                    analysis::isolated(id, &config, self.criterion, report_context, throughput);
+                } else if do_run {
+                    let criterion = &*self.criterion;
+                    analysis::start(id, criterion, report_context);
+                    criterion.catch_failure(id, report_context, || {
+                        analysis::common(
+                            id,
+                            func,
+                            &config,
+                            criterion,
+                            report_context,
+                            input,
+                            throughput,
+                        )
+                    });
                 }
             }
-            Mode::List(_) => {
+            Mode::List(format) => {
                 if do_run {
-                    println!("{}: benchmark", id);
+                    match format {
+                        // The terse format is parsed by other tools, just like libtest's
+                        ListFormat::Pretty if !id.tags.is_empty() => {
+                            println!("{}: benchmark [{}]", id, id.tags.join(", "))
+                        }
+                        _ => println!("{}: benchmark", id),
+                    }
                 }
             }
             Mode::Test => {
                 if do_run {
                     // In test mode, run the benchmark exactly once, then exit.
-                    self.criterion.report.test_start(&id, &report_context);
-                    func.test(&self.criterion.measurement, input);
-                    self.criterion.report.test_pass(&id, &report_context);
+                    let criterion = &*self.criterion;
+                    criterion.report.test_start(id, report_context);
+                    let tested = criterion.catch_failure(id, report_context, || {
+                        func.test(&criterion.measurement, input)
+                    });
+                    if tested.is_some() {
+                        criterion.report.test_pass(id, report_context);
+                    }
                 }
             }
             &Mode::Profile(duration) => {
                 if do_run {
                     func.profile(
                         &self.criterion.measurement,
-                        &id,
+                        id,
                         self.criterion,
-                        &report_context,
+                        report_context,
                         duration,
                         input,
                     );
                 }
             }
         }
-
-        self.all_ids.push(id);
     }
 
     /// Consume the benchmark group and generate the summary reports for the group.
@@ -398,12 +776,420 @@ impl<'a, M: Measurement> Drop for BenchmarkGroup<'a, M> {
     }
 }
 
+/// A set of benchmarks in a [`BenchmarkGroup`](struct.BenchmarkGroup.html) whose samples are
+/// collected interleaved. Created by
+/// [`BenchmarkGroup::interleaved`](struct.BenchmarkGroup.html#method.interleaved).
+///
+/// The benchmarks are only run when the `InterleavedGroup` is finished, so the benchmarked
+/// functions and their inputs have to outlive it.
+pub struct InterleavedGroup<'b, 'a: 'b, M: Measurement> {
+    group: &'b mut BenchmarkGroup<'a, M>,
+    order: Interleaving,
+    // The benchmarks and whether they matched the filter
+    benchmarks: Vec<(DeferredBenchmark<'b, M>, bool)>,
+}
+impl<'b, 'a: 'b, M: Measurement> InterleavedGroup<'b, 'a, M> {
+    /// Add the given parameterless function to the interleaved benchmarks.
+    pub fn bench_function<ID: IntoBenchmarkId, F>(&mut self, id: ID, mut f: F) -> &mut Self
+    where
+        F: FnMut(&mut Bencher<'_, M>) + 'b,
+    {
+        self.add(id.into_benchmark_id(), Function::new(move |b, _: &()| f(b)));
+        self
+    }
+
+    /// Add the given parameterized function to the interleaved benchmarks.
+    pub fn bench_with_input<ID: IntoBenchmarkId, F, I>(
+        &mut self,
+        id: ID,
+        input: &'b I,
+        mut f: F,
+    ) -> &mut Self
+    where
+        F: FnMut(&mut Bencher<'_, M>, &I) + 'b,
+        I: ?Sized,
+    {
+        self.add(
+            id.into_benchmark_id(),
+            Function::new(move |b, _: &()| f(b, input)),
+        );
+        self
+    }
+
+    fn add<F>(&mut self, id: BenchmarkId, routine: Function<M, F, ()>)
+    where
+        F: FnMut(&mut Bencher<'_, M>, &()) + 'b,
+    {
+        let benchmark = self.group.defer(id, routine);
+        self.benchmarks.push(benchmark);
+    }
+
+    /// Run the interleaved benchmarks. This is called automatically when the `InterleavedGroup` is
+    /// dropped.
+    pub fn finish(self) {
+        ::std::mem::drop(self);
+    }
+}
+impl<'b, 'a: 'b, M: Measurement> Drop for InterleavedGroup<'b, 'a, M> {
+    fn drop(&mut self) {
+        let criterion = &*self.group.criterion;
+        let can_interleave = criterion.mode.is_benchmark()
+            && criterion.connection.is_none()
+            && criterion.load_baseline.is_none()
+            && !criterion.isolated
+            && isolation::child_benchmark_id().is_none();
+
+        let mut interleaved = vec![];
+        for (mut benchmark, do_run) in self.benchmarks.drain(..) {
+            if do_run
+                && can_interleave
+                && !benchmark.config.quick_mode
+                && benchmark.config.target_precision.is_none()
+                && benchmark.config.repetitions == 1
+            {
+                shuffle::record(benchmark.id.id());
+                interleaved.push(benchmark);
+            } else {
+                self.group.run_routine(
+                    &benchmark.id,
+                    do_run,
+                    &mut *benchmark.routine,
+                    &(),
+                    &benchmark.config,
+                    &benchmark.report_context,
+                    benchmark.throughput.clone(),
+                );
+            }
+        }
+
+        if !interleaved.is_empty() {
+            self.group.criterion.apply_time_budget(
+                interleaved
+                    .iter_mut()
+                    .map(|benchmark| &mut benchmark.config),
+            );
+            analysis::interleaved(&mut interleaved, self.order, self.group.criterion);
+        }
+    }
+}
+
+/// A set of benchmarks in a [`BenchmarkGroup`](struct.BenchmarkGroup.html) which run in random
+/// order with `--shuffle`. Created by
+/// [`BenchmarkGroup::shuffled`](struct.BenchmarkGroup.html#method.shuffled).
+///
+/// The benchmarks are only run when the `ShuffledGroup` is finished, so the benchmarked functions
+/// and their inputs have to outlive it.
+pub struct ShuffledGroup<'b, 'a: 'b, M: Measurement> {
+    group: &'b mut BenchmarkGroup<'a, M>,
+    // The benchmarks and whether they matched the filter
+    benchmarks: Vec<(DeferredBenchmark<'b, M>, bool)>,
+}
+impl<'b, 'a: 'b, M: Measurement> ShuffledGroup<'b, 'a, M> {
+    /// Add the given parameterless function to the shuffled benchmarks.
+    pub fn bench_function<ID: IntoBenchmarkId, F>(&mut self, id: ID, mut f: F) -> &mut Self
+    where
+        F: FnMut(&mut Bencher<'_, M>) + 'b,
+    {
+        let benchmark = self
+            .group
+            .defer(id.into_benchmark_id(), Function::new(move |b, _: &()| f(b)));
+        self.benchmarks.push(benchmark);
+        self
+    }
+
+    /// Add the given parameterized function to the shuffled benchmarks.
+    pub fn bench_with_input<ID: IntoBenchmarkId, F, I>(
+        &mut self,
+        id: ID,
+        input: &'b I,
+        mut f: F,
+    ) -> &mut Self
+    where
+        F: FnMut(&mut Bencher<'_, M>, &I) + 'b,
+        I: ?Sized,
+    {
+        let benchmark = self.group.defer(
+            id.into_benchmark_id(),
+            Function::new(move |b, _: &()| f(b, input)),
+        );
+        self.benchmarks.push(benchmark);
+        self
+    }
+
+    /// Run the benchmarks, in random order with `--shuffle`. This is called automatically when the
+    /// `ShuffledGroup` is dropped.
+    pub fn finish(self) {
+        ::std::mem::drop(self);
+    }
+}
+impl<'b, 'a: 'b, M: Measurement> Drop for ShuffledGroup<'b, 'a, M> {
+    fn drop(&mut self) {
+        shuffle::shuffle(&mut self.benchmarks);
+        for (mut benchmark, do_run) in self.benchmarks.drain(..) {
+            self.group.run_routine(
+                &benchmark.id,
+                do_run,
+                &mut *benchmark.routine,
+                &(),
+                &benchmark.config,
+                &benchmark.report_context,
+                benchmark.throughput.clone(),
+            );
+        }
+    }
+}
+
+// A function which defines the benchmarks of a group of a `ShuffledGroups`
+type GroupDefinition<'a, M> = Box<dyn FnOnce(&mut BenchmarkGroup<'_, M>) + 'a>;
+
+/// A set of benchmark groups which run in random order with `--shuffle`. Created by
+/// [`Criterion::shuffled_groups`](struct.Criterion.html#method.shuffled_groups).
+///
+/// The groups are only run when the `ShuffledGroups` is finished, so the functions which define
+/// their benchmarks have to outlive it.
+pub struct ShuffledGroups<'a, M: Measurement> {
+    criterion: &'a mut Criterion<M>,
+    // The names of the groups and the functions which define their benchmarks
+    groups: Vec<(String, GroupDefinition<'a, M>)>,
+}
+impl<'a, M: Measurement> ShuffledGroups<'a, M> {
+    pub(crate) fn new(criterion: &'a mut Criterion<M>) -> ShuffledGroups<'a, M> {
+        ShuffledGroups {
+            criterion,
+            groups: vec![],
+        }
+    }
+
+    /// Add a benchmark group. When the group runs, `define` is called with it to configure the
+    /// group and add its benchmarks, and the group is finished afterwards.
+    ///
+    /// # Panics:
+    /// Panics if the group name is empty
+    pub fn benchmark_group<S, F>(&mut self, group_name: S, define: F) -> &mut Self
+    where
+        S: Into<String>,
+        F: FnOnce(&mut BenchmarkGroup<'_, M>) + 'a,
+    {
+        let group_name = group_name.into();
+        assert!(!group_name.is_empty(), "Group name must not be empty.");
+        self.groups.push((group_name, Box::new(define)));
+        self
+    }
+
+    /// Run the benchmark groups, in random order with `--shuffle`. This is called automatically
+    /// when the `ShuffledGroups` is dropped.
+    pub fn finish(self) {
+        ::std::mem::drop(self);
+    }
+}
+impl<'a, M: Measurement> Drop for ShuffledGroups<'a, M> {
+    fn drop(&mut self) {
+        shuffle::shuffle(&mut self.groups);
+        for (group_name, define) in self.groups.drain(..) {
+            let mut group = self.criterion.benchmark_group(group_name);
+            define(&mut group);
+            group.finish();
+        }
+    }
+}
+
+// A hook which runs before or after every batch of iterations of a fixture group
+type Hook<'b, S> = Box<dyn FnMut(&mut S) + 'b>;
+
+// A benchmarked function of a fixture group, which gets the fixture
+type FixtureRoutine<'b, M, S> = Box<dyn FnMut(&mut Bencher<'_, M>, &mut S) + 'b>;
+
+// A benchmark of a fixture group
+struct FixtureBenchmark<'b, M: Measurement, S> {
+    id: InternalBenchmarkId,
+    do_run: bool,
+    routine: FixtureRoutine<'b, M, S>,
+    config: BenchmarkConfig,
+    report_context: ReportContext,
+    throughput: Option<Throughput>,
+}
+
+/// A set of benchmarks in a [`BenchmarkGroup`](struct.BenchmarkGroup.html) which share a fixture
+/// of type `S`. Created by [`BenchmarkGroup::setup`](struct.BenchmarkGroup.html#method.setup).
+///
+/// Every benchmark gets mutable access to the fixture, and the changes it makes are seen by the
+/// benchmarks after it. The per-sample hooks can be used to restore the fixture between samples.
+///
+/// With `--shuffle`, the benchmarks only run when the `FixtureGroup` is finished, so the
+/// benchmarked functions and their inputs have to outlive it.
+pub struct FixtureGroup<'b, 'a: 'b, M: Measurement, S> {
+    group: &'b mut BenchmarkGroup<'a, M>,
+    setup: Option<Box<dyn FnOnce() -> S + 'b>>,
+    fixture: Option<S>,
+    teardown: Option<Box<dyn FnOnce(S) + 'b>>,
+    before_sample: Option<Hook<'b, S>>,
+    after_sample: Option<Hook<'b, S>>,
+    // The benchmarks which run when the group is finished, because the benchmarks are shuffled
+    deferred: Vec<FixtureBenchmark<'b, M, S>>,
+}
+impl<'b, 'a: 'b, M: Measurement, S> FixtureGroup<'b, 'a, M, S> {
+    /// Sets the function which tears down the fixture once the benchmarks are finished. Without
+    /// it, the fixture is simply dropped.
+    pub fn teardown<F>(&mut self, teardown: F) -> &mut Self
+    where
+        F: FnOnce(S) + 'b,
+    {
+        self.teardown = Some(Box::new(teardown));
+        self
+    }
+
+    /// Sets a hook which runs before every batch of iterations of the benchmarks, ie. before each
+    /// sample and each batch of the warm-up. Its time is not measured.
+    pub fn before_sample<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: FnMut(&mut S) + 'b,
+    {
+        self.before_sample = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets a hook which runs after every batch of iterations of the benchmarks, ie. after each
+    /// sample and each batch of the warm-up. Its time is not measured.
+    pub fn after_sample<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: FnMut(&mut S) + 'b,
+    {
+        self.after_sample = Some(Box::new(hook));
+        self
+    }
+
+    /// Benchmark the given function, which gets the fixture, inside this benchmark group.
+    pub fn bench_function<ID: IntoBenchmarkId, F>(&mut self, id: ID, f: F) -> &mut Self
+    where
+        F: FnMut(&mut Bencher<'_, M>, &mut S) + 'b,
+    {
+        self.add(id.into_benchmark_id(), Box::new(f));
+        self
+    }
+
+    /// Benchmark the given parameterized function, which gets the fixture, inside this benchmark
+    /// group.
+    pub fn bench_with_input<ID: IntoBenchmarkId, F, I>(
+        &mut self,
+        id: ID,
+        input: &'b I,
+        mut f: F,
+    ) -> &mut Self
+    where
+        F: FnMut(&mut Bencher<'_, M>, &mut S, &I) + 'b,
+        I: ?Sized,
+    {
+        self.add(
+            id.into_benchmark_id(),
+            Box::new(move |b, fixture| f(b, fixture, input)),
+        );
+        self
+    }
+
+    fn add(&mut self, id: BenchmarkId, routine: FixtureRoutine<'b, M, S>) {
+        let group = &mut *self.group;
+        let config = group.partial_config.to_complete(&group.criterion.config);
+        let report_context = group.report_context();
+        let (id, do_run) = group.register(id);
+        let throughput = group.throughput.clone();
+
+        let benchmark = FixtureBenchmark {
+            id,
+            do_run,
+            routine,
+            config,
+            report_context,
+            throughput,
+        };
+        if shuffle::is_active() {
+            self.deferred.push(benchmark);
+        } else {
+            self.run(benchmark);
+        }
+    }
+
+    fn run(&mut self, mut benchmark: FixtureBenchmark<'b, M, S>) {
+        let group = &mut *self.group;
+
+        // The fixture is built before the routine runs, so that it counts neither towards the
+        // measurements nor towards the timeout of the benchmark. If the setup panics, this
+        // benchmark and the remaining ones fail without running.
+        if group.runs_routine(benchmark.do_run) && self.fixture.is_none() {
+            let criterion = &*group.criterion;
+            match self.setup.take() {
+                Some(setup) => {
+                    self.fixture =
+                        criterion.catch_failure(&benchmark.id, &benchmark.report_context, setup);
+                }
+                None => criterion.benchmark_failed(
+                    &benchmark.id,
+                    &benchmark.report_context,
+                    "the setup of the fixture panicked in an earlier benchmark",
+                ),
+            }
+            if self.fixture.is_none() {
+                return;
+            }
+        }
+
+        let fixture = &mut self.fixture;
+        let before_sample = &mut self.before_sample;
+        let after_sample = &mut self.after_sample;
+        let routine = &mut benchmark.routine;
+        let mut func = Function::new(|b: &mut Bencher<'_, M>, _: &()| {
+            let fixture = fixture
+                .as_mut()
+                .expect("The fixture is built before the benchmark runs");
+            if let Some(hook) = before_sample {
+                hook(fixture);
+            }
+            routine(b, fixture);
+            if let Some(hook) = after_sample {
+                hook(fixture);
+            }
+        });
+
+        group.run_routine(
+            &benchmark.id,
+            benchmark.do_run,
+            &mut func,
+            &(),
+            &benchmark.config,
+            &benchmark.report_context,
+            benchmark.throughput.clone(),
+        );
+    }
+
+    /// Run the benchmarks if they are shuffled, and tear down the fixture, if it was built. This
+    /// is called automatically when the `FixtureGroup` is dropped.
+    pub fn finish(self) {
+        ::std::mem::drop(self);
+    }
+}
+impl<'b, 'a: 'b, M: Measurement, S> Drop for FixtureGroup<'b, 'a, M, S> {
+    fn drop(&mut self) {
+        let mut deferred = std::mem::take(&mut self.deferred);
+        shuffle::shuffle(&mut deferred);
+        for benchmark in deferred {
+            self.run(benchmark);
+        }
+
+        if let Some(fixture) = self.fixture.take() {
+            if let Some(teardown) = self.teardown.take() {
+                teardown(fixture);
+            }
+        }
+    }
+}
+
 /// Simple structure representing an ID for a benchmark. The ID must be unique within a benchmark
 /// group.
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct BenchmarkId {
     pub(crate) function_name: Option<String>,
     pub(crate) parameter: Option<String>,
+    pub(crate) parameters: Vec<(String, String)>,
+    pub(crate) tags: Vec<String>,
 }
 impl BenchmarkId {
     /// Construct a new benchmark ID from a string function name and a parameter value.
@@ -442,6 +1228,8 @@ impl BenchmarkId {
         BenchmarkId {
             function_name: Some(function_name.into()),
             parameter: Some(format!("{}", parameter)),
+            parameters: vec![],
+            tags: vec![],
         }
     }
 
@@ -451,13 +1239,86 @@ impl BenchmarkId {
         BenchmarkId {
             function_name: None,
             parameter: Some(format!("{}", parameter)),
+            parameters: vec![],
+            tags: vec![],
         }
     }
 
+    /// Construct a new benchmark ID from a string function name and several named parameter
+    /// values, for benchmarks that sweep over more than one dimension. The parameters are shown
+    /// as `name=value` pairs, saved in `benchmark.json`, and can be laid out on the axis and the
+    /// lines of the summary plots with
+    /// [`PlotConfiguration::x_axis_parameter`](struct.PlotConfiguration.html#method.x_axis_parameter)
+    /// and [`PlotConfiguration::line_parameters`](struct.PlotConfiguration.html#method.line_parameters).
+    /// See also [`BenchmarkGroup::bench_cartesian`](struct.BenchmarkGroup.html#method.bench_cartesian).
+    ///
+    /// # Examples
+    /// ```
+    /// # use criterion::BenchmarkId;
+    /// // Shown as "sort/size=1024,threads=4"
+    /// let id = BenchmarkId::with_parameters("sort", [("size", 1024), ("threads", 4)]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no parameters, or if two of them have the same name
+    pub fn with_parameters<S, I, K, V>(function_name: S, parameters: I) -> BenchmarkId
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: ::std::fmt::Display,
+    {
+        let parameters = parameters
+            .into_iter()
+            .map(|(name, value)| (name.into(), format!("{}", value)))
+            .collect::<Vec<_>>();
+        assert!(!parameters.is_empty(), "Parameters must not be empty.");
+        for (index, (name, _)) in parameters.iter().enumerate() {
+            assert!(
+                parameters[..index].iter().all(|(other, _)| other != name),
+                "Parameter names must be unique. Encountered duplicated parameter {}",
+                name
+            );
+        }
+
+        let parameter = parameters
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        BenchmarkId {
+            function_name: Some(function_name.into()),
+            parameter: Some(parameter),
+            parameters,
+            tags: vec![],
+        }
+    }
+
+    /// Adds tags to this benchmark ID, in addition to the tags of its group. Tags can be used to
+    /// select benchmarks with `--tag` and `--skip-tag` or with
+    /// [`BenchmarkFilter::Tag`](enum.BenchmarkFilter.html#variant.Tag), and are shown by `--list`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use criterion::BenchmarkId;
+    /// let id = BenchmarkId::new("end_to_end", 1000).with_tags(["slow", "network"]);
+    /// ```
+    pub fn with_tags<I>(mut self, tags: I) -> BenchmarkId
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
     pub(crate) fn no_function() -> BenchmarkId {
         BenchmarkId {
             function_name: None,
             parameter: None,
+            parameters: vec![],
+            tags: vec![],
         }
     }
 
@@ -465,6 +1326,8 @@ impl BenchmarkId {
         BenchmarkId {
             function_name: None,
             parameter: Some(format!("{}", parameter)),
+            parameters: vec![],
+            tags: vec![],
         }
     }
 }
@@ -473,8 +1336,75 @@ mod private {
     pub trait Sealed {}
     impl Sealed for super::BenchmarkId {}
     impl<S: Into<String>> Sealed for S {}
+
+    pub trait SealedDimensions {}
+}
+
+/// The names and displayed values of the parameters of a benchmark.
+type Parameters = Vec<(String, String)>;
+
+/// Sealed trait for the dimensions of
+/// [`BenchmarkGroup::bench_cartesian`](struct.BenchmarkGroup.html#method.bench_cartesian): tuples
+/// of one to four `(name, values)` pairs, where the name converts into a string and the values
+/// are an iterator of displayable and cloneable values.
+pub trait Dimensions: private::SealedDimensions {
+    /// A tuple of one value of each dimension.
+    type Point;
+
+    /// Returns every combination of the values of the dimensions, with the last dimension
+    /// changing fastest, along with the names and displayed values of the parameters.
+    fn product(self) -> Vec<(Parameters, Self::Point)>;
+}
+
+macro_rules! impl_dimensions {
+    ($(($name:ident, $values:ident, $index:tt)),+) => {
+        impl<$($name, $values),+> private::SealedDimensions for ($(($name, $values),)+)
+        where
+            $($name: Into<String>, $values: IntoIterator,
+            $values::Item: ::std::fmt::Display + Clone,)+
+        {
+        }
+
+        impl<$($name, $values),+> Dimensions for ($(($name, $values),)+)
+        where
+            $($name: Into<String>, $values: IntoIterator,
+            $values::Item: ::std::fmt::Display + Clone,)+
+        {
+            type Point = ($($values::Item,)+);
+
+            fn product(self) -> Vec<(Parameters, Self::Point)> {
+                let dimensions = ($({
+                    let (name, values) = self.$index;
+                    (name.into(), values.into_iter().collect::<Vec<_>>())
+                },)+);
+                let lengths = [$(dimensions.$index.1.len()),+];
+                let count = lengths.iter().product::<usize>();
+
+                (0..count)
+                    .map(|mut combination| {
+                        let mut indices = lengths;
+                        for (index, length) in indices.iter_mut().zip(lengths.iter()).rev() {
+                            *index = combination % length;
+                            combination /= length;
+                        }
+                        let parameters = vec![$((
+                            dimensions.$index.0.clone(),
+                            format!("{}", dimensions.$index.1[indices[$index]]),
+                        )),+];
+                        let point = ($(dimensions.$index.1[indices[$index]].clone(),)+);
+                        (parameters, point)
+                    })
+                    .collect()
+            }
+        }
+    };
 }
 
+impl_dimensions!((N0, V0, 0));
+impl_dimensions!((N0, V0, 0), (N1, V1, 1));
+impl_dimensions!((N0, V0, 0), (N1, V1, 1), (N2, V2, 2));
+impl_dimensions!((N0, V0, 0), (N1, V1, 1), (N2, V2, 2), (N3, V3, 3));
+
 /// Sealed trait which allows users to automatically convert strings to benchmark IDs.
 pub trait IntoBenchmarkId: private::Sealed {
     fn into_benchmark_id(self) -> BenchmarkId;
@@ -495,6 +1425,8 @@ impl<S: Into<String>> IntoBenchmarkId for S {
         BenchmarkId {
             function_name: Some(function_name),
             parameter: None,
+            parameters: vec![],
+            tags: vec![],
         }
     }
 }
@@ -0,0 +1,121 @@
+//! Spreading a time budget for the whole benchmark suite across the matched benchmarks.
+//!
+//! The number of benchmarks matched by the filter is only known once every benchmark function has
+//! run, so it is estimated up front by re-executing the benchmark binary with the same
+//! command-line arguments and `--list`. Before each benchmark, the rest of the budget is split
+//! evenly between the benchmarks that are left, and the warm-up and measurement times of the
+//! benchmark are scaled down to fit its share. Benchmarks that finish early, eg. because their
+//! steady-state warm-up ended before the maximum warm-up time, leave more time to the rest.
+//!
+//! Every benchmark function of `criterion_main!` creates its own `Criterion`, so the start of the
+//! budget and the number of benchmarks left are kept for the whole process.
+
+use crate::benchmark::BenchmarkConfig;
+use once_cell::sync::Lazy;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const SCALE_VAR: &str = "CRITERION_TIME_BUDGET_SCALE";
+
+/// Scaled times are never shorter than this, so that every benchmark can at least be warmed up
+/// and sampled once.
+const MIN_TIME: Duration = Duration::from_millis(1);
+
+/// The time budget of this process, once the first benchmark has started.
+static STATE: Lazy<Mutex<Option<State>>> = Lazy::new(|| Mutex::new(None));
+
+struct State {
+    start: Instant,
+    // The number of matched benchmarks which haven't started yet, if they could be counted
+    remaining: Option<usize>,
+}
+
+/// Scales the warm-up and measurement times of the next benchmarks down, so that each of them takes
+/// no more than its share of the rest of the budget `total`. Benchmarks that are run together, like
+/// interleaved benchmarks, get their shares at the same time.
+pub(crate) fn apply<'c>(
+    total: Duration,
+    configs: impl IntoIterator<Item = &'c mut BenchmarkConfig>,
+) {
+    let mut state = STATE.lock().unwrap();
+    let state = state.get_or_insert_with(|| {
+        let start = Instant::now();
+        let remaining = count_benchmarks();
+        if remaining.is_none() {
+            eprintln!(
+                "\nWarning: Unable to count the benchmarks to run; ignoring the time budget."
+            );
+        }
+        State { start, remaining }
+    });
+    let remaining = match state.remaining {
+        Some(remaining) => remaining,
+        None => return,
+    };
+
+    let mut configs = configs.into_iter().collect::<Vec<_>>();
+    // More benchmarks than were listed may run, eg. if they are defined conditionally
+    let remaining = remaining.max(configs.len()).max(1);
+    state.remaining = Some(remaining - configs.len());
+
+    let share = total.saturating_sub(state.start.elapsed()) / remaining as u32;
+    for config in configs.iter_mut() {
+        let nominal = (config.warm_up_time + config.measurement_time) * config.repetitions as u32;
+        let scale = share.as_secs_f64() / nominal.as_secs_f64();
+        if scale < 1.0 {
+            scale_config(config, scale);
+        }
+    }
+}
+
+/// Returns the scale applied by the parent process if this is an isolated child process of a
+/// benchmark run with a time budget.
+pub(crate) fn child_scale() -> Option<f64> {
+    std::env::var(SCALE_VAR)
+        .ok()
+        .and_then(|scale| scale.parse().ok())
+}
+
+/// Passes the scale of the benchmark's times to an isolated child process.
+pub(crate) fn pass_scale_to_child(command: &mut Command, config: &BenchmarkConfig) {
+    if let Some(scale) = config.time_budget_scale {
+        command.env(SCALE_VAR, scale.to_string());
+    }
+}
+
+/// Scales the warm-up and measurement times (and their maximums) of the benchmark by `scale`.
+pub(crate) fn scale_config(config: &mut BenchmarkConfig, scale: f64) {
+    let scaled = |time: Duration| time.mul_f64(scale).max(MIN_TIME);
+    config.warm_up_time = scaled(config.warm_up_time);
+    config.measurement_time = scaled(config.measurement_time);
+    config.max_warm_up_time = scaled(config.max_warm_up_time);
+    config.max_measurement_time = scaled(config.max_measurement_time);
+    config.time_budget_scale = Some(scale);
+}
+
+// Lists the benchmarks matched by the filter by re-running the benchmark binary with `--list`
+fn count_benchmarks() -> Option<usize> {
+    let exe = std::env::current_exe().ok()?;
+    let output = Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .arg("--list")
+        .env_remove("CARGO_CRITERION_PORT")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let count = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.ends_with(": benchmark"))
+        .count();
+    if count == 0 {
+        None
+    } else {
+        Some(count)
+    }
+}
@@ -0,0 +1,91 @@
+//! Evicting the CPU caches between iterations, for `BatchSize::ColdPerIteration`.
+//!
+//! There is no portable instruction to flush the whole cache hierarchy, so the caches are evicted
+//! by writing to every cache line of a buffer that is larger than the last-level cache. The buffer
+//! is owned by the `Bencher`, so it is only allocated while a benchmark samples cold iterations.
+
+/// The size of a cache line on all common platforms.
+const CACHE_LINE: usize = 64;
+
+/// The buffer size used if the size of the last-level cache is unknown. This is larger than the
+/// last-level cache of most desktop and server CPUs.
+const DEFAULT_BUFFER_SIZE: usize = 64 << 20;
+
+/// The largest buffer that is allocated, even if the last-level cache is reported to be larger.
+const MAX_BUFFER_SIZE: usize = 512 << 20;
+
+/// A buffer larger than the last-level cache.
+pub(crate) struct EvictionBuffer {
+    buffer: Vec<u8>,
+}
+impl EvictionBuffer {
+    pub(crate) fn new() -> EvictionBuffer {
+        // Caches are not always fully associative, so twice the size is used to evict every line
+        let size = last_level_cache_size()
+            .map(|size| (size * 2).min(MAX_BUFFER_SIZE))
+            .unwrap_or(DEFAULT_BUFFER_SIZE);
+        EvictionBuffer {
+            buffer: vec![0; size],
+        }
+    }
+
+    /// Evicts the benchmark's data from the CPU caches by streaming through the buffer.
+    pub(crate) fn evict(&mut self) {
+        // Writing makes the lines dirty, so the cache has to evict them instead of just dropping
+        // them
+        for byte in self.buffer.iter_mut().step_by(CACHE_LINE) {
+            *byte = byte.wrapping_add(1);
+        }
+        crate::black_box(&mut self.buffer);
+    }
+}
+
+// The largest cache of the first CPU, which is shared by the other cores
+#[cfg(target_os = "linux")]
+fn last_level_cache_size() -> Option<usize> {
+    std::fs::read_dir("/sys/devices/system/cpu/cpu0/cache")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("size")).ok())
+        .filter_map(|size| parse_size(&size))
+        .max()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn last_level_cache_size() -> Option<usize> {
+    None
+}
+
+// Parses cache sizes like "32K" or "8M" from sysfs
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let (number, unit) = match size.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((index, _)) => size.split_at(index),
+        None => (size, ""),
+    };
+    let multiplier = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return None,
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .map(|number| number * multiplier)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("32K\n"), Some(32 << 10));
+        assert_eq!(parse_size("8M"), Some(8 << 20));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("big"), None);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::report::BenchmarkId as InternalBenchmarkId;
+use crate::routine::Measurements;
 use crate::Throughput;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::io::{Read, Write};
@@ -119,26 +122,42 @@ impl InnerConnection {
 
     #[allow(dead_code)]
     pub fn recv(&mut self) -> Result<IncomingMessage, MessageError> {
-        let mut length_buf = [0u8; 4];
-        self.socket.read_exact(&mut length_buf)?;
-        let length = u32::from_be_bytes(length_buf);
-        self.receive_buffer.resize(length as usize, 0u8);
-        self.socket.read_exact(&mut self.receive_buffer)?;
-        let value = ciborium::de::from_reader(&self.receive_buffer[..])?;
-        Ok(value)
+        read_message(&mut self.socket, &mut self.receive_buffer)
     }
 
     pub fn send(&mut self, message: &OutgoingMessage) -> Result<(), MessageError> {
-        self.send_buffer.truncate(0);
-        ciborium::ser::into_writer(message, &mut self.send_buffer)?;
-        let size = u32::try_from(self.send_buffer.len()).unwrap();
-        let length_buf = size.to_be_bytes();
-        self.socket.write_all(&length_buf)?;
-        self.socket.write_all(&self.send_buffer)?;
-        Ok(())
+        write_message(&mut self.socket, &mut self.send_buffer, message)
     }
 }
 
+// Messages are framed as their length, as a big-endian u32, followed by the message in CBOR.
+fn read_message<T: DeserializeOwned>(
+    reader: &mut impl Read,
+    buffer: &mut Vec<u8>,
+) -> Result<T, MessageError> {
+    let mut length_buf = [0u8; 4];
+    reader.read_exact(&mut length_buf)?;
+    let length = u32::from_be_bytes(length_buf);
+    buffer.resize(length as usize, 0u8);
+    reader.read_exact(buffer)?;
+    let value = ciborium::de::from_reader(&buffer[..])?;
+    Ok(value)
+}
+
+fn write_message<T: Serialize>(
+    writer: &mut impl Write,
+    buffer: &mut Vec<u8>,
+    message: &T,
+) -> Result<(), MessageError> {
+    buffer.truncate(0);
+    ciborium::ser::into_writer(message, &mut *buffer)?;
+    let size = u32::try_from(buffer.len()).unwrap();
+    let length_buf = size.to_be_bytes();
+    writer.write_all(&length_buf)?;
+    writer.write_all(buffer)?;
+    Ok(())
+}
+
 /// This is really just a holder to allow us to send messages through a shared reference to the
 /// connection.
 #[derive(Debug)]
@@ -255,6 +274,10 @@ pub enum OutgoingMessage<'a> {
     SkippingBenchmark {
         id: RawBenchmarkId,
     },
+    FailedBenchmark {
+        id: RawBenchmarkId,
+        message: &'a str,
+    },
     Warmup {
         id: RawBenchmarkId,
         nanos: f64,
@@ -283,6 +306,25 @@ pub enum OutgoingMessage<'a> {
     },
 }
 
+/// Enum defining the messages sent from a benchmark running in an isolated child process to the
+/// parent process
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum IsolatedMessage {
+    MeasurementComplete {
+        measurements: Measurements,
+        threads: Option<usize>,
+    },
+}
+impl IsolatedMessage {
+    pub(crate) fn send(&self, writer: &mut impl Write) -> Result<(), MessageError> {
+        write_message(writer, &mut vec![], self)
+    }
+
+    pub(crate) fn recv(reader: &mut impl Read) -> Result<Self, MessageError> {
+        read_message(reader, &mut vec![])
+    }
+}
+
 // Also define serializable variants of certain things, either to avoid leaking
 // serializability into the public interface or because the serialized form
 // is a bit different from the regular one.
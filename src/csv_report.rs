@@ -87,5 +87,9 @@ impl Report for FileCsvReport {
         path.push("new");
         path.push("raw.csv");
         log_if_err!(self.write_file(&path, id, measurements, formatter));
+
+        for metric in &measurements.metrics {
+            self.measurement_complete(&metric.id, context, &metric.measurements, metric.formatter);
+        }
     }
 }
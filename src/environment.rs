@@ -0,0 +1,305 @@
+//! Inspection of the machine's configuration for settings that make benchmark results noisy.
+//!
+//! The snapshot is read from `/sys` and `/proc` below a configurable root directory, so that it
+//! can be tested against a fake tree. Settings that can't be read (eg. on other operating systems)
+//! are recorded as unknown and not warned about. The environment is inspected once per process.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+// The snapshots of the environment, by the root they were read below
+static SNAPSHOTS: Lazy<Mutex<HashMap<PathBuf, Environment>>> = Lazy::new(Default::default);
+
+/// A snapshot of the settings of the machine that affect benchmark results. It is saved as
+/// `environment.json` next to `benchmark.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Environment {
+    /// The model name of the CPU.
+    pub(crate) cpu_model: Option<String>,
+    /// The distinct CPU frequency scaling governors of all cores.
+    pub(crate) cpu_governors: Option<Vec<String>>,
+    /// Whether turbo boost is enabled.
+    pub(crate) turbo: Option<bool>,
+    /// Whether simultaneous multithreading (hyper-threading) is active.
+    pub(crate) smt: Option<bool>,
+    /// Whether address space layout randomization is enabled.
+    pub(crate) aslr: Option<bool>,
+    /// The load average over the last minute.
+    pub(crate) load_average: Option<f64>,
+    /// Whether the benchmark runs under a debugger or tracer.
+    pub(crate) debugger: Option<bool>,
+    /// Whether the benchmark was compiled with debug assertions, eg. in a debug build.
+    pub(crate) debug_build: bool,
+}
+impl Environment {
+    /// Returns the snapshot of the environment below `root`, which is inspected the first time
+    /// it is needed.
+    pub(crate) fn snapshot(root: &Path) -> Environment {
+        SNAPSHOTS
+            .lock()
+            .unwrap()
+            .entry(root.to_owned())
+            .or_insert_with(|| Environment::inspect(root))
+            .clone()
+    }
+
+    /// Inspect the environment, reading `/sys` and `/proc` below `root`.
+    pub(crate) fn inspect(root: &Path) -> Environment {
+        let read = |path: &str| -> Option<String> {
+            fs::read_to_string(root.join(path))
+                .ok()
+                .map(|contents| contents.trim().to_owned())
+        };
+        let flag = |path: &str| read(path).map(|value| value != "0");
+
+        let cpu_model = read("proc/cpuinfo").and_then(|cpuinfo| {
+            cpuinfo
+                .lines()
+                .find(|line| line.starts_with("model name"))
+                .and_then(|line| line.split(':').nth(1))
+                .map(|model| model.trim().to_owned())
+        });
+
+        let cpu_governors = fs::read_dir(root.join("sys/devices/system/cpu"))
+            .ok()
+            .map(|entries| {
+                let mut governors = entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| {
+                        let name = entry.file_name();
+                        let name = name.to_string_lossy();
+                        name.starts_with("cpu") && name[3..].parse::<usize>().is_ok()
+                    })
+                    .filter_map(|entry| {
+                        fs::read_to_string(entry.path().join("cpufreq/scaling_governor")).ok()
+                    })
+                    .map(|governor| governor.trim().to_owned())
+                    .collect::<Vec<_>>();
+                governors.sort();
+                governors.dedup();
+                governors
+            })
+            .filter(|governors| !governors.is_empty());
+
+        // intel_pstate reports whether turbo is disabled, acpi-cpufreq whether boost is enabled
+        let turbo = flag("sys/devices/system/cpu/intel_pstate/no_turbo")
+            .map(|no_turbo| !no_turbo)
+            .or_else(|| flag("sys/devices/system/cpu/cpufreq/boost"));
+
+        let load_average = read("proc/loadavg")
+            .and_then(|loadavg| loadavg.split_whitespace().next()?.parse().ok());
+
+        let debugger = read("proc/self/status").and_then(|status| {
+            status
+                .lines()
+                .find(|line| line.starts_with("TracerPid:"))
+                .map(|line| line["TracerPid:".len()..].trim() != "0")
+        });
+
+        Environment {
+            cpu_model,
+            cpu_governors,
+            turbo,
+            smt: flag("sys/devices/system/cpu/smt/active"),
+            aslr: flag("proc/sys/kernel/randomize_va_space"),
+            load_average,
+            debugger,
+            debug_build: cfg!(debug_assertions),
+        }
+    }
+
+    /// Returns descriptions of the settings that are likely to make the results noisy. SMT and
+    /// ASLR are only recorded: SMT only adds noise when the sibling core is busy, which the load
+    /// average shows, and ASLR changes the results between processes rather than between samples.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if let Some(governors) = &self.cpu_governors {
+            if governors.iter().any(|governor| governor != "performance") {
+                problems.push(format!(
+                    "the CPU frequency governor is '{}' instead of 'performance'",
+                    governors.join("', '")
+                ));
+            }
+        }
+        if self.turbo == Some(true) {
+            problems.push("turbo boost is enabled".to_owned());
+        }
+        if let Some(load_average) = self.load_average {
+            if load_average > 1.0 {
+                problems.push(format!("the load average is {:.2}", load_average));
+            }
+        }
+        if self.debugger == Some(true) {
+            problems.push("the benchmark is running under a debugger".to_owned());
+        }
+        if self.debug_build {
+            problems.push("the benchmark was compiled with debug assertions".to_owned());
+        }
+        problems
+    }
+
+    /// Prints a warning listing the problems with the environment, once per process.
+    pub(crate) fn warn_once(&self) {
+        let problems = self.problems();
+        if problems.is_empty() || WARNED.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        eprintln!("\nWarning: The results are likely to be noisy because");
+        for problem in problems {
+            eprintln!("  - {}", problem);
+        }
+    }
+
+    /// Returns descriptions of the settings that differ between the two snapshots. The load
+    /// average changes all the time and is not compared.
+    pub(crate) fn differences(&self, other: &Environment) -> Vec<String> {
+        fn describe<T: std::fmt::Debug>(value: &Option<T>) -> String {
+            match value {
+                Some(value) => format!("{:?}", value),
+                None => "unknown".to_owned(),
+            }
+        }
+
+        let mut differences = vec![];
+        let mut compare = |name: &str, this: String, other: String| {
+            if this != other {
+                differences.push(format!("{}: {} -> {}", name, this, other));
+            }
+        };
+        compare(
+            "CPU model",
+            describe(&self.cpu_model),
+            describe(&other.cpu_model),
+        );
+        compare(
+            "CPU governor",
+            describe(&self.cpu_governors),
+            describe(&other.cpu_governors),
+        );
+        compare("turbo", describe(&self.turbo), describe(&other.turbo));
+        compare("SMT", describe(&self.smt), describe(&other.smt));
+        compare("ASLR", describe(&self.aslr), describe(&other.aslr));
+        compare(
+            "debugger",
+            describe(&self.debugger),
+            describe(&other.debugger),
+        );
+        compare(
+            "debug build",
+            self.debug_build.to_string(),
+            other.debug_build.to_string(),
+        );
+        differences
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path: PathBuf = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn fake_tree() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "proc/cpuinfo",
+            "processor\t: 0\nmodel name\t: Fake CPU @ 3.00GHz\n",
+        );
+        write(root, "proc/loadavg", "2.50 1.00 0.50 1/100 1234\n");
+        write(root, "proc/self/status", "Name:\tbench\nTracerPid:\t0\n");
+        write(root, "proc/sys/kernel/randomize_va_space", "0\n");
+        write(
+            root,
+            "sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+            "performance\n",
+        );
+        write(
+            root,
+            "sys/devices/system/cpu/cpu1/cpufreq/scaling_governor",
+            "powersave\n",
+        );
+        write(
+            root,
+            "sys/devices/system/cpu/cpufreq/policy0/scaling_governor",
+            "ignored\n",
+        );
+        write(root, "sys/devices/system/cpu/intel_pstate/no_turbo", "1\n");
+        write(root, "sys/devices/system/cpu/smt/active", "1\n");
+        dir
+    }
+
+    #[test]
+    fn test_inspect() {
+        let dir = fake_tree();
+        let environment = Environment::inspect(dir.path());
+
+        assert_eq!(environment.cpu_model.as_deref(), Some("Fake CPU @ 3.00GHz"));
+        assert_eq!(
+            environment.cpu_governors,
+            Some(vec!["performance".to_owned(), "powersave".to_owned()])
+        );
+        assert_eq!(environment.turbo, Some(false));
+        assert_eq!(environment.smt, Some(true));
+        assert_eq!(environment.aslr, Some(false));
+        assert_eq!(environment.load_average, Some(2.5));
+        assert_eq!(environment.debugger, Some(false));
+
+        let problems = environment.problems();
+        assert!(problems.iter().any(|problem| problem.contains("powersave")));
+        assert!(!problems
+            .iter()
+            .any(|problem| problem.contains("multithreading")));
+        assert!(problems.iter().any(|problem| problem.contains("2.50")));
+        assert!(!problems.iter().any(|problem| problem.contains("turbo")));
+        assert!(!problems
+            .iter()
+            .any(|problem| problem.contains("randomization")));
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let environment = Environment::inspect(dir.path());
+
+        assert_eq!(environment.cpu_governors, None);
+        assert_eq!(environment.turbo, None);
+        assert_eq!(environment.load_average, None);
+        assert_eq!(
+            environment.problems().len(),
+            environment.debug_build as usize
+        );
+    }
+
+    #[test]
+    fn test_differences() {
+        let dir = fake_tree();
+        let before = Environment::inspect(dir.path());
+        write(
+            dir.path(),
+            "sys/devices/system/cpu/intel_pstate/no_turbo",
+            "0\n",
+        );
+        write(dir.path(), "proc/loadavg", "0.10 0.10 0.10 1/100 1234\n");
+        let after = Environment::inspect(dir.path());
+
+        assert_eq!(
+            before.differences(&after),
+            vec!["turbo: false -> true".to_owned()]
+        );
+        assert!(before.differences(&before).is_empty());
+    }
+}
@@ -22,6 +22,10 @@ pub enum Error {
         path: PathBuf,
         inner: SerdeError,
     },
+    IsolatedProcessError {
+        id: String,
+        reason: String,
+    },
     #[cfg(feature = "csv_output")]
     /// This API requires the following crate features to be activated: csv_output
     CsvError(CsvError),
@@ -40,6 +44,11 @@ impl fmt::Display for Error {
                 "Failed to read or write file {:?} due to serialization error: {}",
                 path, inner
             ),
+            Error::IsolatedProcessError { id, reason } => write!(
+                f,
+                "Benchmark {} failed in its isolated process: {}",
+                id, reason
+            ),
             #[cfg(feature = "csv_output")]
             Error::CsvError(inner) => write!(f, "CSV error: {}", inner),
         }
@@ -51,6 +60,7 @@ impl StdError for Error {
             Error::AccessError { .. } => "AccessError",
             Error::CopyError { .. } => "CopyError",
             Error::SerdeError { .. } => "SerdeError",
+            Error::IsolatedProcessError { .. } => "IsolatedProcessError",
             #[cfg(feature = "csv_output")]
             Error::CsvError(_) => "CsvError",
         }
@@ -61,6 +71,7 @@ impl StdError for Error {
             Error::AccessError { inner, .. } => Some(inner),
             Error::CopyError { inner, .. } => Some(inner),
             Error::SerdeError { inner, .. } => Some(inner),
+            Error::IsolatedProcessError { .. } => None,
             #[cfg(feature = "csv_output")]
             Error::CsvError(inner) => Some(inner),
         }
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use crate::stats::univariate::Sample;
 use crate::stats::Distribution;
 
 #[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize, Debug)]
@@ -67,6 +68,7 @@ pub fn build_estimates(
         median_abs_dev: to_estimate(points.median_abs_dev, &distributions.median_abs_dev),
         slope: None,
         std_dev: to_estimate(points.std_dev, &distributions.std_dev),
+        percentiles: None,
     }
 }
 
@@ -109,6 +111,9 @@ pub struct Estimates {
     pub median_abs_dev: Estimate,
     pub slope: Option<Estimate>,
     pub std_dev: Estimate,
+    /// Percentiles of the per-iteration latency, only estimated with `SamplingMode::PerIteration`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percentiles: Option<PercentileEstimates>,
 }
 impl Estimates {
     pub fn typical(&self) -> &Estimate {
@@ -126,6 +131,79 @@ impl Estimates {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PercentileEstimates {
+    pub p50: Estimate,
+    pub p90: Estimate,
+    pub p99: Estimate,
+    pub p999: Estimate,
+}
+impl PercentileEstimates {
+    /// The estimates with their labels, from the lowest to the highest percentile.
+    pub fn labeled(&self) -> [(&'static str, &Estimate); 4] {
+        [
+            ("p50", &self.p50),
+            ("p90", &self.p90),
+            ("p99", &self.p99),
+            ("p99.9", &self.p999),
+        ]
+    }
+}
+
+/// The estimates of every repetition of a benchmark, from the first to the last.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Repetitions {
+    pub estimates: Vec<Estimates>,
+    pub spread: RepetitionSpread,
+}
+impl Repetitions {
+    pub fn new(estimates: Vec<Estimates>) -> Repetitions {
+        let spread = RepetitionSpread::new(&Repetitions::typical_of(&estimates));
+        Repetitions { estimates, spread }
+    }
+
+    /// The typical estimate of each repetition.
+    pub fn typical(&self) -> Vec<f64> {
+        Repetitions::typical_of(&self.estimates)
+    }
+
+    fn typical_of(estimates: &[Estimates]) -> Vec<f64> {
+        estimates
+            .iter()
+            .map(|estimates| estimates.typical().point_estimate)
+            .collect()
+    }
+}
+
+/// The spread of the typical estimates of the repetitions of a benchmark. Unlike the confidence
+/// intervals of a single run, this includes the noise between runs.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RepetitionSpread {
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+impl RepetitionSpread {
+    fn new(typical: &[f64]) -> RepetitionSpread {
+        let sample = Sample::new(typical);
+        let mean = sample.mean();
+        RepetitionSpread {
+            mean,
+            median: sample.percentiles().median(),
+            std_dev: sample.std_dev(Some(mean)),
+            min: sample.min(),
+            max: sample.max(),
+        }
+    }
+
+    /// The standard deviation relative to the mean.
+    pub fn coefficient_of_variation(&self) -> f64 {
+        self.std_dev / self.mean
+    }
+}
+
 pub struct Distributions {
     pub mean: Distribution<f64>,
     pub median: Distribution<f64>,
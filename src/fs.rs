@@ -3,7 +3,7 @@ use serde::Serialize;
 use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::{DirEntry, WalkDir};
 
 use crate::error::{Error, Result};
@@ -57,6 +57,26 @@ pub fn cp(from: &Path, to: &Path) -> Result<()> {
     Ok(())
 }
 
+pub fn list_files<P>(directory: &P) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let access_error = |inner| Error::AccessError {
+        inner,
+        path: directory.as_ref().to_owned(),
+    };
+
+    let mut files = vec![];
+    for entry in fs::read_dir(directory.as_ref()).map_err(access_error)? {
+        let path = entry.map_err(access_error)?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
 pub fn save<D, P>(data: &D, path: &P) -> Result<()>
 where
     D: Serialize,
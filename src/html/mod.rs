@@ -1,4 +1,6 @@
-use crate::report::{make_filename_safe, BenchmarkId, MeasurementData, Report, ReportContext};
+use crate::report::{
+    make_filename_safe, BenchmarkId, MeasurementData, MetricData, Report, ReportContext, ValueType,
+};
 use crate::stats::bivariate::regression::Slope;
 
 use crate::estimate::Estimate;
@@ -6,7 +8,7 @@ use crate::format;
 use crate::fs;
 use crate::measurement::ValueFormatter;
 use crate::plot::{PlotContext, PlotData, Plotter};
-use crate::SavedSample;
+use crate::{PlotConfiguration, SavedSample};
 use criterion_plot::Size;
 use serde::Serialize;
 use std::cell::RefCell;
@@ -44,10 +46,41 @@ struct Context {
     median: ConfidenceInterval,
     mad: ConfidenceInterval,
     throughput: Option<ConfidenceInterval>,
+    percentiles: Vec<Percentile>,
+    warm_up: Option<String>,
+    repetitions: Option<RepetitionSpread>,
 
     additional_plots: Vec<Plot>,
 
     comparison: Option<Comparison>,
+
+    metrics: Vec<Metric>,
+}
+
+#[derive(Serialize)]
+struct Percentile {
+    name: &'static str,
+    estimate: ConfidenceInterval,
+}
+
+#[derive(Serialize)]
+struct RepetitionSpread {
+    count: usize,
+    mean: String,
+    std_dev: String,
+    cv: String,
+    median: String,
+    min: String,
+    max: String,
+}
+
+#[derive(Serialize)]
+struct Metric {
+    name: String,
+    // Path of the metric's report directory, relative to the benchmark's report directory
+    path: String,
+    estimate: ConfidenceInterval,
+    change: Option<ConfidenceInterval>,
 }
 
 #[derive(Serialize)]
@@ -300,104 +333,22 @@ impl Report for Html {
         measurements: &MeasurementData<'_>,
         formatter: &dyn ValueFormatter,
     ) {
-        try_else_return!({
-            let mut report_dir = report_context.output_directory.clone();
-            report_dir.push(id.as_directory_name());
-            report_dir.push("report");
-            fs::mkdirp(&report_dir)
-        });
-
-        let typical_estimate = &measurements.absolute_estimates.typical();
-
-        let time_interval = |est: &Estimate| -> ConfidenceInterval {
-            ConfidenceInterval {
-                lower: formatter.format_value(est.confidence_interval.lower_bound),
-                point: formatter.format_value(est.point_estimate),
-                upper: formatter.format_value(est.confidence_interval.upper_bound),
-            }
-        };
-
-        let data = measurements.data;
-
-        elapsed! {
-            "Generating plots",
-            self.generate_plots(id, report_context, formatter, measurements)
-        }
-
-        let mut additional_plots = vec![
-            Plot::new("Typical", "typical.svg"),
-            Plot::new("Mean", "mean.svg"),
-            Plot::new("Std. Dev.", "SD.svg"),
-            Plot::new("Median", "median.svg"),
-            Plot::new("MAD", "MAD.svg"),
-        ];
-        if measurements.absolute_estimates.slope.is_some() {
-            additional_plots.push(Plot::new("Slope", "slope.svg"));
-        }
-
-        let throughput = measurements
-            .throughput
-            .as_ref()
-            .map(|thr| ConfidenceInterval {
-                lower: formatter
-                    .format_throughput(thr, typical_estimate.confidence_interval.upper_bound),
-                upper: formatter
-                    .format_throughput(thr, typical_estimate.confidence_interval.lower_bound),
-                point: formatter.format_throughput(thr, typical_estimate.point_estimate),
-            });
-
-        let context = Context {
-            title: id.as_title().to_owned(),
-            confidence: format!(
-                "{:.2}",
-                typical_estimate.confidence_interval.confidence_level
-            ),
-
-            thumbnail_width: THUMBNAIL_SIZE.unwrap().0,
-            thumbnail_height: THUMBNAIL_SIZE.unwrap().1,
-
-            slope: measurements
-                .absolute_estimates
-                .slope
-                .as_ref()
-                .map(time_interval),
-            mean: time_interval(&measurements.absolute_estimates.mean),
-            median: time_interval(&measurements.absolute_estimates.median),
-            mad: time_interval(&measurements.absolute_estimates.median_abs_dev),
-            std_dev: time_interval(&measurements.absolute_estimates.std_dev),
-            throughput,
-
-            r2: ConfidenceInterval {
-                lower: format!(
-                    "{:0.7}",
-                    Slope(typical_estimate.confidence_interval.lower_bound).r_squared(&data)
-                ),
-                upper: format!(
-                    "{:0.7}",
-                    Slope(typical_estimate.confidence_interval.upper_bound).r_squared(&data)
-                ),
-                point: format!(
-                    "{:0.7}",
-                    Slope(typical_estimate.point_estimate).r_squared(&data)
-                ),
-            },
-
-            additional_plots,
-
-            comparison: self.comparison(measurements),
-        };
-
-        let mut report_path = report_context.output_directory.clone();
-        report_path.push(id.as_directory_name());
-        report_path.push("report");
-        report_path.push("index.html");
-        debug_context(&report_path, &context);
+        let metrics = measurements
+            .metrics
+            .iter()
+            .map(|metric| {
+                self.benchmark_report(
+                    &metric.id,
+                    report_context,
+                    &metric.measurements,
+                    metric.formatter,
+                    vec![],
+                );
+                self.metric(id, metric)
+            })
+            .collect();
 
-        let text = self
-            .templates
-            .render("benchmark_report", &context)
-            .expect("Failed to render benchmark report template");
-        try_else_return!(fs::save_string(&text, &report_path));
+        self.benchmark_report(id, report_context, measurements, formatter, metrics);
     }
 
     fn summarize(
@@ -421,6 +372,7 @@ impl Report for Html {
 
         let data = self.load_summary_data(&context.output_directory, &all_ids);
 
+        let layout = SweepLayout::new(&all_ids, &context.plot_config);
         let mut function_ids = BTreeSet::new();
         let mut value_strs = Vec::with_capacity(all_ids.len());
         for id in all_ids {
@@ -432,10 +384,6 @@ impl Report for Html {
             }
         }
 
-        fn try_parse(s: &str) -> Option<f64> {
-            s.parse::<f64>().ok()
-        }
-
         // If all of the value strings can be parsed into a number, sort/dedupe
         // numerically. Otherwise sort lexicographically.
         if value_strs.iter().all(|os| try_parse(os).is_some()) {
@@ -487,6 +435,38 @@ impl Report for Html {
             }
         }
 
+        // Every combination of the values of the parameters that are neither on the x-axis nor
+        // lines gets a summary of its own
+        if let Some(layout) = layout.filter(|layout| !layout.separate.is_empty()) {
+            let mut combinations = vec![];
+            for (id, _) in &data {
+                let combination = layout.combination(id);
+                if !combinations.contains(&combination) {
+                    combinations.push(combination);
+                }
+            }
+
+            for combination in combinations {
+                let samples_with_combination: Vec<_> = data
+                    .iter()
+                    .filter(|&&(id, _)| layout.combination(id) == combination)
+                    .collect();
+
+                if samples_with_combination.len() > 1 {
+                    let subgroup_id =
+                        BenchmarkId::new(group_id.clone(), None, Some(combination), None);
+
+                    self.generate_summary(
+                        &subgroup_id,
+                        &samples_with_combination,
+                        context,
+                        formatter,
+                        false,
+                    );
+                }
+            }
+        }
+
         let mut all_data = data.iter().by_ref().collect::<Vec<_>>();
         // First sort the ids/data by value.
         // If all of the value strings can be parsed into a number, sort/dedupe
@@ -558,16 +538,180 @@ impl Report for Html {
     }
 }
 impl Html {
+    fn benchmark_report(
+        &self,
+        id: &BenchmarkId,
+        report_context: &ReportContext,
+        measurements: &MeasurementData<'_>,
+        formatter: &dyn ValueFormatter,
+        metrics: Vec<Metric>,
+    ) {
+        try_else_return!({
+            let mut report_dir = report_context.output_directory.clone();
+            report_dir.push(id.as_directory_name());
+            report_dir.push("report");
+            fs::mkdirp(&report_dir)
+        });
+
+        let typical_estimate = &measurements.absolute_estimates.typical();
+
+        let time_interval = |est: &Estimate| -> ConfidenceInterval {
+            ConfidenceInterval {
+                lower: formatter.format_value(est.confidence_interval.lower_bound),
+                point: formatter.format_value(est.point_estimate),
+                upper: formatter.format_value(est.confidence_interval.upper_bound),
+            }
+        };
+
+        let data = measurements.data;
+
+        elapsed! {
+            "Generating plots",
+            self.generate_plots(id, report_context, formatter, measurements)
+        }
+
+        let mut additional_plots = vec![
+            Plot::new("Typical", "typical.svg"),
+            Plot::new("Mean", "mean.svg"),
+            Plot::new("Std. Dev.", "SD.svg"),
+            Plot::new("Median", "median.svg"),
+            Plot::new("MAD", "MAD.svg"),
+        ];
+        if measurements.absolute_estimates.slope.is_some() {
+            additional_plots.push(Plot::new("Slope", "slope.svg"));
+        }
+        if measurements.repetitions.is_some() {
+            additional_plots.push(Plot::new("Repetitions", "repetitions.svg"));
+        }
+
+        let throughput = measurements
+            .throughput
+            .as_ref()
+            .map(|thr| ConfidenceInterval {
+                lower: formatter
+                    .format_throughput(thr, typical_estimate.confidence_interval.upper_bound),
+                upper: formatter
+                    .format_throughput(thr, typical_estimate.confidence_interval.lower_bound),
+                point: formatter.format_throughput(thr, typical_estimate.point_estimate),
+            });
+
+        let context = Context {
+            title: id.as_title().to_owned(),
+            confidence: format!(
+                "{:.2}",
+                typical_estimate.confidence_interval.confidence_level
+            ),
+
+            thumbnail_width: THUMBNAIL_SIZE.unwrap().0,
+            thumbnail_height: THUMBNAIL_SIZE.unwrap().1,
+
+            slope: measurements
+                .absolute_estimates
+                .slope
+                .as_ref()
+                .map(time_interval),
+            mean: time_interval(&measurements.absolute_estimates.mean),
+            median: time_interval(&measurements.absolute_estimates.median),
+            mad: time_interval(&measurements.absolute_estimates.median_abs_dev),
+            std_dev: time_interval(&measurements.absolute_estimates.std_dev),
+            throughput,
+            percentiles: measurements
+                .absolute_estimates
+                .percentiles
+                .iter()
+                .flat_map(|percentiles| percentiles.labeled())
+                .map(|(name, estimate)| Percentile {
+                    name,
+                    estimate: time_interval(estimate),
+                })
+                .collect(),
+            warm_up: measurements
+                .warm_up
+                .map(|warm_up| format::time(warm_up.length)),
+            repetitions: measurements.repetitions.as_ref().map(|repetitions| {
+                let spread = &repetitions.spread;
+                RepetitionSpread {
+                    count: repetitions.estimates.len(),
+                    mean: formatter.format_value(spread.mean),
+                    std_dev: formatter.format_value(spread.std_dev),
+                    cv: format!("{:.2}%", spread.coefficient_of_variation() * 100.0),
+                    median: formatter.format_value(spread.median),
+                    min: formatter.format_value(spread.min),
+                    max: formatter.format_value(spread.max),
+                }
+            }),
+
+            r2: ConfidenceInterval {
+                lower: format!(
+                    "{:0.7}",
+                    Slope(typical_estimate.confidence_interval.lower_bound).r_squared(&data)
+                ),
+                upper: format!(
+                    "{:0.7}",
+                    Slope(typical_estimate.confidence_interval.upper_bound).r_squared(&data)
+                ),
+                point: format!(
+                    "{:0.7}",
+                    Slope(typical_estimate.point_estimate).r_squared(&data)
+                ),
+            },
+
+            additional_plots,
+
+            comparison: self.comparison(measurements),
+
+            metrics,
+        };
+
+        let mut report_path = report_context.output_directory.clone();
+        report_path.push(id.as_directory_name());
+        report_path.push("report");
+        report_path.push("index.html");
+        debug_context(&report_path, &context);
+
+        let text = self
+            .templates
+            .render("benchmark_report", &context)
+            .expect("Failed to render benchmark report template");
+        try_else_return!(fs::save_string(&text, &report_path));
+    }
+
+    fn metric(&self, id: &BenchmarkId, metric: &MetricData<'_>) -> Metric {
+        let estimate = metric.measurements.absolute_estimates.typical();
+        let interval = |est: &Estimate, format: &dyn Fn(f64) -> String| ConfidenceInterval {
+            lower: format(est.confidence_interval.lower_bound),
+            point: format(est.point_estimate),
+            upper: format(est.confidence_interval.upper_bound),
+        };
+        // The metrics are saved in subdirectories of the benchmark's directory
+        let metric_dir = metric
+            .id
+            .as_directory_name()
+            .strip_prefix(id.as_directory_name())
+            .unwrap();
+
+        Metric {
+            name: metric.name.to_owned(),
+            path: format!("..{}/report", metric_dir),
+            estimate: interval(estimate, &|value| metric.formatter.format_value(value)),
+            change: metric
+                .measurements
+                .comparison
+                .as_ref()
+                .map(|comp| interval(comp.change(), &|value| format::change(value, true))),
+        }
+    }
+
     fn comparison(&self, measurements: &MeasurementData<'_>) -> Option<Comparison> {
         if let Some(ref comp) = measurements.comparison {
             let different_mean = comp.p_value < comp.significance_threshold;
-            let mean_est = &comp.relative_estimates.mean;
+            let change_est = comp.change();
             let explanation_str: String;
 
             if !different_mean {
                 explanation_str = "No change in performance detected.".to_owned();
             } else {
-                let comparison = compare_to_threshold(mean_est, comp.noise_threshold);
+                let comparison = compare_to_threshold(change_est, comp.noise_threshold);
                 match comparison {
                     ComparisonResult::Improved => {
                         explanation_str = "Performance has improved.".to_owned();
@@ -582,27 +726,27 @@ impl Html {
             }
 
             let comp = Comparison {
-                p_value: format!("{:.2}", comp.p_value),
+                p_value: format!("{:.2}{}", comp.p_value, comp.test_suffix()),
                 inequality: (if different_mean { "<" } else { ">" }).to_owned(),
                 significance_level: format!("{:.2}", comp.significance_threshold),
                 explanation: explanation_str,
 
                 change: ConfidenceInterval {
-                    point: format::change(mean_est.point_estimate, true),
-                    lower: format::change(mean_est.confidence_interval.lower_bound, true),
-                    upper: format::change(mean_est.confidence_interval.upper_bound, true),
+                    point: format::change(change_est.point_estimate, true),
+                    lower: format::change(change_est.confidence_interval.lower_bound, true),
+                    upper: format::change(change_est.confidence_interval.upper_bound, true),
                 },
 
                 thrpt_change: measurements.throughput.as_ref().map(|_| {
                     let to_thrpt_estimate = |ratio: f64| 1.0 / (1.0 + ratio) - 1.0;
                     ConfidenceInterval {
-                        point: format::change(to_thrpt_estimate(mean_est.point_estimate), true),
+                        point: format::change(to_thrpt_estimate(change_est.point_estimate), true),
                         lower: format::change(
-                            to_thrpt_estimate(mean_est.confidence_interval.lower_bound),
+                            to_thrpt_estimate(change_est.confidence_interval.lower_bound),
                             true,
                         ),
                         upper: format::change(
-                            to_thrpt_estimate(mean_est.confidence_interval.upper_bound),
+                            to_thrpt_estimate(change_est.confidence_interval.upper_bound),
                             true,
                         ),
                     }
@@ -611,7 +755,7 @@ impl Html {
                 additional_plots: vec![
                     Plot::new("Change in mean", "change/mean.svg"),
                     Plot::new("Change in median", "change/median.svg"),
-                    Plot::new("T-Test", "change/t-test.svg"),
+                    Plot::new(comp.test.name(), "change/t-test.svg"),
                 ],
             };
             Some(comp)
@@ -661,6 +805,9 @@ impl Html {
         self.plotter
             .borrow_mut()
             .abs_distributions(plot_ctx, plot_data);
+        if measurements.repetitions.is_some() {
+            self.plotter.borrow_mut().repetitions(plot_ctx, plot_data);
+        }
 
         if let Some(ref comp) = measurements.comparison {
             try_else_return!({
@@ -698,10 +845,16 @@ impl Html {
                     .borrow_mut()
                     .iteration_times(plot_ctx_small, comp_data);
             }
-            self.plotter.borrow_mut().t_test(plot_ctx, comp_data);
-            self.plotter
-                .borrow_mut()
-                .rel_distributions(plot_ctx, comp_data);
+            // Samples without any variance leave no distribution of the test statistic to plot
+            if !comp.test_distribution.is_empty() {
+                self.plotter.borrow_mut().t_test(plot_ctx, comp_data);
+            }
+            // The relative change from a baseline of zero is infinite and can't be plotted
+            if comp.relative_estimates.mean.point_estimate.is_finite() {
+                self.plotter
+                    .borrow_mut()
+                    .rel_distributions(plot_ctx, comp_data);
+            }
         }
 
         self.plotter.borrow_mut().wait();
@@ -760,7 +913,27 @@ impl Html {
         let value_types: Vec<_> = data.iter().map(|&&(id, _)| id.value_type()).collect();
         let mut line_path = None;
 
-        if value_types.iter().all(|x| x == &value_types[0]) {
+        let ids = data.iter().map(|&&(id, _)| id).collect::<Vec<_>>();
+        if let Some(layout) = SweepLayout::new(&ids, &report_context.plot_config) {
+            let curves = layout.curves(data);
+            if curves
+                .iter()
+                .any(|(id, _)| id.value_str != curves[0].0.value_str)
+            {
+                let curves = curves
+                    .iter()
+                    .map(|(id, sample)| (id, sample.to_vec()))
+                    .collect::<Vec<_>>();
+                let curves = curves.iter().collect::<Vec<_>>();
+                self.plotter.borrow_mut().line_comparison(
+                    plot_ctx,
+                    formatter,
+                    &curves,
+                    ValueType::Value,
+                );
+                line_path = Some(plot_ctx.line_comparison_path());
+            }
+        } else if value_types.iter().all(|x| x == &value_types[0]) {
             if let Some(value_type) = value_types[0] {
                 let values: Vec<_> = data.iter().map(|&&(id, _)| id.as_number()).collect();
                 if values.iter().any(|x| x != &values[0]) {
@@ -806,6 +979,122 @@ impl Html {
     }
 }
 
+fn try_parse(s: &str) -> Option<f64> {
+    s.parse::<f64>().ok()
+}
+
+/// How the benchmarks of a group with several parameters are laid out in the line charts of the
+/// summaries, see `PlotConfiguration::x_axis_parameter` and `PlotConfiguration::line_parameters`.
+struct SweepLayout {
+    /// The parameter on the x-axis.
+    x_axis: String,
+    /// The parameters which are neither on the x-axis nor lines.
+    separate: Vec<String>,
+}
+impl SweepLayout {
+    /// Returns `None` unless all benchmarks have the same parameters, and the parameter on the
+    /// x-axis is a number.
+    fn new(ids: &[&BenchmarkId], config: &PlotConfiguration) -> Option<SweepLayout> {
+        let names = ids
+            .first()?
+            .parameters
+            .iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        let same_parameters = ids.iter().all(|id| {
+            id.parameters.len() == names.len()
+                && id
+                    .parameters
+                    .iter()
+                    .zip(names.iter())
+                    .all(|((name, _), other)| name == *other)
+        });
+        if names.is_empty() || !same_parameters {
+            return None;
+        }
+
+        let numeric = |name: &str| {
+            ids.iter()
+                .all(|id| id.parameter(name).and_then(try_parse).is_some())
+        };
+        let x_axis = match &config.x_axis_parameter {
+            Some(name) => name.clone(),
+            None => names.iter().find(|name| numeric(name))?.to_string(),
+        };
+        if !numeric(&x_axis) {
+            return None;
+        }
+
+        let separate = match &config.line_parameters {
+            Some(lines) => names
+                .iter()
+                .filter(|&&name| *name != x_axis && !lines.contains(name))
+                .map(|name| name.to_string())
+                .collect(),
+            None => vec![],
+        };
+        Some(SweepLayout { x_axis, separate })
+    }
+
+    /// The values of the separate parameters of a benchmark, eg. `threads=4,algorithm=quick`.
+    fn combination(&self, id: &BenchmarkId) -> String {
+        self.separate
+            .iter()
+            .map(|name| format!("{}={}", name, id.parameter(name).unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Relabels the benchmarks for the line chart: the value of the parameter on the x-axis
+    /// becomes the value of the ID, and the function and the other parameters which differ
+    /// between the benchmarks label the lines, which are ordered by their first benchmark.
+    fn curves<'a>(
+        &self,
+        data: &[&'a (&BenchmarkId, Vec<f64>)],
+    ) -> Vec<(BenchmarkId, &'a Vec<f64>)> {
+        let first = data[0].0;
+        let functions_differ = data
+            .iter()
+            .any(|(id, _)| id.function_id != first.function_id);
+        let differs = |name: &str| {
+            data.iter()
+                .any(|(id, _)| id.parameter(name) != first.parameter(name))
+        };
+        let label = |id: &BenchmarkId| {
+            let function = id.function_id.iter().filter(|_| functions_differ).cloned();
+            let parameters = id
+                .parameters
+                .iter()
+                .filter(|(name, _)| *name != self.x_axis && differs(name))
+                .map(|(name, value)| format!("{}={}", name, value));
+            let label = function.chain(parameters).collect::<Vec<_>>().join(", ");
+            if label.is_empty() {
+                None
+            } else {
+                Some(label)
+            }
+        };
+
+        let mut labels: Vec<Option<String>> = vec![];
+        let mut curves = data
+            .iter()
+            .map(|&(id, sample)| {
+                let label = label(id);
+                if !labels.contains(&label) {
+                    labels.push(label.clone());
+                }
+                let x = id.parameter(&self.x_axis).map(str::to_owned);
+                (
+                    BenchmarkId::new(id.group_id.clone(), label, x, None),
+                    sample,
+                )
+            })
+            .collect::<Vec<_>>();
+        curves.sort_by_key(|(id, _)| labels.iter().position(|label| *label == id.function_id));
+        curves
+    }
+}
+
 enum ComparisonResult {
     Improved,
     Regressed,
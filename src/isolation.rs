@@ -0,0 +1,128 @@
+//! Running benchmarks in isolated child processes.
+//!
+//! In isolated mode, the benchmark binary re-executes itself for every benchmark, with the same
+//! command-line arguments and the ID of the benchmark in an environment variable. The child process
+//! only runs that benchmark. It writes the collected measurements to its stdout, which is a pipe
+//! inherited from the parent process, and exits before analyzing them; the parent process analyzes
+//! and reports the results as usual. A benchmark that crashes only takes down its own child process.
+
+use crate::benchmark::BenchmarkConfig;
+use crate::budget;
+use crate::connection::IsolatedMessage;
+use crate::error::{Error, Result};
+use crate::report::BenchmarkId;
+use crate::routine::Measurements;
+use once_cell::sync::Lazy;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ID_VAR: &str = "CRITERION_ISOLATED_ID";
+
+// Written by the child process in front of its measurements, so that they can be told apart from
+// anything the benchmark itself printed to stdout
+const MARKER: &[u8] = b"\0criterion-isolated-measurements\0";
+
+static CHILD_BENCHMARK_ID: Lazy<Option<String>> = Lazy::new(|| std::env::var(ID_VAR).ok());
+
+/// Returns the ID of the only benchmark to run if this is an isolated child process.
+pub(crate) fn child_benchmark_id() -> Option<&'static str> {
+    CHILD_BENCHMARK_ID.as_deref()
+}
+
+/// Sends the measurements of the benchmark to the parent process and exits the child process.
+pub(crate) fn send_to_parent(measurements: Measurements, threads: Option<usize>) -> ! {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    stdout
+        .write_all(MARKER)
+        .map_err(Into::into)
+        .and_then(|()| {
+            IsolatedMessage::MeasurementComplete {
+                measurements,
+                threads,
+            }
+            .send(&mut stdout)
+        })
+        .and_then(|()| stdout.flush().map_err(Into::into))
+        .unwrap_or_else(|err| panic!("Failed to send the measurements: {}", err));
+
+    std::process::exit(0);
+}
+
+/// Runs the benchmark with the given ID and configuration in a child process and returns its
+/// measurements and the number of threads it ran on.
+pub(crate) fn sample_in_child(
+    id: &BenchmarkId,
+    config: &BenchmarkConfig,
+) -> Result<(Measurements, Option<usize>)> {
+    let error = |reason: String| Error::IsolatedProcessError {
+        id: id.as_title().to_owned(),
+        reason,
+    };
+
+    let io_error = |err: std::io::Error| error(err.to_string());
+
+    let exe = std::env::current_exe().map_err(io_error)?;
+    let mut command = Command::new(exe);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(ID_VAR, id.id())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped());
+    budget::pass_scale_to_child(&mut command, config);
+    let mut child = command.spawn().map_err(io_error)?;
+
+    // The output is read on another thread, so that the child never blocks on a full pipe while
+    // this thread waits for it to exit
+    let mut stdout = child.stdout.take().unwrap();
+    let output = thread::spawn(move || {
+        let mut output = vec![];
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(io_error)? {
+            break status;
+        }
+        if let Some(timeout) = config.timeout {
+            if start.elapsed() > timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error(format!(
+                    "timed out after {:.1?} and was killed",
+                    start.elapsed()
+                )));
+            }
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let output = output.join().unwrap().map_err(io_error)?;
+    let marker = output
+        .windows(MARKER.len())
+        .position(|window| window == MARKER);
+    // Whatever the benchmark printed is passed on
+    let printed = &output[..marker.unwrap_or(output.len())];
+    std::io::stdout().write_all(printed).map_err(io_error)?;
+
+    let message = match marker {
+        Some(marker) => Some(
+            IsolatedMessage::recv(&mut &output[marker + MARKER.len()..])
+                .map_err(|err| error(err.to_string()))?,
+        ),
+        None => None,
+    };
+    match message {
+        Some(IsolatedMessage::MeasurementComplete {
+            measurements,
+            threads,
+        }) => Ok((measurements, threads)),
+        None if status.success() => Err(error(
+            "the process did not run the benchmark; is it defined conditionally?".to_owned(),
+        )),
+        None => Err(error(format!("the process exited with {}", status))),
+    }
+}
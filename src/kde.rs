@@ -20,6 +20,10 @@ pub fn sweep_and_estimate(
     let x_min = sample.min();
     let x_max = sample.max();
 
+    if x_min == x_max {
+        return sweep_constant(x_min, npoints, range, point_to_estimate);
+    }
+
     let kde = Kde::new(sample, Gaussian, Bandwidth::Silverman);
     let h = kde.bandwidth();
 
@@ -39,3 +43,30 @@ pub fn sweep_and_estimate(
 
     (xs.into_boxed_slice(), ys, point_estimate)
 }
+
+// A sample in which all values are equal (eg. a constant number of allocations per iteration) has
+// no spread to estimate the bandwidth from, so it is drawn as a narrow normal density around the
+// value instead.
+fn sweep_constant(
+    x: f64,
+    npoints: usize,
+    range: Option<(f64, f64)>,
+    point_to_estimate: f64,
+) -> (Box<[f64]>, Box<[f64]>, f64) {
+    let h = if x == 0. { 1e-3 } else { x.abs() * 1e-3 };
+    let density = |at: f64| {
+        let z = (at - x) / h;
+        (-z * z / 2.).exp() / (h * (2. * std::f64::consts::PI).sqrt())
+    };
+
+    let (start, end) = range.unwrap_or((x - 3. * h, x + 3. * h));
+    let step_size = (end - start) / (npoints - 1) as f64;
+    let xs: Vec<f64> = (0..npoints).map(|n| start + step_size * n as f64).collect();
+    let ys: Vec<f64> = xs.iter().map(|&at| density(at)).collect();
+
+    (
+        xs.into_boxed_slice(),
+        ys.into_boxed_slice(),
+        density(point_to_estimate),
+    )
+}
@@ -0,0 +1,247 @@
+//! Histogram of per-iteration latencies, used by `SamplingMode::PerIteration`.
+
+use crate::estimate::{ConfidenceInterval, Estimate, PercentileEstimates};
+use crate::stats::rand_util::{new_rng, Rng};
+use crate::stats::Distribution;
+
+// Values are recorded with 8 significant bits, so the buckets are less than 0.8% wide relative to
+// the values they hold, regardless of the magnitude of the values.
+const SUB_BUCKET_BITS: u32 = 8;
+const SUB_BUCKET_HALF_BITS: u32 = SUB_BUCKET_BITS - 1;
+
+/// A histogram with logarithmically growing, linearly subdivided buckets in the style of
+/// HdrHistogram. Recording a value takes constant time and memory, so every iteration of a
+/// benchmark can be recorded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct LatencyHistogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+impl LatencyHistogram {
+    pub(crate) fn new() -> LatencyHistogram {
+        LatencyHistogram::default()
+    }
+
+    /// Record one value. Negative values are recorded as zero.
+    pub(crate) fn record(&mut self, value: f64) {
+        let index = bucket_index(value.max(0.0).round() as u64);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.total += 1;
+    }
+
+    /// Add the values recorded by `other` to this histogram.
+    pub(crate) fn merge(&mut self, other: &LatencyHistogram) {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, &other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        self.total += other.total;
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Estimate the 50th, 90th, 99th and 99.9th percentiles of the recorded values, after
+    /// subtracting `offset` from them, with confidence intervals bootstrapped from `nresamples`
+    /// resamples.
+    pub(crate) fn percentiles(
+        &self,
+        offset: f64,
+        cl: f64,
+        nresamples: usize,
+    ) -> PercentileEstimates {
+        let mut rng = new_rng();
+        PercentileEstimates {
+            p50: self.estimate(0.5, offset, cl, nresamples, &mut rng),
+            p90: self.estimate(0.9, offset, cl, nresamples, &mut rng),
+            p99: self.estimate(0.99, offset, cl, nresamples, &mut rng),
+            p999: self.estimate(0.999, offset, cl, nresamples, &mut rng),
+        }
+    }
+
+    // A resample draws `total` values from the histogram, ie. `F⁻¹(U)` for uniform `U` and the
+    // empirical distribution `F`, so its quantile is `F⁻¹` of an order statistic of `total`
+    // uniform values. That order statistic follows a beta distribution, which is sampled here
+    // instead of drawing millions of values for every resample.
+    fn estimate(
+        &self,
+        quantile: f64,
+        offset: f64,
+        cl: f64,
+        nresamples: usize,
+        rng: &mut Rng,
+    ) -> Estimate {
+        let n = self.total as f64;
+        let rank = (quantile * n).ceil().max(1.0);
+
+        // (cumulative count, value) of every non-empty bucket
+        let mut cumulative = 0;
+        let buckets = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count != 0)
+            .map(|(index, &count)| {
+                cumulative += count;
+                (cumulative, (bucket_value(index) - offset).max(0.0))
+            })
+            .collect::<Vec<_>>();
+        // The value of the `rank`-th smallest recorded value
+        let value = |rank: f64| {
+            let i = buckets.partition_point(|&(cumulative, _)| (cumulative as f64) < rank);
+            buckets
+                .get(i)
+                .or_else(|| buckets.last())
+                .map_or(0.0, |&(_, x)| x)
+        };
+
+        let point_estimate = value(rank);
+        let resamples = (0..nresamples)
+            .map(|_| value(beta(rng, rank, n - rank + 1.0) * n))
+            .collect::<Vec<_>>();
+        let distribution = Distribution::from(resamples.into_boxed_slice());
+        let (lower_bound, upper_bound) = distribution.confidence_interval(cl);
+
+        Estimate {
+            confidence_interval: ConfidenceInterval {
+                confidence_level: cl,
+                lower_bound,
+                upper_bound,
+            },
+            point_estimate,
+            standard_error: distribution.std_dev(None),
+        }
+    }
+}
+
+// Samples a beta distribution with shape parameters of at least 1 as the ratio of two gamma
+// variates.
+fn beta(rng: &mut Rng, a: f64, b: f64) -> f64 {
+    let x = gamma(rng, a);
+    let y = gamma(rng, b);
+    x / (x + y)
+}
+
+// Samples a gamma distribution with a shape parameter of at least 1 and a scale of 1, using the
+// method of Marsaglia and Tsang.
+fn gamma(rng: &mut Rng, shape: f64) -> f64 {
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = 1.0 + c * x;
+        if v <= 0.0 {
+            continue;
+        }
+        let v = v * v * v;
+        let u = 1.0 - rng.rand_float();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+// Samples a standard normal distribution with the Box-Muller transform.
+fn standard_normal(rng: &mut Rng) -> f64 {
+    let u = 1.0 - rng.rand_float();
+    let v = rng.rand_float();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+fn bucket_index(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros();
+    if bits <= SUB_BUCKET_BITS {
+        value as usize
+    } else {
+        let shift = bits - SUB_BUCKET_BITS;
+        ((shift as usize) << SUB_BUCKET_HALF_BITS) + (value >> shift) as usize
+    }
+}
+
+// The midpoint of the values in the bucket
+fn bucket_value(index: usize) -> f64 {
+    if index < 1 << SUB_BUCKET_BITS {
+        index as f64
+    } else {
+        let shift = (index >> SUB_BUCKET_HALF_BITS) - 1;
+        let lowest = ((index - (shift << SUB_BUCKET_HALF_BITS)) as u64) << shift;
+        lowest as f64 + ((1u64 << shift) - 1) as f64 / 2.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket_value_round_trip() {
+        for &value in &[
+            0u64,
+            1,
+            255,
+            256,
+            257,
+            1000,
+            123_456,
+            987_654_321,
+            u64::MAX / 3,
+        ] {
+            let recorded = bucket_value(bucket_index(value));
+            assert!((recorded - value as f64).abs() <= value as f64 / 128.0);
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut histogram = LatencyHistogram::new();
+        for value in 1..=10_000 {
+            histogram.record(value as f64);
+        }
+
+        let percentiles = histogram.percentiles(0.0, 0.95, 10_000);
+        for &(estimate, expected) in &[
+            (&percentiles.p50, 5000.0),
+            (&percentiles.p90, 9000.0),
+            (&percentiles.p99, 9900.0),
+            (&percentiles.p999, 9990.0),
+        ] {
+            let ci = &estimate.confidence_interval;
+            assert!((estimate.point_estimate - expected).abs() <= expected / 128.0);
+            assert!(ci.lower_bound <= estimate.point_estimate);
+            assert!(ci.upper_bound >= estimate.point_estimate);
+        }
+
+        // About 1.96 standard deviations of the binomial number of values below the median, ie.
+        // 98 values, on either side of it
+        let ci = &percentiles.p50.confidence_interval;
+        assert!(
+            ci.lower_bound >= 4850.0 && ci.lower_bound <= 4960.0,
+            "{}",
+            ci.lower_bound
+        );
+        assert!(
+            ci.upper_bound >= 5040.0 && ci.upper_bound <= 5150.0,
+            "{}",
+            ci.upper_bound
+        );
+    }
+
+    #[test]
+    fn test_percentiles_of_constant_values() {
+        let mut histogram = LatencyHistogram::new();
+        for _ in 0..100 {
+            histogram.record(42.0);
+        }
+
+        let p99 = histogram.percentiles(2.0, 0.95, 1000).p99;
+        assert_eq!(p99.point_estimate, 40.0);
+        assert_eq!(p99.confidence_interval.lower_bound, 40.0);
+        assert_eq!(p99.confidence_interval.upper_bound, 40.0);
+    }
+}
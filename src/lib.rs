@@ -50,33 +50,46 @@ extern crate serde_derive;
 mod macros_private;
 #[macro_use]
 mod analysis;
+pub mod alloc;
 mod benchmark;
 #[macro_use]
 mod benchmark_group;
 pub mod async_executor;
 mod bencher;
+mod budget;
+mod cache;
 mod connection;
 #[cfg(feature = "csv_output")]
 mod csv_report;
+mod environment;
 mod error;
 mod estimate;
 mod format;
 mod fs;
 mod html;
+mod isolation;
 mod kde;
+mod latency;
 mod macros;
 pub mod measurement;
 mod plot;
 pub mod profiler;
 mod report;
 mod routine;
+mod scheduling;
+mod shard;
+mod shuffle;
 mod stats;
+mod timeout;
+mod warm_up;
+mod worker_pool;
 
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::default::Default;
 use std::env;
 use std::net::TcpStream;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::{Mutex, MutexGuard};
@@ -94,12 +107,18 @@ use crate::measurement::{Measurement, WallTime};
 use crate::plot::PlottersBackend;
 use crate::plot::{Gnuplot, Plotter};
 use crate::profiler::{ExternalProfiler, Profiler};
+use crate::report::BenchmarkId as InternalBenchmarkId;
 use crate::report::{BencherReport, CliReport, CliVerbosity, Report, ReportContext, Reports};
+use crate::shard::Shard;
+use crate::timeout::TimedOut;
 
 #[cfg(feature = "async")]
 pub use crate::bencher::AsyncBencher;
 pub use crate::bencher::Bencher;
-pub use crate::benchmark_group::{BenchmarkGroup, BenchmarkId};
+pub use crate::benchmark_group::{
+    BenchmarkGroup, BenchmarkId, Dimensions, FixtureGroup, InterleavedGroup, ShuffledGroup,
+    ShuffledGroups,
+};
 
 static DEBUG_ENABLED: Lazy<bool> = Lazy::new(|| std::env::var_os("CRITERION_DEBUG").is_some());
 static GNUPLOT_VERSION: Lazy<Result<Version, VersionError>> = Lazy::new(criterion_plot::version);
@@ -107,13 +126,16 @@ static DEFAULT_PLOTTING_BACKEND: Lazy<PlottingBackend> = Lazy::new(|| match &*GN
     Ok(_) => PlottingBackend::Gnuplot,
     #[cfg(feature = "plotters")]
     Err(e) => {
-        match e {
-            VersionError::Exec(_) => eprintln!("Gnuplot not found, using plotters backend"),
-            e => eprintln!(
-                "Gnuplot not found or not usable, using plotters backend\n{}",
-                e
-            ),
-        };
+        // Isolated child processes leave the message to the parent process
+        if isolation::child_benchmark_id().is_none() {
+            match e {
+                VersionError::Exec(_) => eprintln!("Gnuplot not found, using plotters backend"),
+                e => eprintln!(
+                    "Gnuplot not found or not usable, using plotters backend\n{}",
+                    e
+                ),
+            };
+        }
         PlottingBackend::Plotters
     }
     #[cfg(not(feature = "plotters"))]
@@ -128,6 +150,9 @@ static CARGO_CRITERION_CONNECTION: Lazy<Option<Mutex<Connection>>> =
         }
         Err(_) => None,
     });
+/// The output directories and titles of the benchmarks that failed in this process. If there are
+/// any for the output directory of the final summary, the process exits with an error after it.
+static FAILED_BENCHMARKS: Lazy<Mutex<Vec<(PathBuf, String)>>> = Lazy::new(|| Mutex::new(vec![]));
 static DEFAULT_OUTPUT_DIRECTORY: Lazy<PathBuf> = Lazy::new(|| {
     // Set criterion home to (in descending order of preference):
     // - $CRITERION_HOME (cargo-criterion sets this, but other users could as well)
@@ -234,6 +259,18 @@ pub enum BatchSize {
     /// `LargeInput` instead.
     NumIterations(u64),
 
+    /// `ColdPerIteration` is like `PerIteration`, but evicts the CPU caches before every
+    /// iteration, after the setup routine has run. This measures the routine with cold caches,
+    /// as code that runs rarely (eg. a lookup of a database page) usually does in production.
+    /// Without it, every iteration after the first finds its data in the caches.
+    ///
+    /// The caches are evicted by writing to a buffer twice the size of the last-level cache (at
+    /// most 512 MiB, or 64 MiB if its size is unknown), which is only allocated while the
+    /// benchmark is sampled. This is not included in the measurement, but usually takes far longer
+    /// than the routine itself, so fewer iterations fit into the measurement time.
+    /// Like `PerIteration`, this has the worst measurement overhead.
+    ColdPerIteration,
+
     #[doc(hidden)]
     __NonExhaustive,
 }
@@ -248,7 +285,7 @@ impl BatchSize {
         match self {
             BatchSize::SmallInput => (iters + 10 - 1) / 10,
             BatchSize::LargeInput => (iters + 1000 - 1) / 1000,
-            BatchSize::PerIteration => 1,
+            BatchSize::PerIteration | BatchSize::ColdPerIteration => 1,
             BatchSize::NumBatches(batches) => (iters + batches - 1) / batches,
             BatchSize::NumIterations(size) => size,
             BatchSize::__NonExhaustive => panic!("__NonExhaustive is not a valid BatchSize."),
@@ -317,6 +354,10 @@ impl Mode {
     pub fn is_terse(&self) -> bool {
         matches!(self, Mode::List(ListFormat::Terse))
     }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self, Mode::List(_))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -346,6 +387,73 @@ pub enum BenchmarkFilter {
     Exact(String),
     /// Do not run any benchmarks.
     RejectAll,
+    /// Run benchmarks with this tag, given by
+    /// [`BenchmarkGroup::tag`](struct.BenchmarkGroup.html#method.tag) or
+    /// [`BenchmarkId::with_tags`](struct.BenchmarkId.html#method.with_tags).
+    Tag(String),
+    /// Run benchmarks matching all of these filters.
+    All(Vec<BenchmarkFilter>),
+    /// Run benchmarks matching any of these filters.
+    Any(Vec<BenchmarkFilter>),
+    /// Run benchmarks not matching this filter.
+    Not(Box<BenchmarkFilter>),
+}
+impl BenchmarkFilter {
+    fn matches(&self, id: &str, tags: &[String]) -> bool {
+        match self {
+            BenchmarkFilter::AcceptAll => true,
+            BenchmarkFilter::Regex(regex) => regex.is_match(id),
+            BenchmarkFilter::Exact(exact) => id == exact,
+            BenchmarkFilter::RejectAll => false,
+            BenchmarkFilter::Tag(tag) => tags.contains(tag),
+            BenchmarkFilter::All(filters) => filters.iter().all(|filter| filter.matches(id, tags)),
+            BenchmarkFilter::Any(filters) => filters.iter().any(|filter| filter.matches(id, tags)),
+            BenchmarkFilter::Not(filter) => !filter.matches(id, tags),
+        }
+    }
+
+    /// Combines two filters into one which matches the benchmarks matching both of them.
+    pub fn and(self, other: BenchmarkFilter) -> BenchmarkFilter {
+        match (self, other) {
+            (BenchmarkFilter::AcceptAll, filter) | (filter, BenchmarkFilter::AcceptAll) => filter,
+            (BenchmarkFilter::All(mut filters), BenchmarkFilter::All(others)) => {
+                filters.extend(others);
+                BenchmarkFilter::All(filters)
+            }
+            (BenchmarkFilter::All(mut filters), other) => {
+                filters.push(other);
+                BenchmarkFilter::All(filters)
+            }
+            (filter, other) => BenchmarkFilter::All(vec![filter, other]),
+        }
+    }
+
+    /// Combines two filters into one which matches the benchmarks matching either of them.
+    pub fn or(self, other: BenchmarkFilter) -> BenchmarkFilter {
+        match (self, other) {
+            (BenchmarkFilter::RejectAll, filter) | (filter, BenchmarkFilter::RejectAll) => filter,
+            (BenchmarkFilter::Any(mut filters), BenchmarkFilter::Any(others)) => {
+                filters.extend(others);
+                BenchmarkFilter::Any(filters)
+            }
+            (BenchmarkFilter::Any(mut filters), other) => {
+                filters.push(other);
+                BenchmarkFilter::Any(filters)
+            }
+            (filter, other) => BenchmarkFilter::Any(vec![filter, other]),
+        }
+    }
+}
+impl std::ops::Not for BenchmarkFilter {
+    type Output = BenchmarkFilter;
+
+    /// Inverts the filter, so that it matches the benchmarks it didn't match before.
+    fn not(self) -> BenchmarkFilter {
+        match self {
+            BenchmarkFilter::Not(filter) => *filter,
+            filter => BenchmarkFilter::Not(Box::new(filter)),
+        }
+    }
 }
 
 /// The benchmark manager
@@ -376,6 +484,14 @@ pub struct Criterion<M: Measurement = WallTime> {
     profiler: Box<RefCell<dyn Profiler>>,
     connection: Option<MutexGuard<'static, Connection>>,
     mode: Mode,
+    isolated: bool,
+    cpu: Option<usize>,
+    priority: Option<Priority>,
+    environment_root: PathBuf,
+    time_budget: Option<Duration>,
+    shard: Option<Shard>,
+    merge_shards: Vec<PathBuf>,
+    shuffle: Option<u64>,
 }
 
 /// Returns the Cargo target directory, possibly calling `cargo metadata` to
@@ -428,9 +544,18 @@ impl Default for Criterion {
                 nresamples: 100_000,
                 sample_size: 100,
                 significance_level: 0.05,
+                comparison_test: ComparisonTest::Welch,
                 warm_up_time: Duration::from_secs(3),
                 sampling_mode: SamplingMode::Auto,
                 quick_mode: false,
+                calibrate_overhead: false,
+                target_precision: None,
+                max_measurement_time: Duration::from_secs(60),
+                warm_up_mode: WarmUpMode::Fixed,
+                max_warm_up_time: Duration::from_secs(30),
+                repetitions: 1,
+                timeout: None,
+                time_budget_scale: None,
             },
             filter: BenchmarkFilter::AcceptAll,
             report: reports,
@@ -446,6 +571,14 @@ impl Default for Criterion {
                 .as_ref()
                 .map(|mtx| mtx.lock().unwrap()),
             mode: Mode::Benchmark,
+            isolated: false,
+            cpu: None,
+            priority: None,
+            environment_root: PathBuf::from("/"),
+            time_budget: None,
+            shard: None,
+            merge_shards: vec![],
+            shuffle: None,
         };
 
         if criterion.connection.is_some() {
@@ -478,6 +611,14 @@ impl<M: Measurement> Criterion<M> {
             profiler: self.profiler,
             connection: self.connection,
             mode: self.mode,
+            isolated: self.isolated,
+            cpu: self.cpu,
+            priority: self.priority,
+            environment_root: self.environment_root,
+            time_budget: self.time_budget,
+            shard: self.shard,
+            merge_shards: self.merge_shards,
+            shuffle: self.shuffle,
         }
     }
 
@@ -541,6 +682,35 @@ impl<M: Measurement> Criterion<M> {
         self
     }
 
+    #[must_use]
+    /// Changes the default warm-up mode for benchmarks run with this runner. With
+    /// `WarmUpMode::SteadyState`, the warm-up runs the benchmark in short batches and stops once the
+    /// time per iteration of the last 20 batches no longer trends. The
+    /// [warm-up time](#method.warm_up_time) is then the minimum length of the warm-up, and the
+    /// [maximum warm-up time](#method.max_warm_up_time) the maximum; a warning is printed if no
+    /// steady state was reached by then.
+    ///
+    /// This suits suites where some benchmarks take much longer than others to reach a steady
+    /// state, eg. because they fill large caches or initialize lazily.
+    pub fn warm_up_mode(mut self, mode: WarmUpMode) -> Criterion<M> {
+        self.config.warm_up_mode = mode;
+        self
+    }
+
+    #[must_use]
+    /// Changes the default maximum warm-up time for benchmarks run with this runner. It only applies
+    /// to the [steady-state warm-up](#method.warm_up_mode). The default is 30 seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input duration is zero
+    pub fn max_warm_up_time(mut self, dur: Duration) -> Criterion<M> {
+        assert!(dur.as_nanos() > 0);
+
+        self.config.max_warm_up_time = dur;
+        self
+    }
+
     #[must_use]
     /// Changes the default measurement time for benchmarks run with this runner.
     ///
@@ -581,6 +751,47 @@ impl<M: Measurement> Criterion<M> {
         self
     }
 
+    #[must_use]
+    /// Changes the default number of repetitions for benchmarks run with this runner. Each
+    /// repetition runs the whole warm-up, sampling and analysis of the benchmark again, and the
+    /// spread of the estimates of all repetitions is reported after the last one. The bootstrapped
+    /// confidence intervals of a single run understate the noise between runs, so the spread is a
+    /// better guide for choosing the [noise threshold](#method.noise_threshold). The default is 1,
+    /// which disables repetitions.
+    ///
+    /// The last repetition is reported, saved and compared against the baseline as usual, and the
+    /// estimates of all repetitions are saved as `repetitions.json`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is zero
+    pub fn repetitions(mut self, n: usize) -> Criterion<M> {
+        assert!(n > 0);
+
+        self.config.repetitions = n;
+        self
+    }
+
+    #[must_use]
+    /// Changes the default timeout for benchmarks run with this runner. A benchmark whose warm-up
+    /// and sampling take longer than this is aborted and reported as failed, along with how far it
+    /// got, and the run moves on to the next benchmark. By default, benchmarks have no timeout.
+    ///
+    /// The timeout is checked between the batches of the warm-up and between samples, as a
+    /// routine can't be interrupted in the middle of an iteration. If a single sample runs well
+    /// past the timeout, a warning is printed instead. Benchmarks run in
+    /// [isolated processes](#method.isolated) are killed as soon as they time out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout is zero
+    pub fn timeout(mut self, dur: Duration) -> Criterion<M> {
+        assert!(dur.as_nanos() > 0);
+
+        self.config.timeout = Some(dur);
+        self
+    }
+
     #[must_use]
     /// Changes the default noise threshold for benchmarks run with this runner. The noise threshold
     /// is used to filter out small changes in performance, even if they are statistically
@@ -648,6 +859,181 @@ impl<M: Measurement> Criterion<M> {
         self
     }
 
+    #[must_use]
+    /// Changes the default statistical test used to decide whether the performance has changed
+    /// since the last run or the baseline. By default, this is a Welch t test on the mean times,
+    /// which assumes roughly normally distributed times. Timing distributions are often skewed or
+    /// have several modes, and `ComparisonTest::MannWhitney` or `ComparisonTest::MedianDifference`
+    /// give fewer false alarms for them. With either of these, the change in the median time is
+    /// reported instead of the change in the mean.
+    pub fn comparison_test(mut self, test: ComparisonTest) -> Criterion<M> {
+        self.config.comparison_test = test;
+        self
+    }
+
+    #[must_use]
+    /// Enables or disables the calibration of the timing loop overhead for benchmarks run with
+    /// this runner. When enabled, Criterion.rs times an empty routine with the same timing loop,
+    /// iteration counts and measurement after collecting the samples of each benchmark, and
+    /// subtracts that overhead from the samples. The overhead per iteration is printed next to the
+    /// estimate. This is most useful for very fast routines, where the loop itself makes up a
+    /// measurable part of the result. It roughly doubles the measurement time and is disabled by
+    /// default. The overhead is not calibrated in quick mode or for benchmarks using
+    /// `iter_custom`.
+    pub fn calibrate_overhead(mut self, enabled: bool) -> Criterion<M> {
+        self.config.calibrate_overhead = enabled;
+        self
+    }
+
+    #[must_use]
+    /// Enables or disables running each benchmark in an isolated child process. When enabled, the
+    /// benchmark binary re-executes itself for every benchmark, with the same command-line
+    /// arguments, and the child process runs only that benchmark and sends the measurements back.
+    /// The analysis and reporting happen in the parent process as usual.
+    ///
+    /// This keeps global state, heap fragmentation and caches left behind by one benchmark from
+    /// affecting the next one, and a benchmark that crashes or aborts is reported as an error
+    /// without stopping the remaining benchmarks. Isolation is disabled by default, and is not
+    /// used under cargo-criterion or when interleaving benchmarks.
+    pub fn isolated(mut self, enabled: bool) -> Criterion<M> {
+        self.isolated = enabled;
+        self
+    }
+
+    #[must_use]
+    /// Pins the thread that collects the samples to the given CPU core while it is sampling, which
+    /// makes the results much more stable on machines with many cores. The core is saved with the
+    /// samples, and comparisons against a baseline measured on a different core print a warning.
+    ///
+    /// Pinning is only supported on Linux; elsewhere, or if the core doesn't exist, a warning is
+    /// printed and the benchmarks run unpinned. Threads started by the benchmark, eg. by
+    /// `Bencher::iter_parallel`, inherit the affinity and run on the same core.
+    pub fn pin_to_cpu(mut self, core: usize) -> Criterion<M> {
+        self.cpu = Some(core);
+        self
+    }
+
+    #[must_use]
+    /// Changes the scheduling priority of the thread that collects the samples while it is
+    /// sampling. This is only supported on Linux, and raising the priority usually requires
+    /// elevated privileges; if the priority can't be changed, a warning is printed and the
+    /// benchmarks run with the default priority.
+    pub fn priority(mut self, priority: Priority) -> Criterion<M> {
+        self.priority = Some(priority);
+        self
+    }
+
+    #[must_use]
+    /// Limits the time taken by all benchmarks of the benchmark binary, from the start of the first
+    /// one, even if they are run by several `Criterion` instances, like the benchmark functions of
+    /// `criterion_main!`. Before each benchmark, the rest of the budget is split evenly between the
+    /// benchmarks that are left to run, and the warm-up and measurement times of the benchmark are
+    /// scaled down proportionally if they don't fit its share. The number of benchmarks is
+    /// estimated by running the benchmark binary again with `--list` and the same filter. The
+    /// budget also covers the analysis, so it is best to leave some headroom.
+    ///
+    /// If a benchmark gets less time than its sampling mode needs for the sample size, a warning
+    /// recommends a smaller sample size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the budget is 0.
+    pub fn time_budget(mut self, budget: Duration) -> Criterion<M> {
+        assert!(budget.as_nanos() > 0);
+
+        self.time_budget = Some(budget);
+        self
+    }
+
+    #[must_use]
+    /// Only runs the benchmarks in one of `count` shards of the benchmark suite, so that a large
+    /// suite can be split between several machines, eg. CI workers. Shards are numbered from 1.
+    /// Every benchmark belongs to exactly one shard, chosen by hashing its ID, so all machines agree
+    /// on the partition without coordinating. The output directories of the shards can be merged
+    /// afterwards with [`merge_shards`](#method.merge_shards).
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `1 <= index <= count`.
+    pub fn shard(mut self, index: usize, count: usize) -> Criterion<M> {
+        let shard = Shard::new(index, count)
+            .unwrap_or_else(|| panic!("Shard {}/{} does not exist", index, count));
+
+        self.shard = Some(shard);
+        self
+    }
+
+    #[must_use]
+    /// Merges the output directories of the given shards (see [`shard`](#method.shard)) into the
+    /// output directory of this runner, instead of running any benchmarks. The results are copied
+    /// by [`final_summary`](#method.final_summary), which then generates one HTML index for all of
+    /// them. The summaries of groups that were split between shards are generated again, with the
+    /// measurement of this runner.
+    pub fn merge_shards<I>(mut self, shards: I) -> Criterion<M>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        self.merge_shards = shards
+            .into_iter()
+            .map(|shard| shard.as_ref().to_owned())
+            .collect();
+        self
+    }
+
+    #[must_use]
+    /// Changes the directory below which `/sys` and `/proc` are read when inspecting the machine
+    /// before benchmarking. The default is `/`; other roots are mostly useful for testing.
+    ///
+    /// Before benchmarking, Criterion.rs checks the CPU frequency governor, turbo boost, the load
+    /// average and whether the benchmark runs under a debugger or was compiled with debug
+    /// assertions, and prints a warning if any of them are likely to make the results noisy. It
+    /// also records simultaneous multithreading and address space layout randomization. The
+    /// snapshot, taken once per process, is saved as `environment.json` next to `benchmark.json`,
+    /// and comparisons against a baseline taken in a different environment print the differences.
+    pub fn environment_root<P: AsRef<Path>>(mut self, root: P) -> Criterion<M> {
+        self.environment_root = root.as_ref().to_owned();
+        self
+    }
+
+    #[must_use]
+    /// Enables adaptive sampling for benchmarks run with this runner. Instead of always collecting
+    /// `sample_size` samples, Criterion.rs collects samples in rounds of ten until the confidence
+    /// interval of the mean is within `precision` of the mean, or until the
+    /// [maximum measurement time](#method.max_measurement_time) is used up. For example, a
+    /// precision of 0.01 stops sampling once the interval is at most ±1% of the mean.
+    ///
+    /// The iteration counts of the samples are planned as usual from the sample size and the
+    /// measurement time, so stable benchmarks may finish after fewer samples, while noisy benchmarks
+    /// keep collecting samples beyond the sample size. A warning is printed if the precision
+    /// could not be reached in time. Adaptive sampling is disabled by default, and not used in
+    /// quick mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the precision is not positive
+    pub fn target_precision(mut self, precision: f64) -> Criterion<M> {
+        assert!(precision > 0.0);
+
+        self.config.target_precision = Some(precision);
+        self
+    }
+
+    #[must_use]
+    /// Changes the default maximum measurement time for benchmarks run with this runner. When a
+    /// [target precision](#method.target_precision) is set, sampling stops after this time even if
+    /// the precision has not been reached. The default is 60 seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the input duration is zero
+    pub fn max_measurement_time(mut self, dur: Duration) -> Criterion<M> {
+        assert!(dur.as_nanos() > 0);
+
+        self.config.max_measurement_time = dur;
+        self
+    }
+
     #[must_use]
     /// Enables plotting
     pub fn with_plots(mut self) -> Criterion<M> {
@@ -749,16 +1135,32 @@ impl<M: Measurement> Criterion<M> {
     /// Generate the final summary at the end of a run.
     #[doc(hidden)]
     pub fn final_summary(&self) {
-        if !self.mode.is_benchmark() {
-            return;
-        }
+        if self.mode.is_benchmark() {
+            let report_context = ReportContext {
+                output_directory: self.output_directory.clone(),
+                plot_config: PlotConfiguration::default(),
+            };
 
-        let report_context = ReportContext {
-            output_directory: self.output_directory.clone(),
-            plot_config: PlotConfiguration::default(),
-        };
+            if !self.merge_shards.is_empty() {
+                self.merge(&report_context);
+            }
+            self.report.final_summary(&report_context);
+        }
 
-        self.report.final_summary(&report_context);
+        let failed = FAILED_BENCHMARKS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(output_directory, _)| *output_directory == self.output_directory)
+            .map(|(_, id)| id.clone())
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            eprintln!("\nError: {} benchmark(s) failed:", failed.len());
+            for id in failed.iter() {
+                eprintln!("    {}", id);
+            }
+            std::process::exit(1);
+        }
     }
 
     /// Configure this criterion struct based on the command-line arguments to
@@ -769,7 +1171,8 @@ impl<M: Measurement> Criterion<M> {
         use clap::{Arg, Command};
         let matches = Command::new("Criterion Benchmark")
             .arg(Arg::new("FILTER")
-                .help("Skip benchmarks whose names do not contain FILTER.")
+                .help("Skip benchmarks whose names do not contain FILTER. May be given several times to run benchmarks matching any of the filters.")
+                .multiple_values(true)
                 .index(1))
             .arg(Arg::new("color")
                 .short('c')
@@ -828,6 +1231,24 @@ impl<M: Measurement> Criterion<M> {
             .arg(Arg::new("exact")
                 .long("exact")
                 .help("Run benchmarks that exactly match the provided filter"))
+            .arg(Arg::new("skip")
+                .long("skip")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("PATTERN")
+                .help("Skip benchmarks whose names contain PATTERN (or match it exactly, with --exact). May be given several times."))
+            .arg(Arg::new("tag")
+                .long("tag")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("TAG")
+                .help("Only run benchmarks with this tag. May be given several times to run benchmarks with any of the tags."))
+            .arg(Arg::new("skip-tag")
+                .long("skip-tag")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("TAG")
+                .help("Skip benchmarks with this tag. May be given several times."))
             .arg(Arg::new("profile-time")
                 .long("profile-time")
                 .takes_value(true)
@@ -855,6 +1276,14 @@ impl<M: Measurement> Criterion<M> {
                 .long("nresamples")
                 .takes_value(true)
                 .help(&*format!("Changes the default number of resamples for this run. [default: {}]", self.config.nresamples)))
+            .arg(Arg::new("repetitions")
+                .long("repetitions")
+                .takes_value(true)
+                .help(&*format!("Run each benchmark this many times and report the spread of the estimates. [default: {}]", self.config.repetitions)))
+            .arg(Arg::new("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .help("Abort and fail benchmarks whose warm-up and sampling take longer than this many seconds."))
             .arg(Arg::new("noise-threshold")
                 .long("noise-threshold")
                 .takes_value(true)
@@ -867,10 +1296,75 @@ impl<M: Measurement> Criterion<M> {
                 .long("significance-level")
                 .takes_value(true)
                 .help(&*format!("Changes the default significance level for this run. [default: {}]", self.config.significance_level)))
+            .arg(Arg::new("comparison-test")
+                .long("comparison-test")
+                .takes_value(true)
+                .possible_values(["welch", "mann-whitney", "median"])
+                .help("Decide whether the performance has changed with a Welch t test on the mean times, a Mann-Whitney U test, or a bootstrapped test of the difference of the median times. [default: welch]"))
             .arg(Arg::new("quick")
                 .long("quick")
                 .conflicts_with("sample-size")
                 .help(&*format!("Benchmark only until the significance level has been reached [default: {}]", self.config.quick_mode)))
+            .arg(Arg::new("calibrate-overhead")
+                .long("calibrate-overhead")
+                .help(&*format!("Measure the overhead of the timing loop and subtract it from the samples. [default: {}]", self.config.calibrate_overhead)))
+            .arg(Arg::new("isolated")
+                .long("isolated")
+                .help("Run each benchmark in a separate child process."))
+            .arg(Arg::new("cpu")
+                .long("cpu")
+                .takes_value(true)
+                .help("Pin the measurement thread to this CPU core (Linux only)."))
+            .arg(Arg::new("nice")
+                .long("nice")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .conflicts_with("realtime-priority")
+                .help("Run the measurement thread with this nice value (Linux only)."))
+            .arg(Arg::new("realtime-priority")
+                .long("realtime-priority")
+                .takes_value(true)
+                .help("Run the measurement thread with the SCHED_FIFO policy and this priority (Linux only)."))
+            .arg(Arg::new("target-precision")
+                .long("target-precision")
+                .takes_value(true)
+                .conflicts_with("quick")
+                .help("Collect samples until the confidence interval of the mean is within this fraction of the mean, e.g. 0.01 for ±1%."))
+            .arg(Arg::new("max-measurement-time")
+                .long("max-measurement-time")
+                .takes_value(true)
+                .help(&*format!("Changes the default maximum measurement time when a target precision is set. [default: {}]", self.config.max_measurement_time.as_secs())))
+            .arg(Arg::new("warm-up-mode")
+                .long("warm-up-mode")
+                .takes_value(true)
+                .possible_values(["fixed", "steady-state"])
+                .help("Warm up for the warm-up time (fixed), or until the iteration time stops trending (steady-state). [default: fixed]"))
+            .arg(Arg::new("max-warm-up-time")
+                .long("max-warm-up-time")
+                .takes_value(true)
+                .help(&*format!("Changes the default maximum warm-up time of the steady-state warm-up. [default: {}]", self.config.max_warm_up_time.as_secs())))
+            .arg(Arg::new("shard")
+                .long("shard")
+                .takes_value(true)
+                .value_name("INDEX/COUNT")
+                .help("Only run the benchmarks in one of COUNT shards of the suite, numbered from 1."))
+            .arg(Arg::new("merge-shards")
+                .long("merge-shards")
+                .takes_value(true)
+                .multiple_values(true)
+                .value_name("DIR")
+                .help("Merge the output directories of shards into the output directory and generate the report, instead of running benchmarks."))
+            .arg(Arg::new("shuffle")
+                .long("shuffle")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("SEED")
+                .help("Run the benchmark functions, and the shuffled groups and benchmarks, in random order. The seed is printed, and can be passed again to repeat the order."))
+            .arg(Arg::new("time-budget")
+                .long("time-budget")
+                .takes_value(true)
+                .help("Limit the time taken by all matched benchmarks to this many seconds, scaling down their warm-up and measurement times."))
             .arg(Arg::new("test")
                 .hide(true)
                 .long("test")
@@ -991,23 +1485,42 @@ https://bheisler.github.io/criterion.rs/book/faq.html
             self.connection = None;
         }
 
-        let filter = if matches.is_present("ignored") {
-            // --ignored overwrites any name-based filters passed in.
-            BenchmarkFilter::RejectAll
-        } else if let Some(filter) = matches.value_of("FILTER") {
+        let name_filter = |pattern: &str| {
             if matches.is_present("exact") {
-                BenchmarkFilter::Exact(filter.to_owned())
+                BenchmarkFilter::Exact(pattern.to_owned())
             } else {
-                let regex = Regex::new(filter).unwrap_or_else(|err| {
+                let regex = Regex::new(pattern).unwrap_or_else(|err| {
                     panic!(
                         "Unable to parse '{}' as a regular expression: {}",
-                        filter, err
+                        pattern, err
                     )
                 });
                 BenchmarkFilter::Regex(regex)
             }
+        };
+        let tag_filter = |tag: &str| BenchmarkFilter::Tag(tag.to_owned());
+        // Each option matches the benchmarks matching any of its values.
+        let any_of = |name, to_filter: &dyn Fn(&str) -> BenchmarkFilter| {
+            matches.values_of(name).map(|values| {
+                values
+                    .map(to_filter)
+                    .fold(BenchmarkFilter::RejectAll, BenchmarkFilter::or)
+            })
+        };
+
+        let filter = if matches.is_present("ignored") {
+            // --ignored overwrites any name-based filters passed in.
+            BenchmarkFilter::RejectAll
         } else {
-            BenchmarkFilter::AcceptAll
+            vec![
+                any_of("FILTER", &name_filter),
+                any_of("skip", &name_filter).map(|skip| !skip),
+                any_of("tag", &tag_filter),
+                any_of("skip-tag", &tag_filter).map(|skip| !skip),
+            ]
+            .into_iter()
+            .flatten()
+            .fold(BenchmarkFilter::AcceptAll, BenchmarkFilter::and)
         };
         self = self.with_benchmark_filter(filter);
 
@@ -1114,6 +1627,21 @@ https://bheisler.github.io/criterion.rs/book/faq.html
 
             self.config.nresamples = num_resamples;
         }
+        if matches.is_present("repetitions") {
+            let num_repetitions = matches.value_of_t_or_exit("repetitions");
+
+            assert!(num_repetitions > 0);
+
+            self.config.repetitions = num_repetitions;
+        }
+        if matches.is_present("timeout") {
+            let num_seconds = matches.value_of_t_or_exit("timeout");
+
+            let dur = std::time::Duration::from_secs_f64(num_seconds);
+            assert!(dur.as_nanos() > 0);
+
+            self.config.timeout = Some(dur);
+        }
         if matches.is_present("noise-threshold") {
             let num_noise_threshold = matches.value_of_t_or_exit("noise-threshold");
 
@@ -1136,22 +1664,222 @@ https://bheisler.github.io/criterion.rs/book/faq.html
             self.config.significance_level = num_significance_level;
         }
 
+        match matches.value_of("comparison-test") {
+            Some("welch") => self.config.comparison_test = ComparisonTest::Welch,
+            Some("mann-whitney") => self.config.comparison_test = ComparisonTest::MannWhitney,
+            Some("median") => self.config.comparison_test = ComparisonTest::MedianDifference,
+            _ => {}
+        }
+
         if matches.is_present("quick") {
             self.config.quick_mode = true;
         }
 
+        if matches.is_present("calibrate-overhead") {
+            self.config.calibrate_overhead = true;
+        }
+
+        if matches.is_present("isolated") {
+            self.isolated = true;
+        }
+
+        if matches.is_present("cpu") {
+            self.cpu = Some(matches.value_of_t_or_exit("cpu"));
+        }
+        if matches.is_present("nice") {
+            let nice = matches.value_of_t_or_exit("nice");
+
+            assert!((-20..=19).contains(&nice));
+
+            self.priority = Some(Priority::Nice(nice));
+        }
+        if matches.is_present("realtime-priority") {
+            let priority = matches.value_of_t_or_exit("realtime-priority");
+
+            assert!((1..=99).contains(&priority));
+
+            self.priority = Some(Priority::RealTime(priority));
+        }
+
+        if matches.is_present("target-precision") {
+            let precision = matches.value_of_t_or_exit("target-precision");
+
+            assert!(precision > 0.0);
+
+            self.config.target_precision = Some(precision);
+        }
+        if matches.is_present("max-measurement-time") {
+            let num_seconds = matches.value_of_t_or_exit("max-measurement-time");
+
+            let dur = std::time::Duration::from_secs_f64(num_seconds);
+            assert!(dur.as_nanos() > 0);
+
+            self.config.max_measurement_time = dur;
+        }
+
+        if matches.is_present("time-budget") {
+            let num_seconds = matches.value_of_t_or_exit("time-budget");
+
+            let dur = std::time::Duration::from_secs_f64(num_seconds);
+            assert!(dur.as_nanos() > 0);
+
+            self.time_budget = Some(dur);
+        }
+
+        if let Some(shard) = matches.value_of("shard") {
+            match Shard::parse(shard) {
+                Some(shard) => self.shard = Some(shard),
+                None => {
+                    eprintln!(
+                        "Invalid shard '{}'; expected INDEX/COUNT with 1 <= INDEX <= COUNT.",
+                        shard
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        if let Some(shards) = matches.values_of("merge-shards") {
+            self.merge_shards = shards.map(PathBuf::from).collect();
+        }
+
+        if matches.is_present("shuffle") {
+            self.shuffle = Some(match matches.value_of("shuffle") {
+                Some(_) => matches.value_of_t_or_exit("shuffle"),
+                None => stats::rand_util::new_rng().rand_u64(),
+            });
+        }
+
+        match matches.value_of("warm-up-mode") {
+            Some("fixed") => self.config.warm_up_mode = WarmUpMode::Fixed,
+            Some("steady-state") => self.config.warm_up_mode = WarmUpMode::SteadyState,
+            _ => {}
+        }
+        if matches.is_present("max-warm-up-time") {
+            let num_seconds = matches.value_of_t_or_exit("max-warm-up-time");
+
+            let dur = std::time::Duration::from_secs_f64(num_seconds);
+            assert!(dur.as_nanos() > 0);
+
+            self.config.max_warm_up_time = dur;
+        }
+
         self
     }
 
-    fn filter_matches(&self, id: &str) -> bool {
-        match &self.filter {
-            BenchmarkFilter::AcceptAll => true,
-            BenchmarkFilter::Regex(regex) => regex.is_match(id),
-            BenchmarkFilter::Exact(exact) => id == exact,
-            BenchmarkFilter::RejectAll => false,
+    fn filter_matches(&self, id: &str, tags: &[String]) -> bool {
+        if let Some(isolated_id) = isolation::child_benchmark_id() {
+            return id == isolated_id;
+        }
+        // Merging the results of shards doesn't run any benchmarks
+        if !self.merge_shards.is_empty() {
+            return false;
+        }
+        if let Some(shard) = &self.shard {
+            if !shard.contains(id) {
+                return false;
+            }
+        }
+
+        self.filter.matches(id, tags)
+    }
+
+    // Copies the results of the shards into the output directory, and summarizes the groups that
+    // were split between shards again
+    fn merge(&self, report_context: &ReportContext) {
+        let split_groups =
+            try_else_return!(shard::merge(&self.merge_shards, &self.output_directory));
+        let ids = try_else_return!(fs::list_existing_benchmarks(&self.output_directory));
+        for group in split_groups {
+            let group_ids = ids
+                .iter()
+                .filter(|id| id.group_id == group)
+                .cloned()
+                .collect::<Vec<_>>();
+            if group_ids.len() > 1 {
+                self.report
+                    .summarize(report_context, &group_ids, self.measurement.formatter());
+            }
+        }
+    }
+
+    /// Runs a benchmark, reporting it as failed instead of unwinding if it panics, so that the
+    /// remaining benchmarks still run. In isolated child processes, the panic ends the process and
+    /// the parent process reports the failure.
+    fn catch_failure<R>(
+        &self,
+        id: &InternalBenchmarkId,
+        report_context: &ReportContext,
+        run: impl FnOnce() -> R,
+    ) -> Option<R> {
+        if isolation::child_benchmark_id().is_some() {
+            return Some(run());
+        }
+
+        match panic::catch_unwind(AssertUnwindSafe(run)) {
+            Ok(result) => Some(result),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<TimedOut>()
+                    .map(|timed_out| timed_out.message.clone())
+                    .or_else(|| {
+                        payload
+                            .downcast_ref::<&str>()
+                            .map(|message| (*message).to_owned())
+                    })
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "the benchmark panicked".to_owned());
+                self.benchmark_failed(id, report_context, &message);
+                None
+            }
+        }
+    }
+
+    /// Reports a benchmark that failed, and remembers it to exit with an error at the end.
+    fn benchmark_failed(
+        &self,
+        id: &InternalBenchmarkId,
+        report_context: &ReportContext,
+        message: &str,
+    ) {
+        self.report.failed(id, report_context, message);
+        if let Some(conn) = &self.connection {
+            conn.send(&OutgoingMessage::FailedBenchmark {
+                id: id.into(),
+                message,
+            })
+            .unwrap();
+        }
+        FAILED_BENCHMARKS
+            .lock()
+            .unwrap()
+            .push((self.output_directory.clone(), id.as_title().to_owned()));
+    }
+
+    /// Scales the warm-up and measurement times of the benchmarks about to run down to fit the time
+    /// budget. Isolated child processes use the scale chosen by the parent process instead.
+    fn apply_time_budget<'c>(&self, configs: impl IntoIterator<Item = &'c mut BenchmarkConfig>) {
+        if isolation::child_benchmark_id().is_some() {
+            if let Some(scale) = budget::child_scale() {
+                configs
+                    .into_iter()
+                    .for_each(|config| budget::scale_config(config, scale));
+            }
+        } else if let Some(budget) = self.time_budget {
+            if self.load_baseline.is_none() {
+                budget::apply(budget, configs);
+            }
         }
     }
 
+    /// Returns true iff benchmarks should be run in isolated child processes, which is not the case
+    /// in the child processes themselves.
+    fn spawns_isolated(&self) -> bool {
+        self.isolated
+            && self.connection.is_none()
+            && self.load_baseline.is_none()
+            && isolation::child_benchmark_id().is_none()
+    }
+
     /// Returns true iff we should save the benchmark results in
     /// json files on the local disk.
     fn should_save_baseline(&self) -> bool {
@@ -1194,6 +1922,36 @@ https://bheisler.github.io/criterion.rs/book/faq.html
 
         BenchmarkGroup::new(self, group_name)
     }
+
+    /// Return a set of benchmark groups which run when it is finished: in random order with
+    /// `--shuffle`, and in the order they were added otherwise. Each group is added with a function
+    /// which defines its benchmarks, and which is only called when the group runs.
+    ///
+    /// The groups returned by [`benchmark_group`](#method.benchmark_group) run right away, in the
+    /// order they are defined. To shuffle the benchmarks within a group as well, see
+    /// [`BenchmarkGroup::shuffled`](struct.BenchmarkGroup.html#method.shuffled).
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// use criterion::Criterion;
+    ///
+    /// fn bench(c: &mut Criterion) {
+    ///     let input = (0..1000u64).collect::<Vec<_>>();
+    ///
+    ///     let mut groups = c.shuffled_groups();
+    ///     groups.benchmark_group("sum", |group| {
+    ///         group.bench_function("iter", |b| b.iter(|| input.iter().sum::<u64>()));
+    ///     });
+    ///     groups.benchmark_group("max", |group| {
+    ///         group.bench_function("iter", |b| b.iter(|| input.iter().max()));
+    ///     });
+    ///     groups.finish();
+    /// }
+    /// ```
+    pub fn shuffled_groups(&mut self) -> ShuffledGroups<'_, M> {
+        ShuffledGroups::new(self)
+    }
 }
 impl<M> Criterion<M>
 where
@@ -1325,12 +2083,16 @@ pub enum AxisScale {
 #[derive(Debug, Clone)]
 pub struct PlotConfiguration {
     summary_scale: AxisScale,
+    x_axis_parameter: Option<String>,
+    line_parameters: Option<Vec<String>>,
 }
 
 impl Default for PlotConfiguration {
     fn default() -> PlotConfiguration {
         PlotConfiguration {
             summary_scale: AxisScale::Linear,
+            x_axis_parameter: None,
+            line_parameters: None,
         }
     }
 }
@@ -1344,6 +2106,114 @@ impl PlotConfiguration {
         self.summary_scale = new_scale;
         self
     }
+
+    #[must_use]
+    /// Set the parameter whose values go on the x-axis of the line charts of the summary plots,
+    /// for benchmarks with several parameters (see
+    /// [`BenchmarkId::with_parameters`](struct.BenchmarkId.html#method.with_parameters)). Its
+    /// values must be numbers. Defaults to the first parameter whose values are all numbers.
+    pub fn x_axis_parameter<S: Into<String>>(mut self, name: S) -> PlotConfiguration {
+        self.x_axis_parameter = Some(name.into());
+        self
+    }
+
+    #[must_use]
+    /// Set the parameters which get a separate line in the line charts of the summary plots,
+    /// besides the benchmarked function, for benchmarks with several parameters. Every
+    /// combination of the values of the remaining parameters gets a summary of its own. Defaults
+    /// to all parameters except the one on the x-axis.
+    pub fn line_parameters<I>(mut self, names: I) -> PlotConfiguration
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.line_parameters = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// The scheduling priority of the thread that collects the samples. See
+/// [`Criterion::priority`](struct.Criterion.html#method.priority).
+#[derive(Debug, Clone, Copy)]
+pub enum Priority {
+    /// Run with the given nice value, from -20 (highest priority) to 19 (lowest priority).
+    /// Lowering the nice value usually requires elevated privileges.
+    Nice(i32),
+
+    /// Run with the `SCHED_FIFO` real-time policy and the given priority, from 1 to 99. This
+    /// usually requires elevated privileges. A real-time thread that never blocks can starve the
+    /// other threads on its CPU, including those of the system.
+    RealTime(i32),
+}
+
+/// This enum controls the order in which the samples of interleaved benchmarks are collected. See
+/// [`BenchmarkGroup::interleaved`](struct.BenchmarkGroup.html#method.interleaved).
+#[derive(Debug, Clone, Copy)]
+pub enum Interleaving {
+    /// Collect one sample of each benchmark in turn, always in the order the benchmarks were
+    /// defined.
+    RoundRobin,
+
+    /// Collect one sample of each benchmark in turn, in a new random order for every round. This
+    /// also cancels out effects of one benchmark on the next, eg. through the caches.
+    Random,
+}
+
+/// This enum selects the statistical test which decides whether the performance of a benchmark has
+/// changed since the last run or the baseline. Each test compares the times per iteration of the
+/// two samples, and its p-value is reported along with the change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonTest {
+    /// A Welch t test on the mean times, bootstrapped under the null hypothesis. This is the
+    /// default. The change in the mean time is reported.
+    Welch,
+
+    /// A Mann-Whitney U test, which compares the ranks of the times instead of their values, so it
+    /// is robust against outliers and skewed or multi-modal distributions. The change in the median
+    /// time is reported.
+    MannWhitney,
+
+    /// A bootstrapped test of the relative difference of the median times. The change in the
+    /// median time is reported.
+    MedianDifference,
+}
+impl ComparisonTest {
+    /// The name of the test, as shown in the reports.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ComparisonTest::Welch => "Welch t test",
+            ComparisonTest::MannWhitney => "Mann-Whitney U test",
+            ComparisonTest::MedianDifference => "Median difference test",
+        }
+    }
+
+    /// The description of the test statistic, for the axis of its plot.
+    pub(crate) fn statistic_name(self) -> &'static str {
+        match self {
+            ComparisonTest::Welch => "t score",
+            ComparisonTest::MannWhitney => "z score of U",
+            ComparisonTest::MedianDifference => "Relative difference of the medians",
+        }
+    }
+
+    /// Whether the test is about the mean times rather than the median times.
+    pub(crate) fn compares_means(self) -> bool {
+        matches!(self, ComparisonTest::Welch)
+    }
+}
+
+/// This enum controls how long Criterion.rs warms up a benchmark before sampling it.
+#[derive(Debug, Clone, Copy)]
+pub enum WarmUpMode {
+    /// Warm up for the [warm-up time](struct.Criterion.html#method.warm_up_time). This is the
+    /// default.
+    Fixed,
+
+    /// Warm up until the time per iteration stops trending, but for at least the
+    /// [warm-up time](struct.Criterion.html#method.warm_up_time) and at most the
+    /// [maximum warm-up time](struct.Criterion.html#method.max_warm_up_time). The detected length
+    /// of the warm-up is reported in the verbose output and the HTML report.
+    SteadyState,
 }
 
 /// This enum allows the user to control how Criterion.rs chooses the iteration count when sampling.
@@ -1364,6 +2234,14 @@ pub enum SamplingMode {
     /// the Linear method and therefore is more suitable for very long-running benchmarks where
     /// benchmark execution time is more of a problem and statistical precision is less important.
     Flat,
+
+    /// Keep the iteration count the same for all samples, like Flat, but also time every iteration
+    /// separately and record the latencies in a histogram. This adds estimates of the 50th, 90th,
+    /// 99th and 99.9th percentile of the latency to the results, which reveal tail latencies that
+    /// disappear in the averages. The overhead of starting and stopping the measurement around each
+    /// iteration is calibrated and subtracted. Only the `iter` and `iter_batched` families of
+    /// timing loops time individual iterations, and quick mode does not record latencies.
+    PerIteration,
 }
 impl SamplingMode {
     pub(crate) fn choose_sampling_mode(
@@ -1374,7 +2252,7 @@ impl SamplingMode {
     ) -> ActualSamplingMode {
         match self {
             SamplingMode::Linear => ActualSamplingMode::Linear,
-            SamplingMode::Flat => ActualSamplingMode::Flat,
+            SamplingMode::Flat | SamplingMode::PerIteration => ActualSamplingMode::Flat,
             SamplingMode::Auto => {
                 // Estimate execution time with linear sampling
                 let total_runs = sample_count * (sample_count + 1) / 2;
@@ -1404,6 +2282,7 @@ impl ActualSamplingMode {
         warmup_mean_execution_time: f64,
         sample_count: u64,
         target_time: &Duration,
+        budgeted: bool,
     ) -> Vec<u64> {
         match self {
             ActualSamplingMode::Linear => {
@@ -1419,8 +2298,7 @@ impl ActualSamplingMode {
                     let recommended_sample_size =
                         ActualSamplingMode::recommend_linear_sample_size(m_ns as f64, met);
                     let actual_time = Duration::from_nanos(expected_ns as u64);
-                    eprint!("\nWarning: Unable to complete {} samples in {:.1?}. You may wish to increase target time to {:.1?}",
-                            n, target_time, actual_time);
+                    ActualSamplingMode::warn_too_short(n, target_time, actual_time, budgeted);
 
                     if recommended_sample_size != n {
                         eprintln!(
@@ -1448,8 +2326,7 @@ impl ActualSamplingMode {
                     let recommended_sample_size =
                         ActualSamplingMode::recommend_flat_sample_size(m_ns, met);
                     let actual_time = Duration::from_nanos(expected_ns as u64);
-                    eprint!("\nWarning: Unable to complete {} samples in {:.1?}. You may wish to increase target time to {:.1?}",
-                            n, target_time, actual_time);
+                    ActualSamplingMode::warn_too_short(n, target_time, actual_time, budgeted);
 
                     if recommended_sample_size != n {
                         eprintln!(", or reduce sample count to {}.", recommended_sample_size);
@@ -1463,6 +2340,18 @@ impl ActualSamplingMode {
         }
     }
 
+    // Starts the warning about a target time too short for the sample size; the caller completes it
+    // with the recommendations for the sampling mode
+    fn warn_too_short(n: u64, target_time: &Duration, actual_time: Duration, budgeted: bool) {
+        if budgeted {
+            eprint!("\nWarning: The time budget only leaves {:.1?} to complete {} samples. You may wish to increase the time budget to leave {:.1?}",
+                    target_time, n, actual_time);
+        } else {
+            eprint!("\nWarning: Unable to complete {} samples in {:.1?}. You may wish to increase target time to {:.1?}",
+                    n, target_time, actual_time);
+        }
+    }
+
     fn is_linear(&self) -> bool {
         matches!(self, ActualSamplingMode::Linear)
     }
@@ -1510,13 +2399,27 @@ pub(crate) struct SavedSample {
     sampling_mode: ActualSamplingMode,
     iters: Vec<f64>,
     times: Vec<f64>,
+    /// The CPU core the samples were collected on, if the measurement thread was pinned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cpu: Option<usize>,
 }
 
-/// Custom-test-framework runner. Should not be called directly.
+/// Runs the benchmark functions of `criterion_main!` and of custom test frameworks, followed by the
+/// final summary. Should not be called directly.
 #[doc(hidden)]
 pub fn runner(benches: &[&dyn Fn()]) {
-    for bench in benches {
-        bench();
-    }
+    shuffle::run(benches);
     Criterion::default().configure_from_args().final_summary();
 }
+
+/// Runs the targets of `criterion_group!` with the `Criterion` of the group, in random order with
+/// `--shuffle`. Should not be called directly.
+#[doc(hidden)]
+pub fn run_targets<M: Measurement>(
+    criterion: &mut Criterion<M>,
+    targets: &[fn(&mut Criterion<M>)],
+) {
+    for index in shuffle::order(targets.len()) {
+        targets[index](criterion);
+    }
+}
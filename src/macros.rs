@@ -67,9 +67,7 @@ macro_rules! criterion_group {
         pub fn $name() {
             let mut criterion: $crate::Criterion<_> = $config
                 .configure_from_args();
-            $(
-                $target(&mut criterion);
-            )+
+            $crate::run_targets(&mut criterion, &[$( $target ),+]);
         }
     };
     ($name:ident, $( $target:path ),+ $(,)*) => {
@@ -114,19 +112,14 @@ macro_rules! criterion_group {
 /// ```
 ///
 /// The `criterion_main` macro expands to a `main` function which runs all of the
-/// benchmarks in the given groups.
+/// benchmarks in the given groups. With `--shuffle`, the groups, and the targets of each
+/// group, are called in random order.
 ///
 #[macro_export]
 macro_rules! criterion_main {
     ( $( $group:path ),+ $(,)* ) => {
         fn main() {
-            $(
-                $group();
-            )+
-
-            $crate::Criterion::default()
-                .configure_from_args()
-                .final_summary();
+            $crate::runner(&[$( &$group ),+]);
         }
     }
 }
@@ -1,7 +1,9 @@
 //! This module defines a set of traits that can be used to plug different measurements (eg.
 //! Unix's Processor Time, CPU or GPU performance counters, etc.) into Criterion.rs. It also
 //! includes the [WallTime](struct.WallTime.html) struct which defines the default wall-clock time
-//! measurement.
+//! measurement, the [ThreadCpuTime](struct.ThreadCpuTime.html) and
+//! [ProcessCpuTime](struct.ProcessCpuTime.html) CPU time measurements on Unix, and on Linux the
+//! [PerfEvent](struct.PerfEvent.html) struct which reads CPU performance counters.
 
 use crate::format::short;
 use crate::Throughput;
@@ -63,9 +65,8 @@ pub trait ValueFormatter {
     fn scale_for_machines(&self, values: &mut [f64]) -> &'static str;
 }
 
-/// Trait for all types which define something Criterion.rs can measure. The only measurement
-/// currently provided is [WallTime](struct.WallTime.html), but third party crates or benchmarks
-/// may define more.
+/// Trait for all types which define something Criterion.rs can measure. The default measurement is
+/// [WallTime](struct.WallTime.html), but third party crates or benchmarks may define more.
 ///
 /// This trait defines two core methods, `start` and `end`. `start` is called at the beginning of
 /// a measurement to produce some intermediate value (for example, the wall-clock time at the start
@@ -100,8 +101,119 @@ pub trait Measurement {
 
     /// Return a trait-object reference to the value formatter for this measurement.
     fn formatter(&self) -> &dyn ValueFormatter;
+
+    /// Return true if a benchmark can legitimately measure zero, as an allocation-free routine does
+    /// when counting allocations. By default a zero measurement, which is impossible for time,
+    /// is reported as an error.
+    fn allows_zero(&self) -> bool {
+        false
+    }
+
+    /// Return the number of metrics recorded by this measurement. Most measurements record a
+    /// single metric, but composite measurements (such as tuples of measurements) record several
+    /// at once. Metric 0 is the primary metric, which is the one returned by `to_f64` and
+    /// `formatter`.
+    fn metric_count(&self) -> usize {
+        1
+    }
+
+    /// Return a short name for the given metric, such as "time" or "instructions". It is used to
+    /// label the metric in the reports and to name the directory its results are saved in, so the
+    /// names of all metrics of a measurement should be distinct. The default name, "value", should
+    /// be overridden by any measurement that can be combined with others.
+    fn metric_name(&self, _index: usize) -> &str {
+        "value"
+    }
+
+    /// Converts the given metric of the measured value to f64.
+    fn metric_to_f64(&self, value: &Self::Value, _index: usize) -> f64 {
+        self.to_f64(value)
+    }
+
+    /// Return a trait-object reference to the value formatter for the given metric.
+    fn metric_formatter(&self, _index: usize) -> &dyn ValueFormatter {
+        self.formatter()
+    }
+}
+
+// Maps an index into the flattened list of metrics of a composite measurement to the index of the
+// measurement that records it and the index of the metric within that measurement.
+fn locate_metric(counts: &[usize], mut index: usize) -> (usize, usize) {
+    for (measurement, &count) in counts.iter().enumerate() {
+        if index < count {
+            return (measurement, index);
+        }
+        index -= count;
+    }
+    panic!("Metric index out of range");
+}
+
+macro_rules! composite_measurement {
+    ($(($T:ident, $idx:tt, $var:ident)),+; $(($ridx:tt, $rvar:ident)),+) => {
+        /// A tuple of measurements records all of them in the same sampling loop. The first
+        /// element is the primary measurement; it is started last and stopped first so that it
+        /// does not include the overhead of the other measurements. Each element contributes its
+        /// metrics to the combined measurement, and every metric is analyzed and reported
+        /// separately. Only the primary measurement is reported to cargo-criterion.
+        impl<$($T: Measurement),+> Measurement for ($($T,)+) {
+            type Intermediate = ($($T::Intermediate,)+);
+            type Value = ($($T::Value,)+);
+
+            fn start(&self) -> Self::Intermediate {
+                $(let $rvar = self.$ridx.start();)+
+                ($($var,)+)
+            }
+            fn end(&self, i: Self::Intermediate) -> Self::Value {
+                let ($($var,)+) = i;
+                ($(self.$idx.end($var),)+)
+            }
+            fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+                ($(self.$idx.add(&v1.$idx, &v2.$idx),)+)
+            }
+            fn zero(&self) -> Self::Value {
+                ($(self.$idx.zero(),)+)
+            }
+            fn to_f64(&self, value: &Self::Value) -> f64 {
+                self.0.to_f64(&value.0)
+            }
+            fn formatter(&self) -> &dyn ValueFormatter {
+                self.0.formatter()
+            }
+            fn allows_zero(&self) -> bool {
+                self.0.allows_zero()
+            }
+            fn metric_count(&self) -> usize {
+                0 $(+ self.$idx.metric_count())+
+            }
+            fn metric_name(&self, index: usize) -> &str {
+                match locate_metric(&[$(self.$idx.metric_count()),+], index) {
+                    $(($idx, index) => self.$idx.metric_name(index),)+
+                    _ => unreachable!(),
+                }
+            }
+            fn metric_to_f64(&self, value: &Self::Value, index: usize) -> f64 {
+                match locate_metric(&[$(self.$idx.metric_count()),+], index) {
+                    $(($idx, index) => self.$idx.metric_to_f64(&value.$idx, index),)+
+                    _ => unreachable!(),
+                }
+            }
+            fn metric_formatter(&self, index: usize) -> &dyn ValueFormatter {
+                match locate_metric(&[$(self.$idx.metric_count()),+], index) {
+                    $(($idx, index) => self.$idx.metric_formatter(index),)+
+                    _ => unreachable!(),
+                }
+            }
+        }
+    };
 }
 
+composite_measurement!((A, 0, a), (B, 1, b); (1, b), (0, a));
+composite_measurement!((A, 0, a), (B, 1, b), (C, 2, c); (2, c), (1, b), (0, a));
+composite_measurement!(
+    (A, 0, a), (B, 1, b), (C, 2, c), (D, 3, d);
+    (3, d), (2, c), (1, b), (0, a)
+);
+
 pub(crate) struct DurationFormatter;
 impl DurationFormatter {
     fn bytes_per_second(&self, bytes: f64, typical: f64, values: &mut [f64]) -> &'static str {
@@ -211,6 +323,59 @@ impl ValueFormatter for DurationFormatter {
     }
 }
 
+/// Formats plain event counts (eg. instructions, cycles or allocations) using decimal SI prefixes.
+pub(crate) struct CountFormatter {
+    /// The unit for counts, with SI prefixes from none up to giga.
+    pub(crate) units: [&'static str; 4],
+    /// The units for bytes, decimal bytes and elements per event.
+    pub(crate) throughput_units: [&'static str; 3],
+    pub(crate) machine_unit: &'static str,
+}
+
+impl ValueFormatter for CountFormatter {
+    fn scale_throughputs(
+        &self,
+        _typical: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (amount, unit) = match *throughput {
+            Throughput::Bytes(bytes) => (bytes as f64, self.throughput_units[0]),
+            Throughput::BytesDecimal(bytes) => (bytes as f64, self.throughput_units[1]),
+            Throughput::Elements(elems) => (elems as f64, self.throughput_units[2]),
+        };
+
+        for val in values {
+            *val = amount / *val;
+        }
+
+        unit
+    }
+
+    fn scale_values(&self, count: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if count < 10f64.powi(3) {
+            (10f64.powi(0), self.units[0])
+        } else if count < 10f64.powi(6) {
+            (10f64.powi(-3), self.units[1])
+        } else if count < 10f64.powi(9) {
+            (10f64.powi(-6), self.units[2])
+        } else {
+            (10f64.powi(-9), self.units[3])
+        };
+
+        for val in values {
+            *val *= factor;
+        }
+
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        // no scaling is needed
+        self.machine_unit
+    }
+}
+
 /// `WallTime` is the default measurement in Criterion.rs. It measures the elapsed time from the
 /// beginning of a series of iterations to the end.
 pub struct WallTime;
@@ -236,4 +401,314 @@ impl Measurement for WallTime {
     fn formatter(&self) -> &dyn ValueFormatter {
         &DurationFormatter
     }
+    fn metric_name(&self, _index: usize) -> &str {
+        "time"
+    }
+}
+
+/// Read the given clock of `clock_gettime`.
+#[cfg(unix)]
+fn clock_time(clock: libc::clockid_t) -> Duration {
+    let mut time: libc::timespec = unsafe { std::mem::zeroed() };
+    // clock_gettime only fails for unsupported clocks or invalid pointers
+    let result = unsafe { libc::clock_gettime(clock, &mut time) };
+    assert_eq!(
+        result,
+        0,
+        "clock_gettime failed: {}",
+        std::io::Error::last_os_error()
+    );
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
 }
+
+/// `ThreadCpuTime` measures the CPU time consumed by the benchmark thread, using
+/// `clock_gettime(CLOCK_THREAD_CPUTIME_ID)`. Unlike [WallTime](struct.WallTime.html), it excludes
+/// the time the thread spent descheduled, which makes it much less sensitive to other load on the
+/// machine. Time spent in other threads, or waiting for I/O, is not included either.
+///
+/// Asynchronous runtimes may poll the benchmark on other threads; with a multi-threaded runtime,
+/// use [ProcessCpuTime](struct.ProcessCpuTime.html) instead.
+#[cfg(unix)]
+pub struct ThreadCpuTime;
+#[cfg(unix)]
+impl Measurement for ThreadCpuTime {
+    type Intermediate = Duration;
+    type Value = Duration;
+
+    fn start(&self) -> Self::Intermediate {
+        clock_time(libc::CLOCK_THREAD_CPUTIME_ID)
+    }
+    fn end(&self, i: Self::Intermediate) -> Self::Value {
+        clock_time(libc::CLOCK_THREAD_CPUTIME_ID).saturating_sub(i)
+    }
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        *v1 + *v2
+    }
+    fn zero(&self) -> Self::Value {
+        Duration::from_secs(0)
+    }
+    fn to_f64(&self, val: &Self::Value) -> f64 {
+        val.as_nanos() as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &DurationFormatter
+    }
+    fn metric_name(&self, _index: usize) -> &str {
+        "thread-cpu-time"
+    }
+}
+
+/// `ProcessCpuTime` measures the CPU time consumed by all threads of the benchmark process, using
+/// `clock_gettime(CLOCK_PROCESS_CPUTIME_ID)`. Like [ThreadCpuTime](struct.ThreadCpuTime.html) it
+/// excludes the time spent descheduled, but it includes work done by other threads, such as the
+/// worker threads of an asynchronous runtime.
+#[cfg(unix)]
+pub struct ProcessCpuTime;
+#[cfg(unix)]
+impl Measurement for ProcessCpuTime {
+    type Intermediate = Duration;
+    type Value = Duration;
+
+    fn start(&self) -> Self::Intermediate {
+        clock_time(libc::CLOCK_PROCESS_CPUTIME_ID)
+    }
+    fn end(&self, i: Self::Intermediate) -> Self::Value {
+        clock_time(libc::CLOCK_PROCESS_CPUTIME_ID).saturating_sub(i)
+    }
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        *v1 + *v2
+    }
+    fn zero(&self) -> Self::Value {
+        Duration::from_secs(0)
+    }
+    fn to_f64(&self, val: &Self::Value) -> f64 {
+        val.as_nanos() as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &DurationFormatter
+    }
+    fn metric_name(&self, _index: usize) -> &str {
+        "process-cpu-time"
+    }
+}
+
+/// The event counted by a [`PerfEvent`](struct.PerfEvent.html) measurement.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfCounter {
+    /// Retired instructions (`PERF_COUNT_HW_INSTRUCTIONS`). Requires hardware counters.
+    Instructions,
+    /// CPU cycles (`PERF_COUNT_HW_CPU_CYCLES`). Requires hardware counters.
+    Cycles,
+    /// The time the benchmark thread was scheduled on a CPU, in nanoseconds
+    /// (`PERF_COUNT_SW_TASK_CLOCK`). This is a software event provided by the kernel and is
+    /// available in most virtual machines.
+    TaskClock,
+}
+
+#[cfg(target_os = "linux")]
+impl PerfCounter {
+    fn type_and_config(self) -> (u32, u64) {
+        const PERF_TYPE_HARDWARE: u32 = 0;
+        const PERF_TYPE_SOFTWARE: u32 = 1;
+        const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+        const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+        const PERF_COUNT_SW_TASK_CLOCK: u64 = 1;
+
+        match self {
+            PerfCounter::Instructions => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS),
+            PerfCounter::Cycles => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES),
+            PerfCounter::TaskClock => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_TASK_CLOCK),
+        }
+    }
+}
+
+/// Mirror of the kernel's `struct perf_event_attr` (`PERF_ATTR_SIZE_VER5`).
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    reserved: u16,
+}
+
+/// `PerfEvent` counts a CPU performance event for the benchmark thread using the Linux
+/// `perf_event_open` interface. Counting instructions retired or CPU cycles instead of wall-clock
+/// time makes the results much less sensitive to other processes running on the same machine.
+///
+/// Hardware counters are often unavailable inside virtual machines and containers. The
+/// [`instructions`](#method.instructions) and [`cycles`](#method.cycles) constructors fall back to
+/// the `task-clock` software event in that case; use [`counter`](#method.counter) to find out
+/// which event is actually being counted. If even the software event cannot be opened (eg.
+/// because `/proc/sys/kernel/perf_event_paranoid` forbids it), an error is returned.
+///
+/// Only the events of the thread which constructed the `PerfEvent` are counted, and events in the
+/// kernel and hypervisor are excluded. The benchmarks should therefore run on that thread; the
+/// worker threads of [`Bencher::iter_parallel`](../struct.Bencher.html#method.iter_parallel) are
+/// not counted, and asynchronous runtimes may poll the benchmark on other threads.
+///
+/// ```no_run
+/// use criterion::measurement::PerfEvent;
+/// use criterion::Criterion;
+///
+/// let criterion = Criterion::default()
+///     .with_measurement(PerfEvent::instructions().expect("perf_event_open is not permitted"));
+/// ```
+#[cfg(target_os = "linux")]
+pub struct PerfEvent {
+    counter: PerfCounter,
+    file: std::fs::File,
+}
+
+#[cfg(target_os = "linux")]
+impl PerfEvent {
+    /// Open a counter for exactly the given event, without falling back to a software event.
+    pub fn new(counter: PerfCounter) -> std::io::Result<PerfEvent> {
+        use std::os::unix::io::FromRawFd;
+
+        const DISABLED: u64 = 1 << 0;
+        const EXCLUDE_KERNEL: u64 = 1 << 5;
+        const EXCLUDE_HV: u64 = 1 << 6;
+        const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+        const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+
+        let (type_, config) = counter.type_and_config();
+        let attr = PerfEventAttr {
+            type_,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            flags: DISABLED | EXCLUDE_KERNEL | EXCLUDE_HV,
+            ..Default::default()
+        };
+
+        // Count the calling thread (pid = 0) on any CPU (cpu = -1), without a group leader.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                0 as libc::pid_t,
+                -1 as libc::c_int,
+                -1 as libc::c_int,
+                0 as libc::c_ulong,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = fd as libc::c_int;
+        // Take ownership right away so that the descriptor is closed on the error paths below.
+        let file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+        for request in [PERF_EVENT_IOC_RESET, PERF_EVENT_IOC_ENABLE] {
+            if unsafe { libc::ioctl(fd, request as _, 0) } < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        Ok(PerfEvent { counter, file })
+    }
+
+    /// Count instructions retired, falling back to the `task-clock` software event if the hardware
+    /// counter is not available.
+    pub fn instructions() -> std::io::Result<PerfEvent> {
+        PerfEvent::with_fallback(PerfCounter::Instructions)
+    }
+
+    /// Count CPU cycles, falling back to the `task-clock` software event if the hardware counter
+    /// is not available.
+    pub fn cycles() -> std::io::Result<PerfEvent> {
+        PerfEvent::with_fallback(PerfCounter::Cycles)
+    }
+
+    /// Count the time the benchmark thread spent running on a CPU, using the `task-clock` software
+    /// event.
+    pub fn task_clock() -> std::io::Result<PerfEvent> {
+        PerfEvent::new(PerfCounter::TaskClock)
+    }
+
+    /// Return the event which is actually being counted.
+    pub fn counter(&self) -> PerfCounter {
+        self.counter
+    }
+
+    fn with_fallback(counter: PerfCounter) -> std::io::Result<PerfEvent> {
+        PerfEvent::new(counter).or_else(|_| PerfEvent::new(PerfCounter::TaskClock))
+    }
+
+    fn read_counter(&self) -> u64 {
+        use std::io::Read;
+
+        let mut buf = [0u8; 8];
+        (&self.file)
+            .read_exact(&mut buf)
+            .expect("Unable to read performance counter");
+        u64::from_ne_bytes(buf)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Measurement for PerfEvent {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> Self::Intermediate {
+        self.read_counter()
+    }
+    fn end(&self, i: Self::Intermediate) -> Self::Value {
+        self.read_counter().saturating_sub(i)
+    }
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+    fn zero(&self) -> Self::Value {
+        0
+    }
+    fn to_f64(&self, val: &Self::Value) -> f64 {
+        *val as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        match self.counter {
+            PerfCounter::Instructions => &INSTRUCTIONS_FORMATTER,
+            PerfCounter::Cycles => &CYCLES_FORMATTER,
+            PerfCounter::TaskClock => &DurationFormatter,
+        }
+    }
+    fn metric_name(&self, _index: usize) -> &str {
+        match self.counter {
+            PerfCounter::Instructions => "instructions",
+            PerfCounter::Cycles => "cycles",
+            PerfCounter::TaskClock => "task-clock",
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+const INSTRUCTIONS_FORMATTER: CountFormatter = CountFormatter {
+    units: ["instr", "Kinstr", "Minstr", "Ginstr"],
+    throughput_units: ["B/instr", "B/instr", "elem/instr"],
+    machine_unit: "instructions",
+};
+
+#[cfg(target_os = "linux")]
+const CYCLES_FORMATTER: CountFormatter = CountFormatter {
+    units: ["cycles", "Kcycles", "Mcycles", "Gcycles"],
+    throughput_units: ["B/cycle", "B/cycle", "elem/cycle"],
+    machine_unit: "cycles",
+};
@@ -9,12 +9,14 @@ mod distributions;
 mod iteration_times;
 mod pdf;
 mod regression;
+mod repetitions;
 mod summary;
 mod t_test;
 use self::distributions::*;
 use self::iteration_times::*;
 use self::pdf::*;
 use self::regression::*;
+use self::repetitions::*;
 use self::summary::*;
 use self::t_test::*;
 
@@ -199,6 +201,21 @@ impl Plotter for Gnuplot {
         }
     }
 
+    fn repetitions(&mut self, ctx: PlotContext<'_>, data: PlotData<'_>) {
+        let size = ctx.size.map(|(w, h)| Size(w, h));
+        if let Some(estimates) = &data.measurements.repetitions {
+            self.process_list.push(repetitions(
+                ctx.id,
+                ctx.context,
+                data.formatter,
+                estimates,
+                size,
+            ));
+        } else {
+            error!("Repetitions are not provided for repetitions plot");
+        }
+    }
+
     fn line_comparison(
         &mut self,
         ctx: PlotContext<'_>,
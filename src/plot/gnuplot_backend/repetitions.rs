@@ -0,0 +1,88 @@
+use std::process::Child;
+
+use criterion_plot::prelude::*;
+
+use super::*;
+use crate::estimate::Repetitions;
+use crate::report::{BenchmarkId, ReportContext};
+
+use crate::measurement::ValueFormatter;
+
+pub(crate) fn repetitions(
+    id: &BenchmarkId,
+    context: &ReportContext,
+    formatter: &dyn ValueFormatter,
+    repetitions: &Repetitions,
+    size: Option<Size>,
+) -> Child {
+    let count = repetitions.estimates.len();
+    let mut points = repetitions.typical();
+    let mut lower: Vec<_> = repetitions
+        .estimates
+        .iter()
+        .map(|estimates| estimates.typical().confidence_interval.lower_bound)
+        .collect();
+    let mut upper: Vec<_> = repetitions
+        .estimates
+        .iter()
+        .map(|estimates| estimates.typical().confidence_interval.upper_bound)
+        .collect();
+    let mut mean = [repetitions.spread.mean];
+    let unit = formatter.scale_values(repetitions.spread.mean, &mut points);
+    let _ = formatter.scale_values(repetitions.spread.mean, &mut lower);
+    let _ = formatter.scale_values(repetitions.spread.mean, &mut upper);
+    let _ = formatter.scale_values(repetitions.spread.mean, &mut mean);
+
+    let mut figure = Figure::new();
+    figure
+        .set(Font(DEFAULT_FONT))
+        .set(size.unwrap_or(SIZE))
+        .set(Title(format!(
+            "{}: Repetitions",
+            gnuplot_escape(id.as_title())
+        )))
+        .configure(Axis::BottomX, |a| {
+            a.configure(Grid::Major, |g| g.show())
+                .set(Label(format!("Average Iteration Time ({})", unit)))
+        })
+        .configure(Axis::LeftY, |a| {
+            a.set(Label("Repetition"))
+                .set(Range::Limits(0., count as f64 + 1.))
+        })
+        .configure(Key, |k| {
+            k.set(Justification::Left)
+                .set(Order::SampleText)
+                .set(Position::Outside(Vertical::Top, Horizontal::Right))
+        })
+        .plot(
+            XErrorBars {
+                x: &*points,
+                y: 1..(count + 1),
+                x_low: &*lower,
+                x_high: &*upper,
+            },
+            |e| {
+                e.set(DARK_BLUE)
+                    .set(LINEWIDTH)
+                    .set(POINT_SIZE)
+                    .set(PointType::FilledCircle)
+                    .set(Label("Estimate and confidence interval"))
+            },
+        )
+        .plot(
+            Lines {
+                x: &[mean[0], mean[0]],
+                y: &[0., count as f64 + 1.],
+            },
+            |c| {
+                c.set(DARK_ORANGE)
+                    .set(LINEWIDTH)
+                    .set(LineType::Dash)
+                    .set(Label("Mean of the repetitions"))
+            },
+        );
+
+    let path = context.report_path(id, "repetitions.svg");
+    debug_script(&path, &figure);
+    figure.set(Output(path)).draw().unwrap()
+}
@@ -14,8 +14,9 @@ pub(crate) fn t_test(
     comparison: &ComparisonData,
     size: Option<Size>,
 ) -> Child {
-    let t = comparison.t_value;
-    let (xs, ys) = kde::sweep(&comparison.t_distribution, KDE_POINTS, None);
+    let test = comparison.test;
+    let t = comparison.test_statistic;
+    let (xs, ys) = kde::sweep(&comparison.test_distribution, KDE_POINTS, None);
     let zero = iter::repeat(0);
 
     let mut figure = Figure::new();
@@ -23,10 +24,11 @@ pub(crate) fn t_test(
         .set(Font(DEFAULT_FONT))
         .set(size.unwrap_or(SIZE))
         .set(Title(format!(
-            "{}: Welch t test",
-            gnuplot_escape(id.as_title())
+            "{}: {}",
+            gnuplot_escape(id.as_title()),
+            test.name()
         )))
-        .configure(Axis::BottomX, |a| a.set(Label("t score")))
+        .configure(Axis::BottomX, |a| a.set(Label(test.statistic_name())))
         .configure(Axis::LeftY, |a| a.set(Label("Density")))
         .configure(Key, |k| {
             k.set(Justification::Left)
@@ -41,7 +43,7 @@ pub(crate) fn t_test(
             },
             |c| {
                 c.set(DARK_BLUE)
-                    .set(Label("t distribution"))
+                    .set(Label("Null distribution"))
                     .set(Opacity(0.25))
             },
         )
@@ -54,7 +56,7 @@ pub(crate) fn t_test(
                 c.set(Axes::BottomXRightY)
                     .set(DARK_BLUE)
                     .set(LINEWIDTH)
-                    .set(Label("t statistic"))
+                    .set(Label("Test statistic"))
                     .set(LineType::Solid)
             },
         );
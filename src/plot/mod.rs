@@ -101,5 +101,7 @@ pub(crate) trait Plotter {
 
     fn t_test(&mut self, ctx: PlotContext<'_>, data: PlotData<'_>);
 
+    fn repetitions(&mut self, ctx: PlotContext<'_>, data: PlotData<'_>);
+
     fn wait(&mut self);
 }
@@ -21,6 +21,7 @@ mod distributions;
 mod iteration_times;
 mod pdf;
 mod regression;
+mod repetitions;
 mod summary;
 mod t_test;
 
@@ -228,5 +229,16 @@ impl Plotter for PlottersBackend {
         );
     }
 
+    fn repetitions(&mut self, ctx: PlotContext<'_>, data: PlotData<'_>) {
+        let path = ctx.context.report_path(ctx.id, "repetitions.svg");
+        repetitions::repetitions(
+            ctx.id.as_title(),
+            path.as_path(),
+            data.formatter,
+            data.measurements.repetitions.as_ref().unwrap(),
+            convert_size(ctx.size),
+        );
+    }
+
     fn wait(&mut self) {}
 }
@@ -0,0 +1,89 @@
+use super::*;
+
+use crate::estimate::Repetitions;
+use std::path::Path;
+
+pub(crate) fn repetitions(
+    title: &str,
+    path: &Path,
+    formatter: &dyn ValueFormatter,
+    repetitions: &Repetitions,
+    size: Option<(u32, u32)>,
+) {
+    let count = repetitions.estimates.len();
+    let mut points = repetitions.typical();
+    let mut lower: Vec<_> = repetitions
+        .estimates
+        .iter()
+        .map(|estimates| estimates.typical().confidence_interval.lower_bound)
+        .collect();
+    let mut upper: Vec<_> = repetitions
+        .estimates
+        .iter()
+        .map(|estimates| estimates.typical().confidence_interval.upper_bound)
+        .collect();
+    let mut mean = [repetitions.spread.mean];
+    let unit = formatter.scale_values(repetitions.spread.mean, &mut points);
+    let _ = formatter.scale_values(repetitions.spread.mean, &mut lower);
+    let _ = formatter.scale_values(repetitions.spread.mean, &mut upper);
+    let _ = formatter.scale_values(repetitions.spread.mean, &mut mean);
+    let mean = mean[0];
+
+    let mut x_range = plotters::data::fitting_range(lower.iter().chain(upper.iter()));
+    // Repetitions without any spread (eg. a constant number of allocations) need some room
+    if x_range.start == x_range.end {
+        x_range = (x_range.start - 1.0)..(x_range.end + 1.0);
+    }
+    let y_range = 0.0..(count as f64 + 1.0);
+
+    let root_area = SVGBackend::new(path, size.unwrap_or(SIZE)).into_drawing_area();
+    let mut chart = ChartBuilder::on(&root_area)
+        .margin((5).percent())
+        .caption(format!("{}: Repetitions", title), (DEFAULT_FONT, 20))
+        .set_label_area_size(LabelAreaPosition::Left, (5).percent_width().min(60))
+        .set_label_area_size(LabelAreaPosition::Bottom, (5).percent_height().min(40))
+        .build_cartesian_2d(x_range, y_range)
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .x_desc(format!("Average Iteration Time ({})", unit))
+        .y_desc("Repetition")
+        .x_label_formatter(&|x| pretty_print_float(*x, true))
+        .light_line_style(TRANSPARENT)
+        .draw()
+        .unwrap();
+
+    chart
+        .draw_series((1..=count).zip(lower.iter().zip(upper.iter())).map(
+            |(y, (&lower, &upper))| {
+                PathElement::new(vec![(lower, y as f64), (upper, y as f64)], DARK_BLUE)
+            },
+        ))
+        .unwrap();
+
+    chart
+        .draw_series(
+            (1..=count)
+                .zip(points.iter())
+                .map(|(y, &x)| Circle::new((x, y as f64), POINT_SIZE, DARK_BLUE.filled())),
+        )
+        .unwrap()
+        .label("Estimate and confidence interval")
+        .legend(|(x, y)| Circle::new((x + 10, y), POINT_SIZE, DARK_BLUE.filled()));
+
+    chart
+        .draw_series(std::iter::once(PathElement::new(
+            vec![(mean, 0.0), (mean, count as f64 + 1.0)],
+            DARK_ORANGE,
+        )))
+        .unwrap()
+        .label("Mean of the repetitions")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], DARK_ORANGE));
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .draw()
+        .unwrap();
+}
@@ -8,8 +8,9 @@ pub(crate) fn t_test(
     comparison: &ComparisonData,
     size: Option<(u32, u32)>,
 ) {
-    let t = comparison.t_value;
-    let (xs, ys) = kde::sweep(&comparison.t_distribution, KDE_POINTS, None);
+    let test = comparison.test;
+    let t = comparison.test_statistic;
+    let (xs, ys) = kde::sweep(&comparison.test_distribution, KDE_POINTS, None);
 
     let x_range = plotters::data::fitting_range(xs.iter());
     let mut y_range = plotters::data::fitting_range(ys.iter());
@@ -20,7 +21,7 @@ pub(crate) fn t_test(
 
     let mut chart = ChartBuilder::on(&root_area)
         .margin((5).percent())
-        .caption(format!("{}: Welch t test", title), (DEFAULT_FONT, 20))
+        .caption(format!("{}: {}", title, test.name()), (DEFAULT_FONT, 20))
         .set_label_area_size(LabelAreaPosition::Left, (5).percent_width().min(60))
         .set_label_area_size(LabelAreaPosition::Bottom, (5).percent_height().min(40))
         .build_cartesian_2d(x_range, y_range.clone())
@@ -30,7 +31,7 @@ pub(crate) fn t_test(
         .configure_mesh()
         .disable_mesh()
         .y_desc("Density")
-        .x_desc("t score")
+        .x_desc(test.statistic_name())
         .draw()
         .unwrap();
 
@@ -41,7 +42,7 @@ pub(crate) fn t_test(
             DARK_BLUE.mix(0.25),
         ))
         .unwrap()
-        .label("t distribution")
+        .label("Null distribution")
         .legend(|(x, y)| {
             Rectangle::new([(x, y - 5), (x + 20, y + 5)], DARK_BLUE.mix(0.25).filled())
         });
@@ -52,7 +53,7 @@ pub(crate) fn t_test(
             DARK_BLUE.filled().stroke_width(2),
         )))
         .unwrap()
-        .label("t statistic")
+        .label("Test statistic")
         .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], DARK_BLUE));
 
     chart.configure_series_labels().draw().unwrap();
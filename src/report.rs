@@ -4,12 +4,14 @@ use crate::stats::bivariate::regression::Slope;
 use crate::stats::univariate::outliers::tukey::LabeledSample;
 use crate::{html::Html, stats::bivariate::Data};
 
-use crate::estimate::{ChangeDistributions, ChangeEstimates, Distributions, Estimate, Estimates};
+use crate::estimate::{
+    ChangeDistributions, ChangeEstimates, Distributions, Estimate, Estimates, Repetitions,
+};
 use crate::format;
 use crate::measurement::ValueFormatter;
 use crate::stats::univariate::Sample;
 use crate::stats::Distribution;
-use crate::{PlotConfiguration, Throughput};
+use crate::{ComparisonTest, PlotConfiguration, Throughput};
 use anes::{Attribute, ClearLine, Color, ResetAttributes, SetAttribute, SetForegroundColor};
 use std::cmp;
 use std::collections::HashSet;
@@ -22,9 +24,10 @@ const MAX_DIRECTORY_NAME_LEN: usize = 64;
 const MAX_TITLE_LEN: usize = 100;
 
 pub(crate) struct ComparisonData {
+    pub test: ComparisonTest,
     pub p_value: f64,
-    pub t_distribution: Distribution<f64>,
-    pub t_value: f64,
+    pub test_distribution: Distribution<f64>,
+    pub test_statistic: f64,
     pub relative_estimates: ChangeEstimates,
     pub relative_distributions: ChangeDistributions,
     pub significance_threshold: f64,
@@ -34,6 +37,35 @@ pub(crate) struct ComparisonData {
     pub base_avg_times: Vec<f64>,
     pub base_estimates: Estimates,
 }
+impl ComparisonData {
+    /// The relative change in the statistic which the comparison test is about: the mean for the
+    /// t test, the median otherwise.
+    pub fn change(&self) -> &Estimate {
+        if self.test.compares_means() {
+            &self.relative_estimates.mean
+        } else {
+            &self.relative_estimates.median
+        }
+    }
+
+    /// Names the comparison test after the p-value, unless it is the default t test.
+    pub fn test_suffix(&self) -> String {
+        if self.test.compares_means() {
+            String::new()
+        } else {
+            format!(", {}", self.test.name())
+        }
+    }
+}
+
+/// The length of a steady-state warm-up, next to the maximum it was allowed to take.
+#[derive(Clone, Copy)]
+pub(crate) struct WarmUp {
+    /// The time the warm-up took in nanoseconds.
+    pub length: f64,
+    /// The configured maximum warm-up time in nanoseconds.
+    pub max_length: f64,
+}
 
 pub(crate) struct MeasurementData<'a> {
     pub data: Data<'a, f64, f64>,
@@ -42,6 +74,13 @@ pub(crate) struct MeasurementData<'a> {
     pub distributions: Distributions,
     pub comparison: Option<ComparisonData>,
     pub throughput: Option<Throughput>,
+    /// The per-iteration timing loop overhead that was subtracted from the samples, if any.
+    pub overhead: Option<f64>,
+    /// The length of the warm-up, if it ran until a steady state was detected.
+    pub warm_up: Option<WarmUp>,
+    /// The estimates of every repetition of the benchmark, if it was repeated.
+    pub repetitions: Option<Repetitions>,
+    pub metrics: Vec<MetricData<'a>>,
 }
 impl<'a> MeasurementData<'a> {
     pub fn iter_counts(&self) -> &Sample<f64> {
@@ -54,6 +93,14 @@ impl<'a> MeasurementData<'a> {
     }
 }
 
+/// The analysis of one of the secondary metrics of a composite measurement.
+pub(crate) struct MetricData<'a> {
+    pub name: &'a str,
+    pub id: BenchmarkId,
+    pub formatter: &'a dyn ValueFormatter,
+    pub measurements: MeasurementData<'a>,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ValueType {
     Bytes,
@@ -61,17 +108,38 @@ pub enum ValueType {
     Value,
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BenchmarkId {
     pub group_id: String,
     pub function_id: Option<String>,
     pub value_str: Option<String>,
     pub throughput: Option<Throughput>,
+    /// The names and values of the parameters of a benchmark with several parameters.
+    #[serde(default)]
+    pub parameters: Vec<(String, String)>,
+    /// The tags of the benchmark and its group, which benchmarks can be filtered by.
+    #[serde(default)]
+    pub tags: Vec<String>,
     full_id: String,
     directory_name: String,
     title: String,
 }
 
+// The parameters are already part of the value string, and the tags only select which benchmarks
+// run, so a benchmark registered twice with different tags is still the same benchmark.
+impl PartialEq for BenchmarkId {
+    fn eq(&self, other: &BenchmarkId) -> bool {
+        self.group_id == other.group_id
+            && self.function_id == other.function_id
+            && self.value_str == other.value_str
+            && self.throughput == other.throughput
+            && self.full_id == other.full_id
+            && self.directory_name == other.directory_name
+            && self.title == other.title
+    }
+}
+impl Eq for BenchmarkId {}
+
 fn truncate_to_character_boundary(s: &mut String, max_len: usize) {
     let mut boundary = cmp::min(max_len, s.len());
     while !s.is_char_boundary(boundary) {
@@ -153,6 +221,8 @@ impl BenchmarkId {
             function_id,
             value_str,
             throughput,
+            parameters: vec![],
+            tags: vec![],
             full_id,
             directory_name,
             title,
@@ -171,6 +241,27 @@ impl BenchmarkId {
         &self.directory_name
     }
 
+    /// Returns the ID under which the given secondary metric of a composite measurement is saved
+    /// and reported.
+    pub fn metric(&self, name: &str) -> BenchmarkId {
+        let mut id = self.clone();
+        id.title = format!("{} ({})", self.title, name);
+        id.directory_name = format!(
+            "{}/metrics/{}",
+            self.directory_name,
+            make_filename_safe(name)
+        );
+        id
+    }
+
+    /// Returns the value of the named parameter of a benchmark with several parameters.
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.as_str())
+    }
+
     pub fn as_number(&self) -> Option<f64> {
         match self.throughput {
             Some(Throughput::Bytes(n))
@@ -276,6 +367,8 @@ pub(crate) trait Report {
     fn warmup(&self, _id: &BenchmarkId, _context: &ReportContext, _warmup_ns: f64) {}
     fn terminated(&self, _id: &BenchmarkId, _context: &ReportContext) {}
     fn analysis(&self, _id: &BenchmarkId, _context: &ReportContext) {}
+    /// Called when a benchmark panics (or its isolated process fails) instead of completing.
+    fn failed(&self, _id: &BenchmarkId, _context: &ReportContext, _message: &str) {}
     fn measurement_start(
         &self,
         _id: &BenchmarkId,
@@ -340,6 +433,7 @@ impl Report for Reports {
     reports_impl!(fn warmup(&self, id: &BenchmarkId, context: &ReportContext, warmup_ns: f64));
     reports_impl!(fn terminated(&self, id: &BenchmarkId, context: &ReportContext));
     reports_impl!(fn analysis(&self, id: &BenchmarkId, context: &ReportContext));
+    reports_impl!(fn failed(&self, id: &BenchmarkId, context: &ReportContext, message: &str));
     reports_impl!(fn measurement_start(
         &self,
         id: &BenchmarkId,
@@ -478,6 +572,137 @@ impl CliReport {
         print(him, "high mild");
         print(his, "high severe");
     }
+
+    fn comparison(&self, comp: &ComparisonData, throughput: bool) {
+        let different_mean = comp.p_value < comp.significance_threshold;
+        let change_est = comp.change();
+        let point_estimate = change_est.point_estimate;
+        let mut point_estimate_str = format::change(point_estimate, true);
+        // The change in throughput is related to the change in timing. Reducing the timing by
+        // 50% increases the throughput by 100%.
+        let to_thrpt_estimate = |ratio: f64| 1.0 / (1.0 + ratio) - 1.0;
+        let mut thrpt_point_estimate_str = format::change(to_thrpt_estimate(point_estimate), true);
+        let explanation_str: String;
+
+        if !different_mean {
+            explanation_str = "No change in performance detected.".to_owned();
+        } else {
+            let comparison = compare_to_threshold(change_est, comp.noise_threshold);
+            match comparison {
+                ComparisonResult::Improved => {
+                    point_estimate_str = self.green(&self.bold(point_estimate_str));
+                    thrpt_point_estimate_str = self.green(&self.bold(thrpt_point_estimate_str));
+                    explanation_str = format!("Performance has {}.", self.green("improved"));
+                }
+                ComparisonResult::Regressed => {
+                    point_estimate_str = self.red(&self.bold(point_estimate_str));
+                    thrpt_point_estimate_str = self.red(&self.bold(thrpt_point_estimate_str));
+                    explanation_str = format!("Performance has {}.", self.red("regressed"));
+                }
+                ComparisonResult::NonSignificant => {
+                    explanation_str = "Change within noise threshold.".to_owned();
+                }
+            }
+        }
+
+        if throughput {
+            println!("{}change:", " ".repeat(17));
+
+            println!(
+                "{}time:   [{} {} {}] (p = {:.2} {} {:.2}{})",
+                " ".repeat(24),
+                self.faint(format::change(
+                    change_est.confidence_interval.lower_bound,
+                    true
+                )),
+                point_estimate_str,
+                self.faint(format::change(
+                    change_est.confidence_interval.upper_bound,
+                    true
+                )),
+                comp.p_value,
+                if different_mean { "<" } else { ">" },
+                comp.significance_threshold,
+                comp.test_suffix()
+            );
+            println!(
+                "{}thrpt:  [{} {} {}]",
+                " ".repeat(24),
+                self.faint(format::change(
+                    to_thrpt_estimate(change_est.confidence_interval.upper_bound),
+                    true
+                )),
+                thrpt_point_estimate_str,
+                self.faint(format::change(
+                    to_thrpt_estimate(change_est.confidence_interval.lower_bound),
+                    true
+                )),
+            );
+        } else {
+            println!(
+                "{}change: [{} {} {}] (p = {:.2} {} {:.2}{})",
+                " ".repeat(24),
+                self.faint(format::change(
+                    change_est.confidence_interval.lower_bound,
+                    true
+                )),
+                point_estimate_str,
+                self.faint(format::change(
+                    change_est.confidence_interval.upper_bound,
+                    true
+                )),
+                comp.p_value,
+                if different_mean { "<" } else { ">" },
+                comp.significance_threshold,
+                comp.test_suffix()
+            );
+        }
+
+        println!("{}{}", " ".repeat(24), explanation_str);
+    }
+
+    fn metric(&self, metric: &MetricData<'_>) {
+        let meas = &metric.measurements;
+        let typical_estimate = &meas.absolute_estimates.typical();
+        println!(
+            "{}{}: [{} {} {}]",
+            " ".repeat(24),
+            metric.name,
+            self.faint(
+                metric
+                    .formatter
+                    .format_value(typical_estimate.confidence_interval.lower_bound)
+            ),
+            self.bold(
+                metric
+                    .formatter
+                    .format_value(typical_estimate.point_estimate)
+            ),
+            self.faint(
+                metric
+                    .formatter
+                    .format_value(typical_estimate.confidence_interval.upper_bound)
+            )
+        );
+
+        if let Some(overhead) = meas.overhead {
+            self.overhead(overhead, metric.formatter);
+        }
+
+        if !matches!(self.verbosity, CliVerbosity::Quiet) {
+            if let Some(ref comp) = meas.comparison {
+                self.comparison(comp, false);
+            }
+        }
+    }
+
+    fn overhead(&self, overhead: f64, formatter: &dyn ValueFormatter) {
+        println!(
+            "{}overhead: {} per iteration (subtracted)",
+            " ".repeat(24),
+            self.faint(formatter.format_value(overhead).trim().to_owned()),
+        );
+    }
 }
 impl Report for CliReport {
     fn test_start(&self, id: &BenchmarkId, _: &ReportContext) {
@@ -491,6 +716,11 @@ impl Report for CliReport {
         self.print_overwritable(format!("Benchmarking {}", id));
     }
 
+    fn failed(&self, id: &BenchmarkId, _: &ReportContext, message: &str) {
+        self.text_overwrite();
+        println!("{} {}", self.red(&format!("{} failed:", id)), message);
+    }
+
     fn profile(&self, id: &BenchmarkId, _: &ReportContext, warmup_ns: f64) {
         self.text_overwrite();
         self.print_overwritable(format!(
@@ -593,98 +823,48 @@ impl Report for CliReport {
             )
         }
 
-        if !matches!(self.verbosity, CliVerbosity::Quiet) {
-            if let Some(ref comp) = meas.comparison {
-                let different_mean = comp.p_value < comp.significance_threshold;
-                let mean_est = &comp.relative_estimates.mean;
-                let point_estimate = mean_est.point_estimate;
-                let mut point_estimate_str = format::change(point_estimate, true);
-                // The change in throughput is related to the change in timing. Reducing the timing by
-                // 50% increases the throughput by 100%.
-                let to_thrpt_estimate = |ratio: f64| 1.0 / (1.0 + ratio) - 1.0;
-                let mut thrpt_point_estimate_str =
-                    format::change(to_thrpt_estimate(point_estimate), true);
-                let explanation_str: String;
-
-                if !different_mean {
-                    explanation_str = "No change in performance detected.".to_owned();
-                } else {
-                    let comparison = compare_to_threshold(mean_est, comp.noise_threshold);
-                    match comparison {
-                        ComparisonResult::Improved => {
-                            point_estimate_str = self.green(&self.bold(point_estimate_str));
-                            thrpt_point_estimate_str =
-                                self.green(&self.bold(thrpt_point_estimate_str));
-                            explanation_str =
-                                format!("Performance has {}.", self.green("improved"));
-                        }
-                        ComparisonResult::Regressed => {
-                            point_estimate_str = self.red(&self.bold(point_estimate_str));
-                            thrpt_point_estimate_str =
-                                self.red(&self.bold(thrpt_point_estimate_str));
-                            explanation_str = format!("Performance has {}.", self.red("regressed"));
-                        }
-                        ComparisonResult::NonSignificant => {
-                            explanation_str = "Change within noise threshold.".to_owned();
-                        }
-                    }
-                }
+        if let Some(overhead) = meas.overhead {
+            self.overhead(overhead, formatter);
+        }
 
-                if meas.throughput.is_some() {
-                    println!("{}change:", " ".repeat(17));
-
-                    println!(
-                        "{}time:   [{} {} {}] (p = {:.2} {} {:.2})",
-                        " ".repeat(24),
-                        self.faint(format::change(
-                            mean_est.confidence_interval.lower_bound,
-                            true
-                        )),
-                        point_estimate_str,
-                        self.faint(format::change(
-                            mean_est.confidence_interval.upper_bound,
-                            true
-                        )),
-                        comp.p_value,
-                        if different_mean { "<" } else { ">" },
-                        comp.significance_threshold
-                    );
-                    println!(
-                        "{}thrpt:  [{} {} {}]",
-                        " ".repeat(24),
-                        self.faint(format::change(
-                            to_thrpt_estimate(mean_est.confidence_interval.upper_bound),
-                            true
-                        )),
-                        thrpt_point_estimate_str,
-                        self.faint(format::change(
-                            to_thrpt_estimate(mean_est.confidence_interval.lower_bound),
-                            true
-                        )),
-                    );
-                } else {
-                    println!(
-                        "{}change: [{} {} {}] (p = {:.2} {} {:.2})",
-                        " ".repeat(24),
-                        self.faint(format::change(
-                            mean_est.confidence_interval.lower_bound,
-                            true
-                        )),
-                        point_estimate_str,
-                        self.faint(format::change(
-                            mean_est.confidence_interval.upper_bound,
-                            true
-                        )),
-                        comp.p_value,
-                        if different_mean { "<" } else { ">" },
-                        comp.significance_threshold
-                    );
-                }
+        if let Some(percentiles) = &meas.absolute_estimates.percentiles {
+            for (label, estimate) in percentiles.labeled().iter() {
+                println!(
+                    "{}{:<8}[{} {} {}]",
+                    " ".repeat(24),
+                    format!("{}:", label),
+                    self.faint(formatter.format_value(estimate.confidence_interval.lower_bound)),
+                    self.bold(formatter.format_value(estimate.point_estimate)),
+                    self.faint(formatter.format_value(estimate.confidence_interval.upper_bound)),
+                );
+            }
+        }
 
-                println!("{}{}", " ".repeat(24), explanation_str);
+        if let Some(repetitions) = &meas.repetitions {
+            let spread = &repetitions.spread;
+            let format = |value: f64| formatter.format_value(value).trim().to_owned();
+            println!(
+                "{}repetitions: {} runs, mean {} ± {} (CV {:.2}%), range [{} {}]",
+                " ".repeat(24),
+                repetitions.estimates.len(),
+                self.bold(format(spread.mean)),
+                format(spread.std_dev),
+                spread.coefficient_of_variation() * 100.0,
+                self.faint(format(spread.min)),
+                self.faint(format(spread.max)),
+            );
+        }
+
+        if !matches!(self.verbosity, CliVerbosity::Quiet) {
+            if let Some(ref comp) = meas.comparison {
+                self.comparison(comp, meas.throughput.is_some());
             }
         }
 
+        for metric in &meas.metrics {
+            self.metric(metric);
+        }
+
         if !matches!(self.verbosity, CliVerbosity::Quiet) {
             self.outliers(&meas.avg_times);
         }
@@ -723,6 +903,15 @@ impl Report for CliReport {
                 "med. abs. dev.",
                 format_short_estimate(&meas.absolute_estimates.median_abs_dev),
             );
+            if let Some(warm_up) = meas.warm_up {
+                println!(
+                    "{:<7}[{}] {:<15}[{}]",
+                    "warmup",
+                    format::time(warm_up.length),
+                    "max. warmup",
+                    format::time(warm_up.max_length),
+                );
+            }
         }
     }
 
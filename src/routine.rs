@@ -1,17 +1,266 @@
 use crate::benchmark::BenchmarkConfig;
 use crate::connection::OutgoingMessage;
+use crate::latency::LatencyHistogram;
 use crate::measurement::Measurement;
 use crate::report::{BenchmarkId, Report, ReportContext};
-use crate::{black_box, ActualSamplingMode, Bencher, Criterion};
+use crate::stats::normal;
+use crate::stats::univariate::Sample;
+use crate::timeout;
+use crate::warm_up::{self, SteadyStateDetector, SteadyStateWarmUp};
+use crate::worker_pool::WorkerPool;
+use crate::{black_box, ActualSamplingMode, Bencher, Criterion, SamplingMode, WarmUpMode};
 use std::marker::PhantomData;
 use std::time::Duration;
 
+/// The samples collected for a benchmark, before any overhead is subtracted.
+pub(crate) struct Samples<V> {
+    pub(crate) sampling_mode: ActualSamplingMode,
+    pub(crate) iters: Vec<u64>,
+    pub(crate) values: Vec<V>,
+    /// The latencies of the individual iterations; empty unless they were recorded.
+    pub(crate) histogram: LatencyHistogram,
+    /// The length of the warm-up in nanoseconds, if it ran until a steady state was detected.
+    pub(crate) warm_up: Option<f64>,
+}
+
+/// The raw data collected by `Routine::sample`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Measurements {
+    pub(crate) sampling_mode: ActualSamplingMode,
+    pub(crate) iters: Box<[f64]>,
+    /// The values of the primary metric of the measurement.
+    pub(crate) times: Box<[f64]>,
+    /// The values of the secondary metrics of a composite measurement; entry `i` holds the values
+    /// of metric `i + 1`.
+    pub(crate) metrics: Vec<Box<[f64]>>,
+    /// The per-iteration overhead of the timing loop that was subtracted from each metric, or
+    /// `None` for metrics nothing was subtracted from. Empty unless the overhead was calibrated.
+    pub(crate) overhead: Vec<Option<f64>>,
+    /// The latencies of the individual iterations, if they were recorded.
+    pub(crate) latencies: Option<Latencies>,
+    /// The length of the warm-up in nanoseconds, if it ran until a steady state was detected.
+    pub(crate) warm_up: Option<f64>,
+}
+impl Measurements {
+    fn new<M: Measurement>(
+        measurement: &M,
+        sampling_mode: ActualSamplingMode,
+        iters: &[u64],
+        values: &[M::Value],
+    ) -> Measurements {
+        let metric = |index: usize| -> Box<[f64]> {
+            values
+                .iter()
+                .map(|value| measurement.metric_to_f64(value, index))
+                .collect()
+        };
+
+        Measurements {
+            sampling_mode,
+            iters: iters.iter().map(|&x| x as f64).collect(),
+            times: values
+                .iter()
+                .map(|value| measurement.to_f64(value))
+                .collect(),
+            metrics: (1..measurement.metric_count()).map(metric).collect(),
+            overhead: vec![],
+            latencies: None,
+            warm_up: None,
+        }
+    }
+
+    /// Subtract the per-iteration overhead of the timing loop from the samples. Entry `i` of
+    /// `overhead` is the overhead of metric `i`; missing entries count as zero.
+    fn subtract_overhead<M: Measurement>(
+        &mut self,
+        measurement: &M,
+        id: &BenchmarkId,
+        overhead: &[f64],
+    ) {
+        let iters = &self.iters;
+
+        self.overhead = std::iter::once(&mut self.times)
+            .chain(self.metrics.iter_mut())
+            .enumerate()
+            .map(|(index, values)| {
+                let per_iter = overhead.get(index).copied().unwrap_or(0.0);
+                if per_iter == 0.0 {
+                    // Nothing to subtract, eg. for allocations or with `iter_custom`
+                    return None;
+                }
+
+                let corrected: Box<[f64]> = values
+                    .iter()
+                    .zip(iters.iter())
+                    .map(|(value, iters)| value - per_iter * iters)
+                    .collect();
+                if corrected.iter().any(|&value| value <= 0.0) {
+                    eprintln!(
+                        "\nWarning: The timing loop overhead of {} ({}: {}) is as large as the \
+                        benchmark itself and was not subtracted.",
+                        id.as_title(),
+                        measurement.metric_name(index),
+                        measurement
+                            .metric_formatter(index)
+                            .format_value(per_iter)
+                            .trim(),
+                    );
+                    None
+                } else {
+                    *values = corrected;
+                    Some(per_iter)
+                }
+            })
+            .collect();
+    }
+}
+
+/// The latencies of the individual iterations of a benchmark sampled with
+/// `SamplingMode::PerIteration`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Latencies {
+    pub(crate) histogram: LatencyHistogram,
+    /// The overhead of starting and stopping the measurement, included in every recorded latency.
+    pub(crate) timer_overhead: f64,
+}
+
+/// Spread the overhead measured by timing an empty routine evenly over the iterations, for every
+/// metric of the measurement.
+fn overhead_per_iteration<M: Measurement>(
+    measurement: &M,
+    iters: &[u64],
+    overhead: &[M::Value],
+) -> Vec<f64> {
+    let total_iters = iters.iter().sum::<u64>() as f64;
+    (0..measurement.metric_count())
+        .map(|index| {
+            let total_overhead: f64 = overhead
+                .iter()
+                .map(|value| measurement.metric_to_f64(value, index))
+                .sum();
+            total_overhead / total_iters
+        })
+        .collect()
+}
+
+/// The median cost of starting and immediately stopping the measurement.
+fn timer_overhead<M: Measurement>(measurement: &M) -> f64 {
+    let values: Vec<f64> = (0..1000)
+        .map(|_| {
+            let start = measurement.start();
+            measurement.to_f64(&measurement.end(start))
+        })
+        .collect();
+    Sample::new(&values).percentiles().median()
+}
+
 /// PRIVATE
 pub(crate) trait Routine<M: Measurement, T: ?Sized> {
     /// PRIVATE
-    fn bench(&mut self, m: &M, iters: &[u64], parameter: &T) -> Vec<f64>;
+    fn bench(&mut self, m: &M, iters: &[u64], parameter: &T) -> Vec<M::Value>;
     /// PRIVATE
     fn warm_up(&mut self, m: &M, how_long: Duration, parameter: &T) -> (u64, u64);
+    /// Like `warm_up`, but runs until the time per iteration stops trending, for at least `min`
+    /// and at most `max`.
+    fn warm_up_until_steady(
+        &mut self,
+        m: &M,
+        min: Duration,
+        max: Duration,
+        parameter: &T,
+    ) -> SteadyStateWarmUp;
+    /// Like `bench`, but times an empty routine in place of the benchmarked one. With
+    /// `per_iteration`, every iteration is timed on its own, as `bench_latencies` does.
+    fn calibrate(
+        &mut self,
+        m: &M,
+        iters: &[u64],
+        parameter: &T,
+        per_iteration: bool,
+    ) -> Vec<M::Value>;
+    /// Like `bench`, but also records the latency of every iteration.
+    fn bench_latencies(
+        &mut self,
+        m: &M,
+        iters: &[u64],
+        parameter: &T,
+    ) -> (Vec<M::Value>, LatencyHistogram);
+    /// The number of threads the last call to `bench` ran the routine on, if it used
+    /// `iter_parallel`.
+    fn threads(&self) -> Option<usize>;
+
+    /// Runs `bench_latencies` in the per-iteration sampling mode and `bench` otherwise. The
+    /// histogram is empty unless latencies were recorded.
+    fn bench_samples(
+        &mut self,
+        m: &M,
+        iters: &[u64],
+        parameter: &T,
+        config: &BenchmarkConfig,
+    ) -> (Vec<M::Value>, LatencyHistogram) {
+        match config.sampling_mode {
+            SamplingMode::PerIteration => self.bench_latencies(m, iters, parameter),
+            _ => (self.bench(m, iters, parameter), LatencyHistogram::new()),
+        }
+    }
+
+    /// Collects samples in rounds, cycling through the planned iteration counts, until the
+    /// confidence interval of the mean is within `precision` of the mean or the maximum
+    /// measurement time is used up. The interval is estimated from the standard error of the
+    /// mean, which is much cheaper than bootstrapping it after every round.
+    fn sample_until_precise(
+        &mut self,
+        m: &M,
+        config: &BenchmarkConfig,
+        planned_iters: &[u64],
+        precision: f64,
+        parameter: &T,
+    ) -> (Vec<u64>, Vec<M::Value>, LatencyHistogram) {
+        const ROUND_SIZE: usize = 10;
+
+        let time_start = std::time::Instant::now();
+        let z = normal::quantile((1.0 + config.confidence_level) / 2.0);
+
+        let mut planned = planned_iters.iter().copied().cycle();
+        let mut iters = vec![];
+        let mut values = vec![];
+        let mut histogram = LatencyHistogram::new();
+        loop {
+            let round = planned.by_ref().take(ROUND_SIZE).collect::<Vec<_>>();
+            let (round_values, round_histogram) = self.bench_samples(m, &round, parameter, config);
+            iters.extend(round);
+            values.extend(round_values);
+            histogram.merge(&round_histogram);
+
+            let avg_times = iters
+                .iter()
+                .zip(values.iter())
+                .map(|(&n, value)| m.to_f64(value) / n as f64)
+                .collect::<Vec<_>>();
+            let avg_times = Sample::new(&avg_times);
+            let mean = avg_times.mean();
+            let half_width = z * avg_times.std_dev(Some(mean)) / (avg_times.len() as f64).sqrt();
+            let relative_half_width = if mean == 0.0 {
+                0.0
+            } else {
+                half_width / mean.abs()
+            };
+
+            if relative_half_width <= precision {
+                return (iters, values, histogram);
+            }
+            let elapsed = time_start.elapsed();
+            if elapsed >= config.max_measurement_time {
+                eprintln!(
+                    "\nWarning: Unable to reach a precision of ±{:.2}% in {:.1?}; the confidence interval of the mean is ±{:.2}%. You may wish to increase the maximum measurement time.",
+                    precision * 100.0,
+                    elapsed,
+                    relative_half_width * 100.0
+                );
+                return (iters, values, histogram);
+            }
+        }
+    }
 
     /// PRIVATE
     fn test(&mut self, m: &M, parameter: &T) {
@@ -87,7 +336,7 @@ pub(crate) trait Routine<M: Measurement, T: ?Sized> {
         criterion: &Criterion<M>,
         report_context: &ReportContext,
         parameter: &T,
-    ) -> (ActualSamplingMode, Box<[f64]>, Box<[f64]>) {
+    ) -> Measurements {
         if config.quick_mode {
             let minimum_bench_duration = Duration::from_millis(100);
             let maximum_bench_duration = config.measurement_time; // default: 5 seconds
@@ -98,21 +347,24 @@ pub(crate) trait Routine<M: Measurement, T: ?Sized> {
 
             let sq = |val| val * val;
             let mut n = 1;
-            let mut t_prev = *self.bench(measurement, &[n], parameter).first().unwrap();
+            let mut v_prev = self.bench(measurement, &[n], parameter).pop().unwrap();
+            let mut t_prev = measurement.to_f64(&v_prev);
 
             // Early exit for extremely long running benchmarks:
             if time_start.elapsed() > maximum_bench_duration {
-                let iters = vec![n as f64, n as f64].into_boxed_slice();
-                let elapsed = vec![t_prev, t_prev].into_boxed_slice();
-                return (ActualSamplingMode::Flat, iters, elapsed);
+                let v_copy = measurement.add(&v_prev, &measurement.zero());
+                return Measurements::new(
+                    measurement,
+                    ActualSamplingMode::Flat,
+                    &[n, n],
+                    &[v_prev, v_copy],
+                );
             }
 
             // Main data collection loop.
             loop {
-                let t_now = *self
-                    .bench(measurement, &[n * 2], parameter)
-                    .first()
-                    .unwrap();
+                let v_now = self.bench(measurement, &[n * 2], parameter).pop().unwrap();
+                let t_now = measurement.to_f64(&v_now);
                 let t = (t_prev + 2. * t_now) / 5.;
                 let stdev = (sq(t_prev - t) + sq(t_now - 2. * t)).sqrt();
                 // println!("Sample: {} {:.2}", n, stdev / t);
@@ -120,15 +372,67 @@ pub(crate) trait Routine<M: Measurement, T: ?Sized> {
                 if (stdev < target_rel_stdev * t && elapsed > minimum_bench_duration)
                     || elapsed > maximum_bench_duration
                 {
-                    let iters = vec![n as f64, (n * 2) as f64].into_boxed_slice();
-                    let elapsed = vec![t_prev, t_now].into_boxed_slice();
-                    return (ActualSamplingMode::Linear, iters, elapsed);
+                    return Measurements::new(
+                        measurement,
+                        ActualSamplingMode::Linear,
+                        &[n, n * 2],
+                        &[v_prev, v_now],
+                    );
                 }
                 n *= 2;
+                v_prev = v_now;
                 t_prev = t_now;
             }
         }
-        let wu = config.warm_up_time;
+        let planned = self.plan(
+            measurement,
+            id,
+            config,
+            criterion,
+            report_context,
+            parameter,
+        );
+
+        let (iters, values, histogram) = match config.target_precision {
+            Some(precision) => {
+                self.sample_until_precise(measurement, config, &planned.iters, precision, parameter)
+            }
+            None => {
+                let (values, histogram) =
+                    self.bench_samples(measurement, &planned.iters, parameter, config);
+                (planned.iters, values, histogram)
+            }
+        };
+        self.measurements(
+            measurement,
+            id,
+            config,
+            Samples {
+                iters,
+                values,
+                histogram,
+                ..planned
+            },
+            parameter,
+        )
+    }
+
+    /// Warms up the routine and plans the sampling mode and the iteration count of each sample.
+    /// The returned samples have no values yet.
+    fn plan(
+        &mut self,
+        measurement: &M,
+        id: &BenchmarkId,
+        config: &BenchmarkConfig,
+        criterion: &Criterion<M>,
+        report_context: &ReportContext,
+        parameter: &T,
+    ) -> Samples<M::Value> {
+        // The steady-state warm-up reports its maximum length
+        let wu = match config.warm_up_mode {
+            WarmUpMode::Fixed => config.warm_up_time,
+            WarmUpMode::SteadyState => config.max_warm_up_time.max(config.warm_up_time),
+        };
         let m_ns = config.measurement_time.as_nanos();
 
         criterion
@@ -143,7 +447,28 @@ pub(crate) trait Routine<M: Measurement, T: ?Sized> {
             .unwrap();
         }
 
-        let (wu_elapsed, wu_iters) = self.warm_up(measurement, wu, parameter);
+        let (wu_elapsed, wu_iters, warm_up) = match config.warm_up_mode {
+            WarmUpMode::Fixed => {
+                let (wu_elapsed, wu_iters) = self.warm_up(measurement, wu, parameter);
+                (wu_elapsed, wu_iters, None)
+            }
+            WarmUpMode::SteadyState => {
+                let warm_up =
+                    self.warm_up_until_steady(measurement, config.warm_up_time, wu, parameter);
+                if !warm_up.steady {
+                    eprintln!(
+                        "\nWarning: {} did not reach a steady state in {:.1?}. You may wish to increase the maximum warm-up time.",
+                        id.as_title(),
+                        warm_up.duration
+                    );
+                }
+                (
+                    warm_up.elapsed,
+                    warm_up.iters,
+                    Some(warm_up.duration.as_nanos() as f64),
+                )
+            }
+        };
         if crate::debug_enabled() {
             println!(
                 "\nCompleted {} iterations in {} nanoseconds, estimated execution time is {} ns",
@@ -162,7 +487,12 @@ pub(crate) trait Routine<M: Measurement, T: ?Sized> {
             .sampling_mode
             .choose_sampling_mode(met, n, m_ns as f64);
 
-        let m_iters = actual_sampling_mode.iteration_counts(met, n, &config.measurement_time);
+        let m_iters = actual_sampling_mode.iteration_counts(
+            met,
+            n,
+            &config.measurement_time,
+            config.time_budget_scale.is_some(),
+        );
 
         let expected_ns = m_iters
             .iter()
@@ -190,15 +520,57 @@ pub(crate) trait Routine<M: Measurement, T: ?Sized> {
             .unwrap();
         }
 
-        let m_elapsed = self.bench(measurement, &m_iters, parameter);
+        Samples {
+            sampling_mode: actual_sampling_mode,
+            iters: m_iters,
+            values: vec![],
+            histogram: LatencyHistogram::new(),
+            warm_up,
+        }
+    }
 
-        let m_iters_f: Vec<f64> = m_iters.iter().map(|&x| x as f64).collect();
+    /// Turns the collected samples into `Measurements`, subtracting the overhead of the timing
+    /// loop if it was calibrated or if the latencies of the individual iterations were recorded.
+    fn measurements(
+        &mut self,
+        measurement: &M,
+        id: &BenchmarkId,
+        config: &BenchmarkConfig,
+        samples: Samples<M::Value>,
+        parameter: &T,
+    ) -> Measurements {
+        let Samples {
+            sampling_mode,
+            iters,
+            values,
+            histogram,
+            warm_up,
+        } = samples;
+
+        // iter_custom and iter_parallel don't time individual iterations
+        let latencies = if histogram.is_empty() {
+            None
+        } else {
+            Some(Latencies {
+                histogram,
+                timer_overhead: timer_overhead(measurement),
+            })
+        };
 
-        (
-            actual_sampling_mode,
-            m_iters_f.into_boxed_slice(),
-            m_elapsed.into_boxed_slice(),
-        )
+        let mut measurements = Measurements::new(measurement, sampling_mode, &iters, &values);
+        if config.calibrate_overhead {
+            // Calibrate with the same timing loop, so the overhead includes that of the timer
+            let per_iteration = latencies.is_some();
+            let overhead = self.calibrate(measurement, &iters, parameter, per_iteration);
+            let overhead = overhead_per_iteration(measurement, &iters, &overhead);
+            measurements.subtract_overhead(measurement, id, &overhead);
+        } else if let Some(latencies) = &latencies {
+            // Timing every iteration adds the timer overhead to each of them
+            measurements.subtract_overhead(measurement, id, &[latencies.timer_overhead]);
+        }
+        measurements.latencies = latencies;
+        measurements.warm_up = warm_up;
+        measurements
     }
 }
 
@@ -208,6 +580,9 @@ where
     T: ?Sized,
 {
     f: F,
+    threads: Option<usize>,
+    // The threads of `iter_parallel`, kept for all samples of the benchmark
+    workers: Option<WorkerPool>,
     // TODO: Is there some way to remove these?
     _phantom: PhantomData<T>,
     _phamtom2: PhantomData<M>,
@@ -220,18 +595,21 @@ where
     pub fn new(f: F) -> Function<M, F, T> {
         Function {
             f,
+            threads: None,
+            workers: None,
             _phantom: PhantomData,
             _phamtom2: PhantomData,
         }
     }
-}
 
-impl<M: Measurement, F, T> Routine<M, T> for Function<M, F, T>
-where
-    F: FnMut(&mut Bencher<'_, M>, &T),
-    T: ?Sized,
-{
-    fn bench(&mut self, m: &M, iters: &[u64], parameter: &T) -> Vec<f64> {
+    fn run(
+        &mut self,
+        m: &M,
+        iters: &[u64],
+        parameter: &T,
+        calibrating: bool,
+        latencies: Option<LatencyHistogram>,
+    ) -> (Vec<M::Value>, Option<LatencyHistogram>) {
         let f = &mut self.f;
 
         let mut b = Bencher {
@@ -240,17 +618,68 @@ where
             value: m.zero(),
             measurement: m,
             elapsed_time: Duration::from_millis(0),
+            calibrating,
+            threads: None,
+            workers: self.workers.take(),
+            latencies,
+            eviction_buffer: None,
         };
 
-        iters
+        let values = iters
             .iter()
             .map(|iters| {
                 b.iters = *iters;
                 (*f)(&mut b, black_box(parameter));
                 b.assert_iterated();
-                m.to_f64(&b.value)
+                if !calibrating {
+                    timeout::check_sample();
+                }
+                std::mem::replace(&mut b.value, m.zero())
             })
-            .collect()
+            .collect();
+        self.threads = b.threads;
+        self.workers = b.workers.take();
+        (values, b.latencies)
+    }
+}
+
+impl<M: Measurement, F, T> Routine<M, T> for Function<M, F, T>
+where
+    F: FnMut(&mut Bencher<'_, M>, &T),
+    T: ?Sized,
+{
+    fn bench(&mut self, m: &M, iters: &[u64], parameter: &T) -> Vec<M::Value> {
+        self.run(m, iters, parameter, false, None).0
+    }
+
+    fn calibrate(
+        &mut self,
+        m: &M,
+        iters: &[u64],
+        parameter: &T,
+        per_iteration: bool,
+    ) -> Vec<M::Value> {
+        let latencies = if per_iteration {
+            Some(LatencyHistogram::new())
+        } else {
+            None
+        };
+        self.run(m, iters, parameter, true, latencies).0
+    }
+
+    fn bench_latencies(
+        &mut self,
+        m: &M,
+        iters: &[u64],
+        parameter: &T,
+    ) -> (Vec<M::Value>, LatencyHistogram) {
+        let (values, latencies) =
+            self.run(m, iters, parameter, false, Some(LatencyHistogram::new()));
+        (values, latencies.unwrap_or_default())
+    }
+
+    fn threads(&self) -> Option<usize> {
+        self.threads
     }
 
     fn warm_up(&mut self, m: &M, how_long: Duration, parameter: &T) -> (u64, u64) {
@@ -261,6 +690,11 @@ where
             value: m.zero(),
             measurement: m,
             elapsed_time: Duration::from_millis(0),
+            calibrating: false,
+            threads: None,
+            workers: self.workers.take(),
+            latencies: None,
+            eviction_buffer: None,
         };
 
         let mut total_iters = 0;
@@ -272,11 +706,73 @@ where
 
             total_iters += b.iters;
             elapsed_time += b.elapsed_time;
+            timeout::check_warm_up(total_iters);
             if elapsed_time > how_long {
+                self.workers = b.workers.take();
                 return (elapsed_time.as_nanos() as u64, total_iters);
             }
 
             b.iters = b.iters.wrapping_mul(2);
         }
     }
+
+    fn warm_up_until_steady(
+        &mut self,
+        m: &M,
+        min: Duration,
+        max: Duration,
+        parameter: &T,
+    ) -> SteadyStateWarmUp {
+        let f = &mut self.f;
+        let mut b = Bencher {
+            iterated: false,
+            iters: 1,
+            value: m.zero(),
+            measurement: m,
+            elapsed_time: Duration::from_millis(0),
+            calibrating: false,
+            threads: None,
+            workers: self.workers.take(),
+            latencies: None,
+            eviction_buffer: None,
+        };
+
+        let mut detector = SteadyStateDetector::new();
+        let mut total_iters = 0;
+        let mut elapsed_time = Duration::from_millis(0);
+        loop {
+            (*f)(&mut b, black_box(parameter));
+
+            b.assert_iterated();
+
+            total_iters += b.iters;
+            elapsed_time += b.elapsed_time;
+            timeout::check_warm_up(total_iters);
+            // Batches too short to time reliably are left out of the window, and the iteration
+            // count keeps doubling until they are long enough
+            if b.elapsed_time >= warm_up::MIN_BATCH_TIME {
+                detector.push(b.elapsed_time, b.iters);
+            } else {
+                b.iters = b.iters.wrapping_mul(2);
+            }
+
+            let steady = elapsed_time >= min && detector.is_steady();
+            if steady || elapsed_time >= max {
+                // Estimate the execution time from the warm batches only, if there are any
+                let (window_time, window_iters) = detector.totals();
+                let (elapsed, iters) = if window_iters > 0 {
+                    (window_time, window_iters)
+                } else {
+                    (elapsed_time, total_iters)
+                };
+                self.workers = b.workers.take();
+                return SteadyStateWarmUp {
+                    elapsed: elapsed.as_nanos() as u64,
+                    iters,
+                    duration: elapsed_time,
+                    steady,
+                };
+            }
+        }
+    }
 }
@@ -0,0 +1,158 @@
+//! Pinning the measurement thread to a CPU and changing its scheduling priority.
+//!
+//! Both are only supported on Linux. The settings apply to the thread that collects the samples
+//! and are restored when the returned guard is dropped.
+
+use crate::Priority;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+// Failures are reported once per process rather than once per benchmark
+fn warn(message: &str) {
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        eprintln!("\nWarning: {}", message);
+    }
+}
+
+/// Restores the previous CPU affinity and priority of the current thread when dropped.
+#[derive(Default)]
+pub(crate) struct SchedulingGuard {
+    #[cfg(target_os = "linux")]
+    affinity: Option<libc::cpu_set_t>,
+    #[cfg(target_os = "linux")]
+    nice: Option<libc::c_int>,
+    #[cfg(target_os = "linux")]
+    policy: Option<(libc::c_int, libc::sched_param)>,
+}
+
+/// Pins the current thread to the given CPU and changes its priority, if requested.
+#[cfg(target_os = "linux")]
+pub(crate) fn apply(cpu: Option<usize>, priority: Option<Priority>) -> SchedulingGuard {
+    let mut guard = SchedulingGuard::default();
+
+    if let Some(cpu) = cpu {
+        guard.affinity = pin_to_cpu(cpu)
+            .map_err(|err| {
+                warn(&format!(
+                    "Failed to pin the benchmark to CPU {}: {}",
+                    cpu, err
+                ))
+            })
+            .ok();
+    }
+
+    match priority {
+        Some(Priority::Nice(nice)) => {
+            guard.nice = set_nice(nice)
+                .map_err(|err| {
+                    warn(&format!(
+                        "Failed to set the nice value to {}: {}",
+                        nice, err
+                    ))
+                })
+                .ok();
+        }
+        Some(Priority::RealTime(priority)) => {
+            guard.policy = set_real_time(priority)
+                .map_err(|err| {
+                    warn(&format!(
+                        "Failed to set the real-time priority to {}: {}",
+                        priority, err
+                    ))
+                })
+                .ok();
+        }
+        None => {}
+    }
+
+    guard
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply(cpu: Option<usize>, priority: Option<Priority>) -> SchedulingGuard {
+    if cpu.is_some() || priority.is_some() {
+        warn("CPU pinning and scheduling priorities are only supported on Linux.");
+    }
+    SchedulingGuard::default()
+}
+
+#[cfg(target_os = "linux")]
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    if result == -1 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn thread_id() -> libc::id_t {
+    unsafe { libc::syscall(libc::SYS_gettid) as libc::id_t }
+}
+
+// Returns the previous affinity
+#[cfg(target_os = "linux")]
+fn pin_to_cpu(cpu: usize) -> std::io::Result<libc::cpu_set_t> {
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if cpu >= size * 8 {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    }
+    unsafe {
+        let mut previous: libc::cpu_set_t = std::mem::zeroed();
+        check(libc::sched_getaffinity(0, size, &mut previous))?;
+
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        check(libc::sched_setaffinity(0, size, &set))?;
+        Ok(previous)
+    }
+}
+
+// Returns the previous nice value
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> std::io::Result<libc::c_int> {
+    // On Linux, the nice value is a per-thread attribute
+    let tid = thread_id();
+    unsafe {
+        let previous = libc::getpriority(libc::PRIO_PROCESS, tid);
+        check(libc::setpriority(libc::PRIO_PROCESS, tid, nice))?;
+        Ok(previous)
+    }
+}
+
+// Returns the previous scheduling policy and parameters
+#[cfg(target_os = "linux")]
+fn set_real_time(priority: i32) -> std::io::Result<(libc::c_int, libc::sched_param)> {
+    unsafe {
+        let policy = libc::sched_getscheduler(0);
+        check(policy)?;
+        let mut previous: libc::sched_param = std::mem::zeroed();
+        check(libc::sched_getparam(0, &mut previous))?;
+
+        let param = libc::sched_param {
+            sched_priority: priority,
+        };
+        check(libc::sched_setscheduler(0, libc::SCHED_FIFO, &param))?;
+        Ok((policy, previous))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SchedulingGuard {
+    fn drop(&mut self) {
+        // Raising the priority back up may need privileges the process doesn't have, in which case
+        // the thread keeps the lower priority.
+        unsafe {
+            if let Some((policy, param)) = &self.policy {
+                libc::sched_setscheduler(0, *policy, param);
+            }
+            if let Some(nice) = self.nice {
+                libc::setpriority(libc::PRIO_PROCESS, thread_id(), nice);
+            }
+            if let Some(affinity) = &self.affinity {
+                libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), affinity);
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Splitting the benchmark suite between several machines, and merging their results.
+//!
+//! With `--shard INDEX/COUNT`, only the benchmarks whose IDs hash to that shard run. The IDs are
+//! hashed with FNV-1a rather than the standard library's hasher, whose output may change between
+//! Rust versions, so that every machine agrees on the partition. Afterwards, `--merge-shards`
+//! copies the output directories of all shards into one tree.
+
+use crate::error::Result;
+use crate::fs;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// One of several shards of the benchmark suite, numbered from 1.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Shard {
+    index: usize,
+    count: usize,
+}
+impl Shard {
+    /// Parses a shard given as `INDEX/COUNT`.
+    pub(crate) fn parse(shard: &str) -> Option<Shard> {
+        let (index, count) = shard.split_once('/')?;
+        Shard::new(index.trim().parse().ok()?, count.trim().parse().ok()?)
+    }
+
+    /// Returns `None` unless `1 <= index <= count`.
+    pub(crate) fn new(index: usize, count: usize) -> Option<Shard> {
+        if index >= 1 && index <= count {
+            Some(Shard { index, count })
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the benchmark with the given ID belongs to this shard.
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        fnv1a(id) % self.count as u64 == (self.index - 1) as u64
+    }
+}
+
+fn fnv1a(id: &str) -> u64 {
+    id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Copies the results of the shards into the output directory, and returns the IDs of the groups
+/// whose benchmarks were spread over several shards. The summary of such a group only covers the
+/// benchmarks of one shard, so it has to be generated again.
+pub(crate) fn merge<P: AsRef<Path>>(shards: &[P], output_directory: &Path) -> Result<Vec<String>> {
+    let mut shards_per_group = HashMap::<String, usize>::new();
+    for shard in shards {
+        let shard = shard.as_ref();
+        if !fs::is_dir(&shard) {
+            eprintln!(
+                "\nWarning: The shard directory {:?} does not exist; skipping it.",
+                shard
+            );
+            continue;
+        }
+
+        let groups = fs::list_existing_benchmarks(&shard)?
+            .into_iter()
+            .map(|id| id.group_id)
+            .collect::<HashSet<_>>();
+        for group in groups {
+            *shards_per_group.entry(group).or_insert(0) += 1;
+        }
+
+        for entry in WalkDir::new(shard)
+            .into_iter()
+            // Ignore errors.
+            .filter_map(::std::result::Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+        {
+            let relative = entry.path().strip_prefix(shard).unwrap();
+            // The index of every shard is replaced by the index of the merged results
+            if relative.starts_with("report") {
+                continue;
+            }
+            let destination = output_directory.join(relative);
+            fs::mkdirp(&destination.parent().unwrap())?;
+            fs::cp(entry.path(), &destination)?;
+        }
+    }
+
+    Ok(shards_per_group
+        .into_iter()
+        .filter(|&(_, shards)| shards > 1)
+        .map(|(group, _)| group)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let shard = Shard::parse("2/4").unwrap();
+        assert_eq!((shard.index, shard.count), (2, 4));
+        assert!(Shard::parse("0/4").is_none());
+        assert!(Shard::parse("5/4").is_none());
+        assert!(Shard::parse("2").is_none());
+    }
+
+    #[test]
+    fn test_every_id_in_one_shard() {
+        let shards = (1..=3)
+            .map(|index| Shard::new(index, 3).unwrap())
+            .collect::<Vec<_>>();
+        for i in 0..100 {
+            let id = format!("group/function/{}", i);
+            let count = shards.iter().filter(|shard| shard.contains(&id)).count();
+            assert_eq!(count, 1);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Running the benchmarks in a random order, with `--shuffle[=SEED]`.
+//!
+//! The benchmark functions passed to `criterion_main!`, and the targets of each `criterion_group!`,
+//! are called once each, in random order. Benchmark groups run their benchmarks right away, so
+//! only the benchmarks which are collected until they are finished can be shuffled: those of a
+//! `ShuffledGroup` and of a `FixtureGroup`, and the groups of a `ShuffledGroups`. An interleaved
+//! group runs as a whole when it is finished.
+//!
+//! All random decisions are taken from a single generator, seeded with the seed of the run, so
+//! that the same seed repeats the same order.
+
+use crate::fs;
+use crate::isolation;
+use crate::stats::rand_util::{self, Rng};
+use crate::Criterion;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+/// The state of a shuffled run, or `None` while the benchmarks run in order.
+static STATE: Lazy<Mutex<Option<State>>> = Lazy::new(|| Mutex::new(None));
+
+struct State {
+    seed: u64,
+    rng: Rng,
+    /// The IDs of the benchmarks which ran so far, in the order they ran in.
+    order: Vec<String>,
+}
+impl State {
+    fn new(seed: u64) -> State {
+        State {
+            seed,
+            rng: Rng::new(u128::from(seed)),
+            order: vec![],
+        }
+    }
+}
+
+/// The seed and the order of a shuffled run, saved as `shuffle.json` in the output directory.
+#[derive(Serialize)]
+struct ShuffleRecord {
+    seed: u64,
+    order: Vec<String>,
+}
+
+/// Runs the benchmark functions, in random order if `--shuffle` was given.
+pub(crate) fn run(functions: &[&dyn Fn()]) {
+    // The runner is dropped before the benchmarks run, so that it releases the connection to
+    // cargo-criterion
+    let (seed, listing, benchmarking, connected, output_directory) = {
+        let criterion = Criterion::default().configure_from_args();
+        (
+            criterion.shuffle,
+            criterion.mode.is_list(),
+            criterion.mode.is_benchmark(),
+            criterion.connection.is_some(),
+            criterion.output_directory.clone(),
+        )
+    };
+
+    // Isolated child processes run a single benchmark anyway
+    let seed = match seed {
+        Some(_) if connected => {
+            eprintln!("\nWarning: --shuffle is not supported under cargo-criterion; running the benchmarks in order.");
+            None
+        }
+        Some(seed) if !listing && isolation::child_benchmark_id().is_none() => Some(seed),
+        _ => None,
+    };
+    let seed = match seed {
+        Some(seed) => seed,
+        None => {
+            for function in functions {
+                function();
+            }
+            return;
+        }
+    };
+
+    eprintln!(
+        "\nRunning the benchmarks in random order with seed {0}; use --shuffle={0} to repeat it.",
+        seed
+    );
+    *STATE.lock().unwrap() = Some(State::new(seed));
+    for index in order(functions.len()) {
+        functions[index]();
+    }
+
+    let state = STATE.lock().unwrap().take();
+    if let (Some(state), true) = (state, benchmarking) {
+        let record = ShuffleRecord {
+            seed: state.seed,
+            order: state.order,
+        };
+        log_if_err!(fs::mkdirp(&output_directory));
+        log_if_err!(fs::save(&record, &output_directory.join("shuffle.json")));
+    }
+}
+
+/// Returns true if the benchmarks are shuffled, so that a fixture group has to collect its
+/// benchmarks and run them when it is finished.
+pub(crate) fn is_active() -> bool {
+    STATE.lock().unwrap().is_some()
+}
+
+/// Shuffles the benchmarks or groups collected by a shuffled group, if the benchmarks are
+/// shuffled.
+pub(crate) fn shuffle<T>(items: &mut [T]) {
+    if let Some(state) = &mut *STATE.lock().unwrap() {
+        rand_util::shuffle(&mut state.rng, items);
+    }
+}
+
+/// Returns the order to call `len` functions in: random if the benchmarks are shuffled, and in
+/// order otherwise.
+pub(crate) fn order(len: usize) -> Vec<usize> {
+    let mut order = (0..len).collect::<Vec<_>>();
+    shuffle(&mut order);
+    order
+}
+
+/// Records that the benchmark with the given ID runs now, if the benchmarks are shuffled.
+pub(crate) fn record(id: &str) {
+    if let Some(state) = &mut *STATE.lock().unwrap() {
+        state.order.push(id.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Shuffles the function order and the benchmarks of three groups, like a shuffled run
+    fn shuffled_run(seed: u64) -> Vec<Vec<usize>> {
+        let mut state = State::new(seed);
+        (0..4)
+            .map(|_| {
+                let mut order = (0..5).collect::<Vec<_>>();
+                rand_util::shuffle(&mut state.rng, &mut order);
+                order
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_order() {
+        assert_eq!(shuffled_run(42), shuffled_run(42));
+        assert_ne!(shuffled_run(42), shuffled_run(43));
+    }
+
+    #[test]
+    fn test_groups_are_shuffled_independently() {
+        let run = shuffled_run(7);
+        assert!(run.windows(2).any(|pair| pair[0] != pair[1]));
+        for order in &run {
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..5).collect::<Vec<_>>());
+        }
+    }
+}
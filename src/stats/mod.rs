@@ -9,11 +9,12 @@
 mod test;
 
 pub mod bivariate;
+pub mod normal;
 pub mod tuple;
 pub mod univariate;
 
 mod float;
-mod rand_util;
+pub(crate) mod rand_util;
 
 use std::mem;
 use std::ops::Deref;
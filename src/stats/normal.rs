@@ -0,0 +1,56 @@
+//! The standard normal distribution
+
+/// The cumulative distribution function of the standard normal distribution
+///
+/// Uses formula 7.1.26 of Abramowitz and Stegun; the absolute error is below 1.5e-7.
+pub fn cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - polynomial * (-z * z).exp();
+
+    if x >= 0.0 {
+        (1.0 + erf) / 2.0
+    } else {
+        (1.0 - erf) / 2.0
+    }
+}
+
+/// The quantile function (inverse of the cumulative distribution function) of the standard
+/// normal distribution
+///
+/// # Panics
+///
+/// Panics if `p` is outside the `(0, 1)` range
+pub fn quantile(p: f64) -> f64 {
+    assert!(p > 0. && p < 1.);
+
+    // The CDF is monotonic, so bisection converges to the precision of the CDF
+    let (mut low, mut high) = (-10., 10.);
+    for _ in 0..64 {
+        let mid = (low + high) / 2.;
+        if cdf(mid) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cdf, quantile};
+
+    #[test]
+    fn quantile_inverts_cdf() {
+        assert!((quantile(0.975) - 1.959_964).abs() < 1e-5);
+        assert!((quantile(0.5)).abs() < 1e-6);
+        for &p in &[0.001, 0.05, 0.3, 0.9, 0.999] {
+            assert!((cdf(quantile(p)) - p).abs() < 1e-9);
+        }
+    }
+}
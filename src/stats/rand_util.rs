@@ -19,3 +19,11 @@ pub fn new_rng() -> Rng {
         Rand64::new(seed)
     })
 }
+
+/// Shuffles the slice in place (Fisher-Yates)
+pub fn shuffle<T>(rng: &mut Rng, slice: &mut [T]) {
+    for i in (1..slice.len()).rev() {
+        let j = rng.rand_range(0..(i as u64 + 1)) as usize;
+        slice.swap(i, j);
+    }
+}
@@ -33,7 +33,12 @@ where
             let &floor = self.0.get_unchecked(n);
             let &ceiling = self.0.get_unchecked(n + 1);
 
-            floor + (ceiling - floor) * fraction
+            if floor == ceiling {
+                // Also avoids `inf - inf` for infinite values
+                floor
+            } else {
+                floor + (ceiling - floor) * fraction
+            }
         }
     }
 
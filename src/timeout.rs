@@ -0,0 +1,216 @@
+//! Aborting benchmarks that run longer than their timeout.
+//!
+//! The deadline of the running benchmark is kept in a thread-local, which the warm-up and sampling
+//! loops check after every batch and sample. A benchmark past its deadline is unwound with a
+//! `TimedOut` payload, which `Criterion::catch_failure` reports as a failure like any other panic.
+//! A routine can't be interrupted in the middle of a sample, so a watchdog thread, shared by all
+//! benchmarks of the process, warns if a benchmark is still running well after its deadline.
+//! Benchmarks run in isolated processes are killed by the parent process instead.
+
+use crate::benchmark::BenchmarkConfig;
+use crate::isolation;
+use crate::report::BenchmarkId;
+use once_cell::sync::Lazy;
+use std::cell::Cell;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a benchmark may run past its timeout before the watchdog warns about it.
+const GRACE: Duration = Duration::from_secs(1);
+
+thread_local! {
+    static DEADLINE: Cell<Option<Deadline>> = const { Cell::new(None) };
+}
+
+/// The panic payload that unwinds a benchmark which timed out.
+pub(crate) struct TimedOut {
+    pub(crate) message: String,
+}
+
+#[derive(Clone, Copy)]
+struct Deadline {
+    timeout: Duration,
+    // The time used by earlier calls to `Timer::run`
+    used: Duration,
+    start: Instant,
+    samples: usize,
+}
+impl Deadline {
+    fn elapsed(&self) -> Duration {
+        self.used + self.start.elapsed()
+    }
+}
+
+/// Limits the time a benchmark spends warming up and sampling, which may be spread over several
+/// calls, eg. for interleaved benchmarks.
+pub(crate) struct Timer {
+    title: String,
+    timeout: Option<Duration>,
+    used: Duration,
+    samples: usize,
+}
+impl Timer {
+    pub(crate) fn new(id: &BenchmarkId, config: &BenchmarkConfig) -> Timer {
+        Timer {
+            title: id.as_title().to_owned(),
+            timeout: config.timeout,
+            used: Duration::from_secs(0),
+            samples: 0,
+        }
+    }
+
+    /// Runs part of the warm-up or sampling of the benchmark, which unwinds with a `TimedOut`
+    /// payload once the benchmark has used up its timeout.
+    pub(crate) fn run<R>(&mut self, run: impl FnOnce() -> R) -> R {
+        let timeout = match self.timeout {
+            // An isolated child process is killed by its parent instead
+            Some(timeout) if isolation::child_benchmark_id().is_none() => timeout,
+            _ => return run(),
+        };
+
+        let watch = Watch::new(&self.title, timeout, self.used);
+        DEADLINE.with(|deadline| {
+            deadline.set(Some(Deadline {
+                timeout,
+                used: self.used,
+                start: Instant::now(),
+                samples: self.samples,
+            }))
+        });
+        let _guard = Guard {
+            timer: self,
+            _watch: watch,
+        };
+        run()
+    }
+}
+
+// Clears the deadline and stops the watchdog when the benchmark returns or unwinds
+struct Guard<'a> {
+    timer: &'a mut Timer,
+    _watch: Watch,
+}
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        if let Some(deadline) = DEADLINE.with(Cell::take) {
+            self.timer.used = deadline.elapsed();
+            self.timer.samples = deadline.samples;
+        }
+    }
+}
+
+static WATCHDOG: Lazy<Watchdog> = Lazy::new(|| {
+    thread::spawn(|| WATCHDOG.run());
+    Watchdog {
+        watched: Mutex::new(vec![]),
+        changed: Condvar::new(),
+    }
+});
+static NEXT_KEY: AtomicUsize = AtomicUsize::new(0);
+
+// A benchmark watched by the watchdog
+struct Watched {
+    key: usize,
+    title: String,
+    timeout: Duration,
+    warn_at: Instant,
+}
+
+// Warns about every benchmark which is still running some time after its deadline, which means
+// that it is stuck in a single sample. The thread is started with the first benchmark that has a
+// timeout, and sleeps until the earliest deadline of the benchmarks running at that time.
+struct Watchdog {
+    watched: Mutex<Vec<Watched>>,
+    changed: Condvar,
+}
+impl Watchdog {
+    fn run(&self) -> ! {
+        let mut watched = self.watched.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            watched.retain(|watched| {
+                if watched.warn_at > now {
+                    return true;
+                }
+                eprintln!(
+                    "\nWarning: {} is still running {:?} after its timeout of {:?}, in a sample which can't be interrupted. It will be aborted once the sample ends; benchmarks run in isolated processes (--isolated) are killed instead.",
+                    watched.title, GRACE, watched.timeout
+                );
+                false
+            });
+            watched = match watched.iter().map(|watched| watched.warn_at).min() {
+                Some(warn_at) => {
+                    let wait = warn_at.saturating_duration_since(now);
+                    self.changed.wait_timeout(watched, wait).unwrap().0
+                }
+                None => self.changed.wait(watched).unwrap(),
+            };
+        }
+    }
+}
+
+// Watches a benchmark until it is dropped
+struct Watch {
+    key: usize,
+}
+impl Watch {
+    fn new(title: &str, timeout: Duration, used: Duration) -> Watch {
+        let key = NEXT_KEY.fetch_add(1, Ordering::Relaxed);
+        WATCHDOG.watched.lock().unwrap().push(Watched {
+            key,
+            title: title.to_owned(),
+            timeout,
+            warn_at: Instant::now() + timeout.saturating_sub(used) + GRACE,
+        });
+        // The new benchmark may be due before the one the watchdog is waiting for
+        WATCHDOG.changed.notify_one();
+        Watch { key }
+    }
+}
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let key = self.key;
+        WATCHDOG
+            .watched
+            .lock()
+            .unwrap()
+            .retain(|watched| watched.key != key);
+    }
+}
+
+/// Aborts the benchmark if it has used up its timeout during the warm-up, after running `iters`
+/// iterations.
+pub(crate) fn check_warm_up(iters: u64) {
+    if let Some(deadline) = DEADLINE.with(Cell::get) {
+        if deadline.elapsed() > deadline.timeout {
+            abort(format!(
+                "timed out after {:.1?} while warming up, after {} iterations",
+                deadline.elapsed(),
+                iters
+            ));
+        }
+    }
+}
+
+/// Counts a collected sample, and aborts the benchmark if it has used up its timeout.
+pub(crate) fn check_sample() {
+    if let Some(mut deadline) = DEADLINE.with(Cell::get) {
+        deadline.samples += 1;
+        DEADLINE.with(|cell| cell.set(Some(deadline)));
+        if deadline.elapsed() > deadline.timeout {
+            abort(format!(
+                "timed out after {:.1?} while sampling, after collecting {} samples",
+                deadline.elapsed(),
+                deadline.samples
+            ));
+        }
+    }
+}
+
+fn abort(message: String) -> ! {
+    // Unlike `panic!`, this doesn't print the message through the panic hook
+    panic::resume_unwind(Box::new(TimedOut { message }))
+}
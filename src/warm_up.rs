@@ -0,0 +1,159 @@
+//! Detecting when a benchmark has reached a steady state during the warm-up.
+//!
+//! The warm-up runs the routine in batches and keeps the time per iteration of the most recent
+//! batches in a sliding window. The routine is considered warm once the window no longer shows a
+//! significant trend, or once the trend across the window is negligible compared to the median.
+
+use crate::stats::normal;
+use crate::stats::univariate::Sample;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The number of batches in the sliding window.
+const WINDOW: usize = 20;
+
+/// The shortest batch that is added to the window. Shorter batches are mostly timer noise, so the
+/// warm-up doubles the iteration count until a batch takes at least this long.
+pub(crate) const MIN_BATCH_TIME: Duration = Duration::from_millis(1);
+
+/// A trend across the whole window smaller than this fraction of the median is ignored, even if it
+/// is statistically significant.
+const NEGLIGIBLE_DRIFT: f64 = 0.01;
+
+/// The result of a warm-up that ran until the routine reached a steady state.
+pub(crate) struct SteadyStateWarmUp {
+    /// The time taken by the batches in the final window, in nanoseconds.
+    pub(crate) elapsed: u64,
+    /// The number of iterations in the batches of the final window.
+    pub(crate) iters: u64,
+    /// The time taken by the whole warm-up.
+    pub(crate) duration: Duration,
+    /// Whether a steady state was detected before the maximum warm-up time was used up.
+    pub(crate) steady: bool,
+}
+
+/// A sliding window of the most recent batches of the warm-up.
+#[derive(Default)]
+pub(crate) struct SteadyStateDetector {
+    batches: VecDeque<(Duration, u64)>,
+}
+impl SteadyStateDetector {
+    pub(crate) fn new() -> SteadyStateDetector {
+        SteadyStateDetector::default()
+    }
+
+    /// Adds a batch of `iters` iterations that took `elapsed`, dropping the oldest batch if the
+    /// window is full.
+    pub(crate) fn push(&mut self, elapsed: Duration, iters: u64) {
+        if self.batches.len() == WINDOW {
+            self.batches.pop_front();
+        }
+        self.batches.push_back((elapsed, iters));
+    }
+
+    /// The total time and number of iterations of the batches in the window.
+    pub(crate) fn totals(&self) -> (Duration, u64) {
+        self.batches
+            .iter()
+            .fold((Duration::from_secs(0), 0), |(elapsed, iters), batch| {
+                (elapsed + batch.0, iters + batch.1)
+            })
+    }
+
+    /// Returns true if the window is full and the time per iteration no longer trends.
+    pub(crate) fn is_steady(&self) -> bool {
+        if self.batches.len() < WINDOW {
+            return false;
+        }
+
+        let times = self
+            .batches
+            .iter()
+            .map(|(elapsed, iters)| elapsed.as_nanos() as f64 / *iters as f64)
+            .collect::<Vec<_>>();
+        is_flat(&times)
+    }
+}
+
+// Tests the values for a monotonic trend with the Mann-Kendall test, and estimates the size of the
+// trend with the Theil-Sen slope. Both are robust against outliers, so a few batches slowed down by
+// the scheduler don't hide a trend or fake one.
+fn is_flat(values: &[f64]) -> bool {
+    let n = values.len();
+    let mut signs = 0i64;
+    let mut slopes = Vec::with_capacity(n * (n - 1) / 2);
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let difference = values[j] - values[i];
+            signs += if difference > 0.0 {
+                1
+            } else if difference < 0.0 {
+                -1
+            } else {
+                0
+            };
+            slopes.push(difference / (j - i) as f64);
+        }
+    }
+
+    let slope = Sample::new(&slopes).percentiles().median();
+    let median = Sample::new(values).percentiles().median();
+    if (slope * (n - 1) as f64).abs() <= NEGLIGIBLE_DRIFT * median.abs() {
+        return true;
+    }
+
+    let n = n as f64;
+    let variance = n * (n - 1.0) * (2.0 * n + 5.0) / 18.0;
+    let z = (signs.abs() - 1).max(0) as f64 / variance.sqrt();
+    z <= normal::quantile(0.975)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn detector(times: impl Iterator<Item = f64>) -> SteadyStateDetector {
+        let mut detector = SteadyStateDetector::new();
+        for time in times {
+            detector.push(Duration::from_nanos(time as u64 * 10), 10);
+        }
+        detector
+    }
+
+    // Deterministic noise of about ±5%
+    fn noise(i: usize) -> f64 {
+        [0.0, 4.0, -3.0, 5.0, -5.0, 2.0, -1.0, 3.0, -4.0, 1.0][i % 10]
+    }
+
+    #[test]
+    fn test_steady() {
+        assert!(detector((0..WINDOW).map(|i| 100.0 + noise(i))).is_steady());
+    }
+
+    #[test]
+    fn test_trending() {
+        assert!(!detector((0..WINDOW).map(|i| 200.0 - 5.0 * i as f64 + noise(i))).is_steady());
+    }
+
+    #[test]
+    fn test_window_slides() {
+        let warming = (0..WINDOW).map(|i| 1000.0 - 40.0 * i as f64);
+        let warm = (0..WINDOW).map(|i| 100.0 + noise(i));
+        let mut detector = detector(warming);
+        assert!(!detector.is_steady());
+
+        for (i, time) in warm.enumerate() {
+            detector.push(Duration::from_nanos(time as u64 * 10), 10);
+            if i < WINDOW / 2 {
+                assert!(!detector.is_steady());
+            }
+        }
+        assert!(detector.is_steady());
+        assert_eq!(detector.totals().1, 10 * WINDOW as u64);
+    }
+
+    #[test]
+    fn test_window_not_full() {
+        assert!(!detector((0..WINDOW - 1).map(|_| 100.0)).is_steady());
+    }
+}
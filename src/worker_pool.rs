@@ -0,0 +1,211 @@
+//! The worker threads of `Bencher::iter_parallel`.
+//!
+//! The threads are spawned once per benchmark and parked between samples, so that spawning them
+//! isn't measured. Every sample passes three barriers shared by the workers and the measurement
+//! thread: `ready` once all workers are parked, `go` to start them after the measurement has been
+//! started, and `done` once all of them have finished their iterations.
+
+use std::any::Any;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// The iterations of one sample, run by every worker with its index.
+type Job = &'static (dyn Fn(usize) + Sync);
+
+struct Shared {
+    ready: Barrier,
+    go: Barrier,
+    done: Barrier,
+    /// The job of the current sample, or `None` to stop the workers.
+    job: Mutex<Option<Job>>,
+    /// The payload of the first panic of a worker in the current sample.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+pub(crate) struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+impl WorkerPool {
+    pub(crate) fn new(threads: usize) -> WorkerPool {
+        let shared = Arc::new(Shared {
+            ready: Barrier::new(threads + 1),
+            go: Barrier::new(threads + 1),
+            done: Barrier::new(threads + 1),
+            job: Mutex::new(None),
+            panic: Mutex::new(None),
+        });
+        let workers = (0..threads)
+            .map(|thread_idx| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("criterion-worker-{}", thread_idx))
+                    .spawn(move || work(&shared, thread_idx))
+                    .expect("Unable to spawn a worker thread")
+            })
+            .collect();
+        WorkerPool { shared, workers }
+    }
+
+    pub(crate) fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Runs `job` on every worker, calling `start` once all of them are parked and ready to run
+    /// it, and `end` with the result of `start` once all of them have finished. A panic of a
+    /// worker is resumed on this thread after the sample.
+    pub(crate) fn run<S, E, T, U>(&mut self, job: &(dyn Fn(usize) + Sync), start: S, end: E) -> U
+    where
+        S: FnOnce() -> T,
+        E: FnOnce(T) -> U,
+    {
+        // SAFETY: The workers only call the job between the `go` and `done` barriers, and this
+        // function doesn't return before all of them have passed `done`.
+        let job = unsafe { mem::transmute::<&(dyn Fn(usize) + Sync), Job>(job) };
+        *self.shared.job.lock().unwrap() = Some(job);
+
+        self.shared.ready.wait();
+        let mut sample = Sample {
+            shared: &self.shared,
+            finished: false,
+        };
+        let started = start();
+        self.shared.go.wait();
+        self.shared.done.wait();
+        sample.finished = true;
+        let result = end(started);
+        drop(sample);
+
+        let panic = self.shared.panic.lock().unwrap().take();
+        if let Some(payload) = panic {
+            panic::resume_unwind(payload);
+        }
+        result
+    }
+}
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // The workers stop when they find no job after the `go` barrier
+        *self.shared.job.lock().unwrap() = None;
+        self.shared.ready.wait();
+        self.shared.go.wait();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A sample which the workers are parked for. When it is dropped, even by a panic of `start` or
+/// `end`, the workers are parked again for the next sample and the job is cleared.
+struct Sample<'p> {
+    shared: &'p Shared,
+    /// Whether the workers have passed the `done` barrier.
+    finished: bool,
+}
+impl Drop for Sample<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // `start` panicked before the workers were released, so release them with a job that
+            // does nothing
+            *self.shared.job.lock().unwrap() = Some(&idle);
+            self.shared.go.wait();
+            self.shared.done.wait();
+        }
+        *self.shared.job.lock().unwrap() = None;
+    }
+}
+
+fn idle(_thread_idx: usize) {}
+
+fn work(shared: &Shared, thread_idx: usize) {
+    loop {
+        shared.ready.wait();
+        shared.go.wait();
+        let job = match *shared.job.lock().unwrap() {
+            Some(job) => job,
+            None => return,
+        };
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| job(thread_idx))) {
+            shared.panic.lock().unwrap().get_or_insert(payload);
+        }
+        shared.done.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_runs_job_on_every_worker() {
+        let mut pool = WorkerPool::new(3);
+        let calls = AtomicUsize::new(0);
+        let indices = Mutex::new(vec![]);
+        for _ in 0..2 {
+            pool.run(
+                &|thread_idx| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    indices.lock().unwrap().push(thread_idx);
+                },
+                // The workers are parked when the measurement starts
+                || assert_eq!(calls.load(Ordering::SeqCst) % 3, 0),
+                |()| assert_eq!(calls.load(Ordering::SeqCst) % 3, 0),
+            );
+        }
+        let mut indices = indices.into_inner().unwrap();
+        indices.sort_unstable();
+        assert_eq!(indices, [0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_resumes_panic_of_worker() {
+        let mut pool = WorkerPool::new(2);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.run(
+                &|thread_idx| assert!(thread_idx != 1, "worker panicked"),
+                || {},
+                |()| {},
+            )
+        }));
+        assert!(result.is_err());
+
+        // The pool is still usable, and dropping it stops the workers
+        let calls = AtomicUsize::new(0);
+        pool.run(
+            &|_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+            },
+            || {},
+            |()| {},
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_runs_after_panicking_start_and_end() {
+        let mut pool = WorkerPool::new(2);
+        let calls = AtomicUsize::new(0);
+        let job = |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.run(&job, || panic!("start panicked"), |()| {})
+        }));
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.run(&job, || {}, |()| panic!("end panicked"))
+        }));
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The workers are parked again, so the pool runs further samples and stops when dropped
+        pool.run(&job, || {}, |()| {});
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        drop(pool);
+    }
+}
@@ -0,0 +1,62 @@
+//! Tests for the `Allocations` measurement. This needs its own test binary without the default test
+//! harness, because the counting allocator has to be the global allocator of the whole binary, and
+//! it counts the allocations of all threads, like those of other tests running in parallel.
+
+use criterion::alloc::{Allocations, CountingAllocator};
+use criterion::measurement::WallTime;
+use criterion::{black_box, Criterion};
+use serde_json::Value;
+use std::alloc::System;
+use std::path::Path;
+use std::time::Duration;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator<System> = CountingAllocator::new(System);
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("criterion-allocations-{}", std::process::id()));
+
+    // The second run is compared to the first one
+    let metrics = dir.join("with_capacity/metrics");
+    for &(capacity, allocations) in &[(16, 1.0), (0, 0.0)] {
+        let mut c = Criterion::default()
+            .output_directory(&dir)
+            .warm_up_time(Duration::from_millis(250))
+            .measurement_time(Duration::from_millis(500))
+            .nresamples(2000)
+            .without_plots()
+            .with_measurement((WallTime, Allocations));
+        c.bench_function("with_capacity", |b| {
+            b.iter(|| Vec::<u8>::with_capacity(black_box(capacity)))
+        });
+
+        let estimates = metrics.join("allocations/new/estimates.json");
+        assert_eq!(mean(&estimates), allocations);
+        let estimates = metrics.join("bytes/new/estimates.json");
+        assert_eq!(mean(&estimates), capacity as f64);
+    }
+    assert!(metrics.join("allocations/change/estimates.json").is_file());
+
+    // Allocations can be the only measurement, even of a routine which never allocates
+    let mut c = Criterion::default()
+        .output_directory(&dir)
+        .warm_up_time(Duration::from_millis(250))
+        .measurement_time(Duration::from_millis(500))
+        .nresamples(2000)
+        .without_plots()
+        .with_measurement(Allocations);
+    c.bench_function("allocations_only", |b| {
+        b.iter(|| Vec::<u8>::with_capacity(black_box(0)))
+    });
+    let results = dir.join("allocations_only");
+    assert_eq!(mean(&results.join("new/estimates.json")), 0.0);
+    assert_eq!(mean(&results.join("metrics/bytes/new/estimates.json")), 0.0);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// Returns the mean per iteration from the given estimates
+fn mean(path: &Path) -> f64 {
+    let estimates: Value = serde_json::from_reader(std::fs::File::open(path).unwrap()).unwrap();
+    estimates["mean"]["point_estimate"].as_f64().unwrap()
+}
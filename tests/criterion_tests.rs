@@ -1,10 +1,11 @@
 use criterion;
 use serde_json;
 
-#[cfg(feature = "plotters")]
-use criterion::SamplingMode;
+use criterion::measurement::{Measurement, ValueFormatter, WallTime};
 use criterion::{
-    criterion_group, criterion_main, profiler::Profiler, BatchSize, BenchmarkId, Criterion,
+    black_box, criterion_group, criterion_main, profiler::Profiler, BatchSize, BenchmarkFilter,
+    BenchmarkId, ComparisonTest, Criterion, Interleaving, PlotConfiguration, Priority,
+    SamplingMode, WarmUpMode,
 };
 use serde_json::value::Value;
 use std::cell::{Cell, RefCell};
@@ -12,7 +13,7 @@ use std::cmp::max;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use tempfile::{tempdir, TempDir};
 use walkdir::WalkDir;
 
@@ -182,6 +183,20 @@ fn test_compare_baseline_strict_panics_when_missing_baseline() {
         .bench_function("test_compare_baseline", |b| b.iter(|| 10));
 }
 
+#[test]
+#[should_panic(expected = "Benchmark IDs must be unique within a group")]
+fn test_duplicate_ids_with_different_tags_panic() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir);
+    let mut group = c.benchmark_group("duplicate_tags");
+    group.bench_function(BenchmarkId::from_parameter(1).with_tags(["a"]), |b| {
+        b.iter(|| 10)
+    });
+    group.bench_function(BenchmarkId::from_parameter(1).with_tags(["b"]), |b| {
+        b.iter(|| 10)
+    });
+}
+
 #[test]
 fn test_compare_baseline_lenient_when_missing_baseline() {
     let dir = temp_dir();
@@ -272,6 +287,97 @@ fn test_filtering() {
     assert!(!dir.path().join("test_filtering").is_dir());
 }
 
+#[test]
+fn test_tags() {
+    let dir = temp_dir();
+    let run = |filter: BenchmarkFilter| {
+        let ran = Rc::new(RefCell::new(vec![]));
+        let mut c = short_benchmark(&dir)
+            .sample_size(10)
+            .with_benchmark_filter(filter);
+        let mut group = c.benchmark_group("tags");
+        for &(name, tags) in &[("fast", &[][..]), ("slow", &["slow"][..])] {
+            let ran = ran.clone();
+            let id = BenchmarkId::from_parameter(name).with_tags(tags.iter().copied());
+            group.bench_function(id, move |b| {
+                ran.borrow_mut().push(name);
+                b.iter(|| 10)
+            });
+        }
+        group.tag("e2e");
+        let ran_e2e = ran.clone();
+        group.bench_function(
+            BenchmarkId::from_parameter("e2e").with_tags(["slow"]),
+            |b| {
+                ran_e2e.borrow_mut().push("e2e");
+                b.iter(|| 10)
+            },
+        );
+        group.finish();
+
+        let mut ran = ran.borrow().clone();
+        ran.dedup();
+        ran
+    };
+    let tag = |tag: &str| BenchmarkFilter::Tag(tag.to_owned());
+
+    assert_eq!(run(tag("slow")), ["slow", "e2e"]);
+    assert_eq!(run(tag("e2e")), ["e2e"]);
+    assert_eq!(run(BenchmarkFilter::Not(Box::new(tag("slow")))), ["fast"]);
+    assert_eq!(
+        run(BenchmarkFilter::All(vec![
+            BenchmarkFilter::Regex(regex::Regex::new("fast|e2e").unwrap()),
+            BenchmarkFilter::Any(vec![tag("slow"), tag("e2e")]),
+        ])),
+        ["e2e"]
+    );
+
+    // The tags of the benchmark and its group are saved
+    let path = dir.path().join("tags/e2e/new/benchmark.json");
+    let benchmark: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+    assert_eq!(benchmark["tags"], serde_json::json!(["e2e", "slow"]));
+}
+
+#[test]
+fn test_filter_combinators() {
+    let dir = temp_dir();
+    let run = |filter: BenchmarkFilter| {
+        let ran = Rc::new(RefCell::new(vec![]));
+        let mut c = short_benchmark(&dir)
+            .sample_size(10)
+            .with_benchmark_filter(filter);
+        let mut group = c.benchmark_group("combinators");
+        for &name in &["parse", "parse_huge", "encode", "encode_huge", "decode"] {
+            let ran = ran.clone();
+            group.bench_function(name, move |b| {
+                ran.borrow_mut().push(name);
+                b.iter(|| 10)
+            });
+        }
+        group.finish();
+
+        let mut ran = ran.borrow().clone();
+        ran.dedup();
+        ran
+    };
+    let regex = |pattern: &str| BenchmarkFilter::Regex(regex::Regex::new(pattern).unwrap());
+
+    assert_eq!(
+        run(regex("parse").or(regex("encode"))),
+        ["parse", "parse_huge", "encode", "encode_huge"]
+    );
+    assert_eq!(
+        run(regex("parse").or(regex("encode")).and(!regex("huge"))),
+        ["parse", "encode"]
+    );
+    assert_eq!(run(!!regex("decode")), ["decode"]);
+    assert_eq!(
+        run(BenchmarkFilter::AcceptAll.and(!regex("_"))),
+        ["parse", "encode", "decode"]
+    );
+    assert!(run(BenchmarkFilter::RejectAll.or(BenchmarkFilter::RejectAll)).is_empty());
+}
+
 #[test]
 fn test_timing_loops() {
     let dir = temp_dir();
@@ -318,6 +424,34 @@ fn test_timing_loops() {
     });
 }
 
+#[test]
+fn test_cold_cache() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).sample_size(10);
+    let mut group = c.benchmark_group("cold_cache");
+    // Every iteration evicts the caches, so keep the number of iterations low
+    group.sampling_mode(criterion::SamplingMode::Flat);
+    group.bench_function("iter_batched", |b| {
+        b.iter_batched(|| vec![10], |v| v[0], BatchSize::ColdPerIteration)
+    });
+    group.bench_function("iter_batched_ref", |b| {
+        b.iter_batched_ref(|| vec![10], |v| v[0], BatchSize::ColdPerIteration)
+    });
+    group.finish();
+
+    // Evicting the caches takes far longer than the routine, but is not measured
+    for name in &["iter_batched", "iter_batched_ref"] {
+        let path = dir
+            .path()
+            .join("cold_cache")
+            .join(name)
+            .join("new/estimates.json");
+        let estimates: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        let mean = estimates["mean"]["point_estimate"].as_f64().unwrap();
+        assert!(mean < 50_000.0, "{}: {} ns", name, mean);
+    }
+}
+
 // Verify that all expected output files are present
 #[cfg(feature = "plotters")]
 #[test]
@@ -431,10 +565,44 @@ fn test_output_files_flat_sampling() {
 }
 
 #[test]
-#[should_panic(expected = "Benchmark function must call Bencher::iter or related method.")]
-fn test_bench_with_no_iteration_panics() {
+fn test_comparison_tests() {
     let dir = temp_dir();
-    short_benchmark(&dir).bench_function("no_iter", |_b| {});
+    let tests = [
+        ("welch", ComparisonTest::Welch),
+        ("mann_whitney", ComparisonTest::MannWhitney),
+        ("median", ComparisonTest::MedianDifference),
+    ];
+    // Run benchmarks twice to produce comparisons
+    for _ in 0..2 {
+        let mut c = short_benchmark(&dir).sample_size(10);
+        let mut group = c.benchmark_group("comparison_tests");
+        for &(name, test) in &tests {
+            group.comparison_test(test);
+            group.bench_function(name, |b| b.iter(|| 10));
+        }
+        group.finish();
+    }
+
+    for &(name, _) in &tests {
+        let dir = dir.path().join("comparison_tests").join(name);
+        verify_json(&dir, "change/estimates.json");
+        #[cfg(feature = "html_reports")]
+        {
+            verify_svg(&dir, "report/change/t-test.svg");
+            verify_html(&dir, "report/index.html");
+        }
+    }
+}
+
+#[test]
+fn test_bench_with_no_iteration_fails() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir);
+    c.bench_function("no_iter", |_b| {});
+    c.bench_function("after_no_iter", |b| b.iter(|| 10));
+
+    verify_not_exists(&dir.path().to_owned(), "no_iter/new/estimates.json");
+    verify_stats(&dir.path().join("after_no_iter"), "new");
 }
 
 #[test]
@@ -468,6 +636,668 @@ fn test_criterion_doesnt_panic_if_measured_time_is_zero() {
     });
 }
 
+#[test]
+fn test_calibrate_overhead() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).calibrate_overhead(true);
+    c.bench_function("calibrate_iter", |b| {
+        b.iter(|| (0..1000u64).map(black_box).sum::<u64>())
+    });
+    c.bench_function("calibrate_iter_batched", |b| {
+        b.iter_batched(
+            || 1000u64,
+            |n| (0..n).map(black_box).sum::<u64>(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("calibrate_iter_custom", |b| {
+        b.iter_custom(|iters| {
+            let start = Instant::now();
+            for _ in 0..iters {
+                black_box((0..1000u64).map(black_box).sum::<u64>());
+            }
+            start.elapsed()
+        })
+    });
+
+    verify_stats(&dir.path().join("calibrate_iter"), "new");
+    verify_stats(&dir.path().join("calibrate_iter_batched"), "new");
+    verify_stats(&dir.path().join("calibrate_iter_custom"), "new");
+
+    for &calibrate in &[true, false] {
+        let mut c = short_benchmark(&dir)
+            .with_measurement(Ticks)
+            .calibrate_overhead(calibrate);
+        let mut group = c.benchmark_group(format!("ticks_{}", calibrate));
+        // All samples have the same number of iterations, so the fixed overhead of a sample is
+        // spread over them exactly
+        group.sampling_mode(SamplingMode::Flat);
+        group.bench_function("iter", |b| b.iter(tick));
+        group.bench_function("iter_custom", |b| {
+            b.iter_custom(|iters| {
+                for _ in 0..iters {
+                    tick();
+                }
+                TICKS_PER_CALL * iters + TICKS_OVERHEAD
+            })
+        });
+        group.finish();
+
+        // Timing every iteration adds the overhead of the timer to each of them
+        let mut group = c.benchmark_group(format!("ticks_{}_per_iteration", calibrate));
+        group.sampling_mode(SamplingMode::PerIteration);
+        group.bench_function("iter", |b| b.iter(tick));
+        group.finish();
+    }
+
+    // Returns the overhead left in each saved sample, ie. its value minus the ticks of the routine
+    let overheads = |group: &str, name: &str| {
+        let path = dir.path().join(group).join(name).join("new/sample.json");
+        let sample: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        let iters = sample["iters"].as_array().unwrap();
+        let times = sample["times"].as_array().unwrap();
+        iters
+            .iter()
+            .zip(times)
+            .map(|(iters, time)| {
+                let iters = iters.as_f64().unwrap();
+                time.as_f64().unwrap() - TICKS_PER_CALL as f64 * iters
+            })
+            .collect::<Vec<_>>()
+    };
+    let overhead = TICKS_OVERHEAD as f64;
+    for calibrated in overheads("ticks_true", "iter") {
+        assert!(calibrated.abs() < 1e-3, "{}", calibrated);
+    }
+    for uncalibrated in overheads("ticks_false", "iter") {
+        assert_eq!(uncalibrated, overhead);
+    }
+    // iter_custom does its own measurement, so nothing is subtracted from what it reports
+    for group in &["ticks_true", "ticks_false"] {
+        for custom in overheads(group, "iter_custom") {
+            assert_eq!(custom, overhead);
+        }
+    }
+    // Whether calibrated or not, the overhead of every timed iteration is subtracted
+    for group in &["ticks_true_per_iteration", "ticks_false_per_iteration"] {
+        for per_iteration in overheads(group, "iter") {
+            assert!(per_iteration.abs() < 1e-3, "{}: {}", group, per_iteration);
+        }
+    }
+}
+
+const TICKS_PER_CALL: u64 = 10;
+const TICKS_OVERHEAD: u64 = 1_000_000_000;
+
+thread_local! {
+    static TICKS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn tick() {
+    TICKS.with(|ticks| ticks.set(ticks.get() + TICKS_PER_CALL));
+}
+
+static WALL_TIME: WallTime = WallTime;
+
+/// Counts the ticks of `tick`, plus a large fixed overhead for every measurement, so that the
+/// overhead calibration has a known overhead to subtract.
+struct Ticks;
+impl Measurement for Ticks {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        TICKS.with(Cell::get)
+    }
+    fn end(&self, start: u64) -> u64 {
+        TICKS.with(Cell::get) - start + TICKS_OVERHEAD
+    }
+    fn add(&self, v1: &u64, v2: &u64) -> u64 {
+        v1 + v2
+    }
+    fn zero(&self) -> u64 {
+        0
+    }
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+    fn formatter(&self) -> &dyn ValueFormatter {
+        WALL_TIME.formatter()
+    }
+}
+
+#[test]
+fn test_iter_parallel() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).sample_size(10);
+    let counter = AtomicU64::new(0);
+    let mut group = c.benchmark_group("parallel");
+    for threads in [1, 2] {
+        group.bench_with_input(
+            BenchmarkId::new("count", threads),
+            &threads,
+            |b, &threads| b.iter_parallel(threads, |_| counter.fetch_add(1, Ordering::Relaxed)),
+        );
+        // Sleeping threads don't compete for cores, so the operations overlap completely
+        group.bench_with_input(
+            BenchmarkId::new("sleep", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_parallel(threads, |_| std::thread::sleep(Duration::from_micros(200)))
+            },
+        );
+    }
+    group.finish();
+
+    assert!(counter.load(Ordering::Relaxed) > 0);
+    let typical = |name: &str, threads: u64| {
+        let dir = dir.path().join(format!("parallel/{}/{}", name, threads));
+        verify_stats(&dir, "new");
+
+        // The throughput of all threads together is saved
+        let file = File::open(dir.join("new/benchmark.json")).unwrap();
+        let benchmark: Value = serde_json::from_reader(file).unwrap();
+        assert_eq!(
+            benchmark["throughput"],
+            serde_json::json!({ "Elements": threads })
+        );
+
+        let file = File::open(dir.join("new/estimates.json")).unwrap();
+        let estimates: Value = serde_json::from_reader(file).unwrap();
+        let typical = match &estimates["slope"] {
+            Value::Null => &estimates["mean"],
+            slope => slope,
+        };
+        typical["point_estimate"].as_f64().unwrap()
+    };
+    typical("count", 1);
+    typical("count", 2);
+
+    // The time is the latency of one operation, not the time of all threads' operations
+    let (one, two) = (typical("sleep", 1), typical("sleep", 2));
+    assert!(one >= 200_000.0, "{}", one);
+    assert!(two >= 200_000.0 && two < 1.5 * one, "{} vs {}", two, one);
+}
+
+#[test]
+fn test_per_iteration_sampling() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir);
+    let mut group = c.benchmark_group("per_iteration");
+    group.sampling_mode(criterion::SamplingMode::PerIteration);
+    group.bench_function("iter", |b| b.iter(|| (0..100u64).sum::<u64>()));
+    group.bench_function("iter_batched", |b| {
+        b.iter_batched(|| 100u64, |n| (0..n).sum::<u64>(), BatchSize::SmallInput)
+    });
+    group.finish();
+
+    for name in &["iter", "iter_batched"] {
+        let path = dir
+            .path()
+            .join("per_iteration")
+            .join(name)
+            .join("new/estimates.json");
+        let estimates: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        let percentiles = &estimates["percentiles"];
+        for percentile in &["p50", "p90", "p99", "p999"] {
+            assert!(percentiles[percentile]["point_estimate"].is_number());
+        }
+        let p50 = percentiles["p50"]["point_estimate"].as_f64().unwrap();
+        let p99 = percentiles["p99"]["point_estimate"].as_f64().unwrap();
+        assert!(p50 <= p99);
+    }
+}
+
+#[test]
+fn test_target_precision() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir)
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(50));
+    let mut group = c.benchmark_group("target_precision");
+    group.target_precision(1e6);
+    group.bench_function("reached", |b| b.iter(|| (0..100u64).sum::<u64>()));
+    group
+        .target_precision(1e-12)
+        .max_measurement_time(Duration::from_millis(200));
+    group.bench_function("budget_exhausted", |b| {
+        b.iter(|| (0..100u64).map(black_box).sum::<u64>())
+    });
+    group.finish();
+
+    let sample_count = |name: &str| {
+        let path = dir
+            .path()
+            .join("target_precision")
+            .join(name)
+            .join("new/sample.json");
+        let sample: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        sample["iters"].as_array().unwrap().len()
+    };
+    assert_eq!(sample_count("reached"), 10);
+    let exhausted = sample_count("budget_exhausted");
+    assert!(exhausted > 10);
+    assert_eq!(exhausted % 10, 0);
+}
+
+#[test]
+fn test_steady_state_warm_up() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir)
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(100));
+    let mut group = c.benchmark_group("steady_state_warm_up");
+    group
+        .warm_up_mode(WarmUpMode::SteadyState)
+        .warm_up_time(Duration::from_millis(10))
+        .max_warm_up_time(Duration::from_secs(30));
+
+    let start = Instant::now();
+    group.bench_function("stable", |b| b.iter(|| 10));
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // Every iteration spins for a time that decays exponentially from 1ms, so the routine keeps
+    // getting faster for more than a second
+    let start = Instant::now();
+    group.bench_function("warming", |b| {
+        b.iter(|| {
+            let spin =
+                Duration::from_secs_f64(1e-3 * (-start.elapsed().as_secs_f64() / 0.15).exp());
+            let spin_start = Instant::now();
+            while spin_start.elapsed() < spin {}
+        })
+    });
+    assert!(start.elapsed() > Duration::from_millis(700));
+    group.finish();
+}
+
+#[test]
+fn test_interleaved_group() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).sample_size(10);
+    let calls = Rc::new(RefCell::new(vec![]));
+    let input = 100u64;
+
+    let mut group = c.benchmark_group("interleaved");
+    for &(order, name) in &[
+        (Interleaving::RoundRobin, "round_robin"),
+        (Interleaving::Random, "random"),
+    ] {
+        calls.borrow_mut().clear();
+        let mut interleaved = group.interleaved(order);
+        for &function in &["a", "b"] {
+            let calls = calls.clone();
+            interleaved.bench_with_input(format!("{}_{}", name, function), &input, move |b, i| {
+                calls.borrow_mut().push(function);
+                b.iter(|| (0..*i).sum::<u64>())
+            });
+        }
+        interleaved.finish();
+
+        // Every round of sampling collects one sample of each benchmark
+        let calls = calls.borrow();
+        let rounds = calls[calls.len() - 20..].chunks(2).collect::<Vec<_>>();
+        for round in &rounds {
+            assert_ne!(round[0], round[1]);
+        }
+        if let Interleaving::RoundRobin = order {
+            assert!(rounds.iter().all(|round| round == &["a", "b"]));
+        }
+        for function in &["a", "b"] {
+            let dir = dir
+                .path()
+                .join("interleaved")
+                .join(format!("{}_{}", name, function));
+            verify_stats(&dir, "new");
+        }
+    }
+    group.finish();
+}
+
+#[test]
+fn test_fixture_group() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).sample_size(10);
+    let setups = Counter::default();
+    let teardowns = Counter::default();
+    let before = Counter::default();
+    let after = Counter::default();
+
+    let mut group = c.benchmark_group("fixture");
+    let (setup, teardown) = (setups.clone(), teardowns.clone());
+    let mut fixture = group.setup(move || {
+        setup.count();
+        (0..100u64).collect::<Vec<_>>()
+    });
+    let (before_sample, after_sample) = (before.clone(), after.clone());
+    fixture
+        .teardown(move |input| {
+            assert_eq!(input.len(), 100);
+            teardown.count();
+        })
+        .before_sample(move |_| {
+            before_sample.count();
+            std::thread::sleep(Duration::from_millis(2));
+        })
+        .after_sample(move |_| after_sample.count());
+    fixture.bench_function("sum", |b, input| b.iter(|| input.iter().sum::<u64>()));
+    fixture.bench_with_input("max", &2, |b, input, n| {
+        b.iter(|| input.iter().map(|i| i * n).max())
+    });
+    assert_eq!(setups.read(), 1);
+    assert_eq!(teardowns.read(), 0);
+    fixture.finish();
+    group.finish();
+
+    assert_eq!(teardowns.read(), 1);
+    assert!(before.read() > 20);
+    assert_eq!(before.read(), after.read());
+
+    // The hooks take far longer than the routines, but are not measured
+    for name in &["sum", "max"] {
+        let path = dir
+            .path()
+            .join("fixture")
+            .join(name)
+            .join("new/estimates.json");
+        let estimates: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        let mean = estimates["mean"]["point_estimate"].as_f64().unwrap();
+        assert!(mean < 50_000.0, "{}: {} ns", name, mean);
+    }
+
+    // The fixture isn't built if none of its benchmarks run
+    let mut c = short_benchmark(&dir).with_filter("Foo");
+    let mut group = c.benchmark_group("filtered_fixture");
+    let setup = setups.clone();
+    let mut fixture = group.setup(move || setup.count());
+    fixture.bench_function("unused", |b, _| b.iter(|| 1));
+    fixture.finish();
+    group.finish();
+    assert_eq!(setups.read(), 1);
+}
+
+#[test]
+fn test_bench_cartesian() {
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).sample_size(10);
+    let inputs = Rc::new(RefCell::new(vec![]));
+
+    let mut group = c.benchmark_group("sweep");
+    group.plot_config(
+        PlotConfiguration::default()
+            .x_axis_parameter("size")
+            .line_parameters(["threads"]),
+    );
+    let recorded = inputs.clone();
+    group.bench_cartesian(
+        "sum",
+        (
+            ("mode", ["a", "b"]),
+            ("threads", [1, 2]),
+            ("size", [10u64, 100]),
+        ),
+        move |b, &(mode, threads, size)| {
+            let input = (mode, threads, size);
+            if !recorded.borrow().contains(&input) {
+                recorded.borrow_mut().push(input);
+            }
+            b.iter(|| (0..size).sum::<u64>())
+        },
+    );
+    group.finish();
+
+    // The last dimension changes fastest
+    assert_eq!(
+        *inputs.borrow(),
+        [
+            ("a", 1, 10),
+            ("a", 1, 100),
+            ("a", 2, 10),
+            ("a", 2, 100),
+            ("b", 1, 10),
+            ("b", 1, 100),
+            ("b", 2, 10),
+            ("b", 2, 100),
+        ]
+    );
+
+    let dir = dir.path().join("sweep");
+    verify_stats(&dir.join("sum/mode=a,threads=2,size=100"), "new");
+    let path = dir.join("sum/mode=a,threads=2,size=100/new/benchmark.json");
+    let benchmark: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+    assert_eq!(
+        benchmark["parameters"],
+        serde_json::json!([["mode", "a"], ["threads", "2"], ["size", "100"]])
+    );
+
+    // Every mode gets a summary, with a line for each number of threads
+    #[cfg(feature = "html_reports")]
+    {
+        verify_svg(&dir, "report/lines.svg");
+        verify_svg(&dir, "mode=a/report/lines.svg");
+        verify_svg(&dir, "mode=b/report/lines.svg");
+    }
+}
+
+#[test]
+fn test_pin_to_cpu() {
+    let dir = temp_dir();
+    let sample_cpu = || {
+        let path = dir.path().join("pinned/new/sample.json");
+        let sample: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+        sample["cpu"].as_u64()
+    };
+
+    short_benchmark(&dir)
+        .pin_to_cpu(0)
+        .bench_function("pinned", |b| b.iter(|| 10));
+    assert_eq!(sample_cpu(), Some(0));
+
+    short_benchmark(&dir).bench_function("pinned", |b| b.iter(|| 10));
+    assert_eq!(sample_cpu(), None);
+}
+
+#[test]
+fn test_nice_priority() {
+    // An unprivileged process can't lower its nice value back, and the nice value is per thread on
+    // Linux, so the benchmark runs on a thread of its own rather than on the test harness's
+    let dir = temp_dir();
+    let dir = std::thread::spawn(move || {
+        short_benchmark(&dir)
+            .priority(Priority::Nice(5))
+            .bench_function("niced", |b| b.iter(|| 10));
+        dir
+    })
+    .join()
+    .unwrap();
+    verify_stats(&dir.path().join("niced"), "new");
+}
+
+#[test]
+fn test_environment_snapshot() {
+    let dir = temp_dir();
+    let root = temp_dir();
+    let write = |path: &str, contents: &str| {
+        let path = root.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    write(
+        "sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+        "powersave\n",
+    );
+    write("sys/devices/system/cpu/smt/active", "0\n");
+    write("proc/loadavg", "0.25 0.20 0.15 1/100 1234\n");
+
+    short_benchmark(&dir)
+        .environment_root(root.path())
+        .bench_function("environment", |b| b.iter(|| 10));
+
+    let bench_dir = dir.path().join("environment");
+    let path = verify_file(&bench_dir, "new/environment.json");
+    let environment: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+    assert_eq!(environment["cpu_governors"][0], "powersave");
+    assert_eq!(environment["smt"], false);
+    assert_eq!(environment["turbo"], Value::Null);
+    assert_eq!(environment["load_average"], 0.25);
+    verify_json(&bench_dir, "base/environment.json");
+}
+
+#[test]
+fn test_repetitions() {
+    let dir = temp_dir();
+    short_benchmark(&dir)
+        .repetitions(3)
+        .bench_function("repetitions", |b| b.iter(|| 10));
+
+    let bench_dir = dir.path().join("repetitions");
+    let path = verify_file(&bench_dir, "new/repetitions.json");
+    let repetitions: Value = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+    assert_eq!(repetitions["estimates"].as_array().unwrap().len(), 3);
+    let spread = &repetitions["spread"];
+    let (min, mean, max) = (
+        spread["min"].as_f64().unwrap(),
+        spread["mean"].as_f64().unwrap(),
+        spread["max"].as_f64().unwrap(),
+    );
+    assert!(min <= mean && mean <= max);
+    #[cfg(feature = "html_reports")]
+    {
+        verify_svg(&bench_dir, "report/repetitions.svg");
+        verify_html(&bench_dir, "report/index.html");
+    }
+
+    // A later run without repetitions doesn't leave the old ones behind
+    short_benchmark(&dir).bench_function("repetitions", |b| b.iter(|| 10));
+    verify_not_exists(&bench_dir, "new/repetitions.json");
+}
+
+#[test]
+fn test_shards() {
+    let dir = temp_dir();
+    let names = (0..6).map(|i| format!("bench_{}", i)).collect::<Vec<_>>();
+    let shards = (1..=3)
+        .map(|index| {
+            let shard_dir = dir.path().join(format!("shard-{}", index));
+            let mut c = short_benchmark(&dir)
+                .output_directory(&shard_dir)
+                .warm_up_time(Duration::from_millis(50))
+                .measurement_time(Duration::from_millis(100))
+                .shard(index, 3);
+            let mut group = c.benchmark_group("sharded");
+            for name in &names {
+                group.bench_function(name.as_str(), |b| b.iter(|| 10));
+            }
+            group.finish();
+            shard_dir
+        })
+        .collect::<Vec<_>>();
+
+    // Every benchmark runs in exactly one shard, and the group is split between shards
+    for name in &names {
+        let runs = shards
+            .iter()
+            .filter(|shard| shard.join("sharded").join(name).join("new").is_dir())
+            .count();
+        assert_eq!(runs, 1, "{}", name);
+    }
+    let used = shards
+        .iter()
+        .filter(|shard| shard.join("sharded").is_dir())
+        .count();
+    assert!(used > 1);
+
+    let merged = dir.path().join("merged");
+    short_benchmark(&dir)
+        .output_directory(&merged)
+        .merge_shards(&shards)
+        .final_summary();
+    for name in &names {
+        verify_stats(&merged.join("sharded").join(name), "new");
+    }
+    #[cfg(feature = "html_reports")]
+    {
+        verify_html(&merged, "report/index.html");
+        verify_html(&merged, "sharded/report/index.html");
+    }
+}
+
+#[test]
+fn test_composite_measurement() {
+    use criterion::measurement::WallTime;
+
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).with_measurement((WallTime, WallTime));
+    c.bench_function("composite", |b| b.iter(|| 10));
+    // Measurements which don't name their metric get a generic name
+    let mut c = short_benchmark(&dir).with_measurement((WallTime, Ticks));
+    c.bench_function("unnamed", |b| b.iter(tick));
+    verify_json(
+        &dir.path().join("unnamed"),
+        "metrics/value/new/estimates.json",
+    );
+
+    let dir = dir.path().join("composite");
+    verify_stats(&dir, "new");
+    verify_json(&dir, "metrics/time/new/estimates.json");
+    verify_json(&dir, "metrics/time/new/sample.json");
+    verify_not_exists(&dir, "metrics/time/new/benchmark.json");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cpu_time_measurements() {
+    use criterion::measurement::{ProcessCpuTime, ThreadCpuTime};
+
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).with_measurement(ThreadCpuTime);
+    c.bench_function("thread_cpu_time", |b| b.iter(|| (0..100u64).sum::<u64>()));
+    c.bench_function("thread_cpu_time_batched", |b| {
+        b.iter_batched(|| 100u64, |n| (0..n).sum::<u64>(), BatchSize::SmallInput)
+    });
+    #[cfg(feature = "async_futures")]
+    c.bench_function("thread_cpu_time_async", |b| {
+        b.to_async(criterion::async_executor::FuturesExecutor)
+            .iter(|| async { (0..100u64).sum::<u64>() })
+    });
+
+    let mut c = short_benchmark(&dir).with_measurement(ProcessCpuTime);
+    c.bench_function("process_cpu_time", |b| b.iter(|| (0..100u64).sum::<u64>()));
+
+    verify_stats(&dir.path().join("thread_cpu_time"), "new");
+    verify_stats(&dir.path().join("thread_cpu_time_batched"), "new");
+    #[cfg(feature = "async_futures")]
+    verify_stats(&dir.path().join("thread_cpu_time_async"), "new");
+    verify_stats(&dir.path().join("process_cpu_time"), "new");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_perf_event_measurement() {
+    use criterion::measurement::{PerfCounter, PerfEvent};
+
+    // perf_event_open may be forbidden entirely, eg. by perf_event_paranoid inside containers.
+    if let Err(err) = PerfEvent::task_clock() {
+        eprintln!("Skipping test_perf_event_measurement: {}", err);
+        return;
+    }
+
+    // Without a hardware counter, eg. in a virtual machine, the software event is counted instead
+    let measurement = PerfEvent::instructions().unwrap();
+    let expected = match PerfEvent::new(PerfCounter::Instructions) {
+        Ok(_) => PerfCounter::Instructions,
+        Err(_) => PerfCounter::TaskClock,
+    };
+    assert_eq!(measurement.counter(), expected);
+
+    let dir = temp_dir();
+    let mut c = short_benchmark(&dir).with_measurement(measurement);
+    c.bench_function("perf_event", |b| b.iter(|| (0..100u64).sum::<u64>()));
+    verify_stats(&dir.path().join("perf_event"), "new");
+}
+
 mod macros {
     use super::{criterion, criterion_group, criterion_main};
 
@@ -0,0 +1,112 @@
+//! Tests for benchmarks that panic or time out, or whose fixture panics. This needs its own test
+//! binary without the default test harness, because a failed benchmark makes the process exit with
+//! an error after the final summary. The benchmarks run in a child process, and the parent process checks its output.
+
+use criterion::Criterion;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+const DIR_VAR: &str = "CRITERION_FAILURES_TEST_DIR";
+
+fn main() {
+    if let Some(dir) = std::env::var_os(DIR_VAR) {
+        run_benchmarks(Path::new(&dir));
+        return;
+    }
+
+    let dir = std::env::temp_dir().join(format!("criterion-failures-{}", std::process::id()));
+    let output = Command::new(std::env::current_exe().unwrap())
+        .env(DIR_VAR, &dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1), "{}\n{}", stdout, stderr);
+    assert!(stdout.contains("failures/panic failed: boom"), "{}", stdout);
+    assert!(
+        stdout.contains("timeouts/sampling failed: timed out after"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("while sampling, after collecting"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("timeouts/warm_up failed: timed out after"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("while warming up, after"), "{}", stdout);
+    assert!(
+        stdout.contains("fixture/first failed: no database"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("fixture/second failed: the setup of the fixture panicked"),
+        "{}",
+        stdout
+    );
+    assert!(stderr.contains("5 benchmark(s) failed"), "{}", stderr);
+
+    // The benchmarks after the failed ones still run
+    let results = dir.join("failures");
+    assert!(!results.join("panic/new/estimates.json").exists());
+    assert!(results.join("after_panic/new/estimates.json").is_file());
+    let results = dir.join("timeouts");
+    assert!(!results.join("sampling/new/estimates.json").exists());
+    assert!(!results.join("warm_up/new/estimates.json").exists());
+    assert!(results.join("after_timeout/new/estimates.json").is_file());
+    let results = dir.join("fixture");
+    assert!(!results.join("first/new/estimates.json").exists());
+    assert!(!results.join("second/new/estimates.json").exists());
+    assert!(results.join("after_fixture/new/estimates.json").is_file());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn run_benchmarks(dir: &Path) {
+    let mut c = Criterion::default()
+        .output_directory(dir)
+        .warm_up_time(Duration::from_millis(100))
+        .measurement_time(Duration::from_millis(200))
+        .sample_size(10)
+        .nresamples(2000)
+        .without_plots();
+
+    let mut group = c.benchmark_group("failures");
+    group.bench_function("panic", |b| b.iter(|| panic!("boom")));
+    group.bench_function("after_panic", |b| b.iter(|| 1 + 1));
+    group.finish();
+
+    let mut group = c.benchmark_group("timeouts");
+    group.timeout(Duration::from_millis(300));
+    group.measurement_time(Duration::from_secs(10));
+    group.bench_function("sampling", |b| {
+        b.iter(|| std::thread::sleep(Duration::from_millis(1)))
+    });
+    group.warm_up_time(Duration::from_secs(10));
+    group.bench_function("warm_up", |b| {
+        b.iter(|| std::thread::sleep(Duration::from_millis(1)))
+    });
+    group.timeout(Duration::from_secs(60));
+    group.warm_up_time(Duration::from_millis(100));
+    group.measurement_time(Duration::from_millis(200));
+    group.bench_function("after_timeout", |b| b.iter(|| 1 + 1));
+    group.finish();
+
+    let mut group = c.benchmark_group("fixture");
+    let mut fixture = group.setup(|| -> u64 { panic!("no database") });
+    fixture.teardown(|_| unreachable!("the fixture was never built"));
+    fixture.bench_function("first", |b, n| b.iter(|| *n + 1));
+    fixture.bench_function("second", |b, n| b.iter(|| *n + 2));
+    fixture.finish();
+    group.bench_function("after_fixture", |b| b.iter(|| 1 + 1));
+    group.finish();
+
+    c.final_summary();
+}
@@ -0,0 +1,68 @@
+//! Tests for running benchmarks in isolated child processes. This needs its own test binary
+//! without the default test harness, because the binary re-executes itself for every benchmark.
+
+use criterion::Criterion;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static POLLUTED: AtomicBool = AtomicBool::new(false);
+static PRINTED: AtomicBool = AtomicBool::new(false);
+
+fn main() {
+    // The child processes inherit the environment, so they use the same output directory
+    let dir = match std::env::var_os("CRITERION_ISOLATED_TEST_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let dir =
+                std::env::temp_dir().join(format!("criterion-isolated-{}", std::process::id()));
+            std::env::set_var("CRITERION_ISOLATED_TEST_DIR", &dir);
+            dir
+        }
+    };
+
+    let mut c = Criterion::default()
+        .output_directory(&dir)
+        .warm_up_time(Duration::from_millis(100))
+        .measurement_time(Duration::from_millis(200))
+        .sample_size(10)
+        .nresamples(2000)
+        .without_plots()
+        .isolated(true);
+
+    let mut group = c.benchmark_group("isolated");
+    group.bench_function("pollute", |b| {
+        b.iter(|| POLLUTED.store(true, Ordering::SeqCst))
+    });
+    group.bench_function("check", |b| {
+        b.iter(|| assert!(!POLLUTED.load(Ordering::SeqCst)))
+    });
+    // The measurements are sent over stdout, after whatever the benchmark prints to it
+    group.bench_function("print", |b| {
+        if !PRINTED.swap(true, Ordering::SeqCst) {
+            println!("printed by the benchmark");
+        }
+        b.iter(|| 1 + 1)
+    });
+    group.bench_function("crash", |b| b.iter(|| std::process::abort()));
+    group.timeout(Duration::from_secs(1));
+    group.bench_function("hang", |b| {
+        b.iter(|| std::thread::sleep(Duration::from_secs(3600)))
+    });
+    group.timeout(Duration::from_secs(60));
+    group.bench_function("after_crash", |b| b.iter(|| 1 + 1));
+    group.finish();
+
+    // The benchmarks never run in the parent process
+    assert!(!POLLUTED.load(Ordering::SeqCst));
+
+    let results = dir.join("isolated");
+    for name in &["pollute", "check", "print", "after_crash"] {
+        assert!(results.join(name).join("new/estimates.json").is_file());
+        assert!(results.join(name).join("new/sample.json").is_file());
+    }
+    assert!(!results.join("crash").exists());
+    assert!(!results.join("hang").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
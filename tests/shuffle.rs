@@ -0,0 +1,206 @@
+//! Tests for running the benchmarks in random order. This needs its own test binary without the
+//! default test harness, because the order is decided by `criterion::runner` from the
+//! command-line arguments. The benchmarks run in child processes with different arguments, and
+//! the parent process checks the order they ran in.
+
+use criterion::{criterion_group, Criterion, Interleaving};
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DIR_VAR: &str = "CRITERION_SHUFFLE_TEST_DIR";
+
+static RAN: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+// The number of calls of the benchmark functions and targets, and of the setup of the fixture
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+static SETUPS: AtomicUsize = AtomicUsize::new(0);
+
+fn main() {
+    if std::env::var_os(DIR_VAR).is_some() {
+        criterion::runner(&[&first_group, &second_group, &third_group]);
+        println!("order: {}", RAN.lock().unwrap().join(" "));
+        // Every benchmark function and target runs once, whatever the order
+        assert_eq!(CALLS.load(Ordering::SeqCst), 4);
+        assert_eq!(SETUPS.load(Ordering::SeqCst), 1);
+        return;
+    }
+
+    let dir = std::env::temp_dir().join(format!("criterion-shuffle-{}", std::process::id()));
+    let in_order = run(&dir, &[]).0;
+    assert_eq!(
+        in_order,
+        "a/1 a/2 a/3 b/1 b/2 c/x c/y d/1 d/2 d/3 e/1 e/2 f/1 g/1"
+    );
+
+    let (shuffled, stderr) = run(&dir, &["--shuffle=42"]);
+    assert!(stderr.contains("seed 42"), "{}", stderr);
+    assert_eq!(run(&dir, &["--shuffle=42"]).0, shuffled);
+    let mut sorted = shuffled.split(' ').collect::<Vec<_>>();
+    sorted.sort_unstable();
+    assert_eq!(sorted.join(" "), in_order);
+
+    // Groups run one after another, interleaved benchmarks run together and the benchmarks of a
+    // plain group run in the order they are defined
+    let orders = (1..=8)
+        .map(|seed| run(&dir, &[&format!("--shuffle={}", seed)]).0)
+        .collect::<Vec<_>>();
+    let group_orders = orders
+        .iter()
+        .map(|order| {
+            let mut groups = order.split(' ').map(|id| &id[..1]).collect::<Vec<_>>();
+            groups.dedup();
+            groups.concat()
+        })
+        .collect::<Vec<_>>();
+    for (order, groups) in orders.iter().zip(&group_orders) {
+        assert_eq!(groups.len(), 7, "{}", order);
+        assert!(order.contains("b/1 b/2"), "{}", order);
+        assert!(order.contains("c/x c/y"), "{}", order);
+    }
+    // The benchmarks of a shuffled group, the groups of a function and the targets of a
+    // criterion_group! are shuffled
+    assert!(orders.iter().any(|order| !order.contains("a/1 a/2 a/3")));
+    assert!(group_orders.iter().any(|groups| !groups.contains("cde")));
+    assert!(group_orders.iter().any(|groups| groups.contains("gf")));
+
+    // A random seed is printed, so that the order can be repeated
+    let (random, stderr) = run(&dir, &["--shuffle"]);
+    let seed = stderr
+        .split("--shuffle=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap();
+    assert_eq!(run(&dir, &[&format!("--shuffle={}", seed)]).0, random);
+
+    // Benchmark runs record the seed and the order
+    let (benchmarked, _) = run(&dir, &["--bench", "--shuffle=42"]);
+    assert_eq!(benchmarked, shuffled);
+    let record = std::fs::read_to_string(dir.join("criterion/shuffle.json")).unwrap();
+    assert!(record.contains("\"seed\":42"), "{}", record);
+    let recorded_order = format!("\"{}\"", shuffled.replace(' ', "\",\""));
+    assert!(record.contains(&recorded_order), "{}", record);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// Runs the benchmarks in a child process, and returns the order they ran in and the standard error
+fn run(dir: &Path, args: &[&str]) -> (String, String) {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(args)
+        .env(DIR_VAR, dir)
+        .env("CARGO_TARGET_DIR", dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "{}\n{}", stdout, stderr);
+
+    let order = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("order: "))
+        .unwrap()
+        .to_owned();
+    (order, stderr)
+}
+
+// Records the first time each benchmark runs
+fn ran(id: String) {
+    let mut ran = RAN.lock().unwrap();
+    if !ran.contains(&id) {
+        ran.push(id);
+    }
+}
+
+fn criterion() -> Criterion {
+    Criterion::default()
+        .warm_up_time(Duration::from_millis(10))
+        .measurement_time(Duration::from_millis(20))
+        .sample_size(10)
+        .nresamples(2000)
+        .without_plots()
+        .configure_from_args()
+}
+
+fn first_group() {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    let mut c = criterion();
+    let mut group = c.benchmark_group("a");
+    let mut shuffled = group.shuffled();
+    for name in &["1", "2", "3"] {
+        shuffled.bench_function(*name, move |b| {
+            ran(format!("a/{}", name));
+            b.iter(|| 1)
+        });
+    }
+    shuffled.finish();
+    group.finish();
+
+    let mut group = c.benchmark_group("b");
+    for name in &["1", "2"] {
+        group.bench_function(*name, |b| {
+            ran(format!("b/{}", name));
+            b.iter(|| 1)
+        });
+    }
+    group.finish();
+}
+
+fn second_group() {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    let mut c = criterion();
+    let mut groups = c.shuffled_groups();
+    groups.benchmark_group("c", |group| {
+        let mut interleaved = group.interleaved(Interleaving::RoundRobin);
+        for name in &["x", "y"] {
+            interleaved.bench_function(*name, move |b| {
+                ran(format!("c/{}", name));
+                b.iter(|| 1)
+            });
+        }
+        interleaved.finish();
+    });
+    groups.benchmark_group("d", |group| {
+        let mut fixture = group.setup(|| SETUPS.fetch_add(1, Ordering::SeqCst));
+        for name in &["1", "2", "3"] {
+            fixture.bench_function(*name, move |b, _| {
+                ran(format!("d/{}", name));
+                b.iter(|| 1)
+            });
+        }
+        fixture.finish();
+    });
+    groups.benchmark_group("e", |group| {
+        for name in &["1", "2"] {
+            group.bench_function(*name, |b| {
+                ran(format!("e/{}", name));
+                b.iter(|| 1)
+            });
+        }
+    });
+    groups.finish();
+}
+
+criterion_group! {
+    name = third_group;
+    config = criterion();
+    targets = f_group, g_group
+}
+
+fn f_group(c: &mut Criterion) {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    c.bench_function("f/1", |b| {
+        ran("f/1".to_owned());
+        b.iter(|| 1)
+    });
+}
+
+fn g_group(c: &mut Criterion) {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    c.bench_function("g/1", |b| {
+        ran("g/1".to_owned());
+        b.iter(|| 1)
+    });
+}
@@ -0,0 +1,112 @@
+//! Tests for the time budget. This needs its own test binary without the default test harness,
+//! because the binary re-executes itself with `--list` to count the benchmarks. The benchmarks run
+//! in a child process, with two benchmark functions like those of `criterion_main!`, which have to
+//! share the budget.
+
+use criterion::{Criterion, Interleaving, SamplingMode};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const DIR_VAR: &str = "CRITERION_BUDGET_TEST_DIR";
+
+fn main() {
+    if let Some(dir) = std::env::var_os(DIR_VAR) {
+        if std::env::args().any(|arg| arg == "--list") {
+            let marker = format!("listed-{}", std::process::id());
+            std::fs::write(PathBuf::from(dir).join(marker), "").unwrap();
+        }
+        criterion::runner(&[&slow, &fast]);
+        return;
+    }
+
+    let dir = std::env::temp_dir().join(format!("criterion-budget-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--bench", "--time-budget", "2"])
+        .env(DIR_VAR, &dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}\n{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The benchmarks are counted once for the whole binary
+    let listed = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with("listed-")
+        })
+        .count();
+    assert_eq!(listed, 1);
+
+    // The slow benchmark used up the budget, so the benchmarks of the second function are scaled
+    // down to the minimum instead of getting a budget of their own
+    let fast = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("fast: "))
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+    assert!(fast < 1000, "the fast benchmarks took {} ms", fast);
+    for &(group, name) in &[
+        ("budget_slow", "sleep"),
+        ("budget", "sequential_1"),
+        ("budget_interleaved", "interleaved_1"),
+    ] {
+        assert!(dir
+            .join(group)
+            .join(name)
+            .join("new/estimates.json")
+            .is_file());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn criterion() -> Criterion {
+    Criterion::default()
+        .output_directory(&PathBuf::from(std::env::var_os(DIR_VAR).unwrap()))
+        .warm_up_time(Duration::from_secs(1))
+        .measurement_time(Duration::from_secs(2))
+        .sample_size(10)
+        .nresamples(2000)
+        .without_plots()
+        .configure_from_args()
+}
+
+// Takes at least 10 samples of 200 ms, more than the whole budget
+fn slow() {
+    let mut c = criterion();
+    let mut group = c.benchmark_group("budget_slow");
+    group.sampling_mode(SamplingMode::Flat);
+    group.bench_function("sleep", |b| {
+        b.iter(|| std::thread::sleep(Duration::from_millis(200)))
+    });
+    group.finish();
+}
+
+fn fast() {
+    let start = Instant::now();
+    let mut c = criterion();
+    let mut group = c.benchmark_group("budget");
+    for i in 0..2u64 {
+        group.bench_function(format!("sequential_{}", i), |b| b.iter(|| i + 1));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("budget_interleaved");
+    let mut interleaved = group.interleaved(Interleaving::RoundRobin);
+    for i in 0..2u64 {
+        interleaved.bench_function(format!("interleaved_{}", i), move |b| b.iter(|| i + 1));
+    }
+    interleaved.finish();
+    group.finish();
+
+    println!("fast: {}", start.elapsed().as_millis());
+}